@@ -0,0 +1,44 @@
+#!/usr/bin/env bash
+# Re-records tests/fixtures/research_local.json against the live providers, with the settings
+# tests/replay.rs replays it under. Needs Ollama serving deepseek-r1:8b on 127.0.0.1:11434 and
+# PERPLEXITY_API_KEY in the environment or .env; the run sends one billed Perplexity request.
+set -euo pipefail
+
+cd "$(dirname "$0")/.."
+
+topic="Do heat pumps work in cold climates?"
+fixture="tests/fixtures/research_local.json"
+work="$(mktemp -d)"
+trap 'rm -rf "$work"' EXIT
+
+cat > "$work/researcher.toml" <<EOF
+[http]
+max_retries = 0
+
+[http.fixtures]
+mode = "record"
+path = "$work/research_local.json"
+EOF
+
+# Environment variables override the file, so everything that shapes a request is pinned here;
+# empty values count as unset
+RESEARCHER_CONFIG="$work/researcher.toml" \
+RESEARCH_MODE=local \
+MAX_WEB_RESEARCH_LOOPS=1 \
+LOCAL_LLM=deepseek-r1:8b \
+SEARCH_API=perplexity \
+PERPLEXITY_BASE_URL=https://api.perplexity.ai \
+RESEARCH_LANGUAGE=English \
+BILINGUAL_SEARCH=false \
+NUM_PERSPECTIVES=2 \
+IMAGE_PROVIDER=none \
+NO_CACHE=true \
+RESEARCH_PROFILE= \
+LLM_FALLBACK= \
+MAX_COST_PER_RUN= \
+MAX_TOKENS_PER_RUN= \
+    cargo run --quiet -- research "$topic" > /dev/null
+
+mv "$work/research_local.json" "$fixture"
+echo "Recorded $fixture"
+echo "The assertions in tests/replay.rs quote the recorded answers; update them, then run: cargo test --test replay"
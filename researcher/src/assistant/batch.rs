@@ -0,0 +1,451 @@
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+use tokio::sync::{broadcast, Mutex, Semaphore};
+use tokio::time::Instant;
+
+use super::configuration::Configuration;
+use super::error::ResearchError;
+use super::graph::ResearchGraph;
+use super::history::{now, ResearchRun, RunHistory};
+use super::state::{StatusUpdate, SummaryStateInput, SummaryStateOutput};
+use crate::podcast::library::{is_valid_id, new_id, slugify};
+
+// Per-topic progress is relayed through a channel of the same size as the server's
+const ITEM_CHANNEL_CAPACITY: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchFormat {
+    Jsonl,
+    Csv,
+}
+
+impl BatchFormat {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "jsonl" | "ndjson" => Some(BatchFormat::Jsonl),
+            "csv" => Some(BatchFormat::Csv),
+            _ => None,
+        }
+    }
+}
+
+// One topic, optionally with its own profile and configuration overrides
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub topic: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(flatten)]
+    pub overrides: Map<String, Value>,
+}
+
+impl BatchItem {
+    fn config(&self, base: &Configuration) -> Result<Configuration> {
+        let profiled;
+        let base = match &self.profile {
+            Some(name) => {
+                profiled = base.with_profile(name)?;
+                &profiled
+            }
+            None => base,
+        };
+
+        if self.overrides.is_empty() {
+            return Ok(base.clone());
+        }
+        let config = base.with_overrides(&Value::Object(self.overrides.clone()))?;
+        config.check_request_limits(base)?;
+        Ok(config)
+    }
+}
+
+// JSONL lines are topic objects or bare strings; CSV needs a `topic` column and every other
+// column is an override, left blank to keep the default
+pub fn parse_batch(contents: &str, format: BatchFormat) -> Result<Vec<BatchItem>> {
+    let items = match format {
+        BatchFormat::Jsonl => contents.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                serde_json::from_str(line)
+                    .map_err(anyhow::Error::from)
+                    .and_then(item_from_value)
+                    .map_err(|e| anyhow::anyhow!("Line {}: {}", i + 1, e))
+            })
+            .collect::<Result<Vec<BatchItem>>>()?,
+        BatchFormat::Csv => {
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(contents.as_bytes());
+            let headers = reader.headers()?.clone();
+            if !headers.iter().any(|header| header == "topic") {
+                return Err(anyhow::anyhow!("The CSV header needs a 'topic' column"));
+            }
+
+            let mut items = Vec::new();
+            for (i, record) in reader.records().enumerate() {
+                let record = record?;
+                let mut fields = Map::new();
+                for (header, field) in headers.iter().zip(record.iter()) {
+                    if field.is_empty() {
+                        continue;
+                    }
+                    // Numbers and booleans are read as JSON, anything else stays a string
+                    let value = match header {
+                        "topic" | "profile" => Value::from(field),
+                        _ => serde_json::from_str(field).unwrap_or_else(|_| Value::from(field)),
+                    };
+                    fields.insert(header.to_string(), value);
+                }
+
+                let item: BatchItem = serde_json::from_value(Value::Object(fields))
+                    .map_err(|e| anyhow::anyhow!("Row {}: {}", i + 2, e))?;
+                items.push(item);
+            }
+            items
+        }
+    };
+
+    check_items(items)
+}
+
+// The `topics` of a JSON batch request: topic strings or objects, as on the lines of a JSONL file
+pub fn batch_from_values(topics: Vec<Value>) -> Result<Vec<BatchItem>> {
+    let items = topics.into_iter()
+        .enumerate()
+        .map(|(i, topic)| item_from_value(topic).map_err(|e| anyhow::anyhow!("Topic {}: {}", i + 1, e)))
+        .collect::<Result<Vec<BatchItem>>>()?;
+
+    check_items(items)
+}
+
+fn check_items(items: Vec<BatchItem>) -> Result<Vec<BatchItem>> {
+    if let Some(i) = items.iter().position(|item| item.topic.trim().is_empty()) {
+        return Err(anyhow::anyhow!("Topic {} is empty", i + 1));
+    }
+    if items.is_empty() {
+        return Err(anyhow::anyhow!("The batch has no topics"));
+    }
+    Ok(items)
+}
+
+fn item_from_value(value: Value) -> Result<BatchItem> {
+    match value {
+        Value::String(topic) => Ok(BatchItem {
+            topic,
+            profile: None,
+            overrides: Map::new(),
+        }),
+        value => Ok(serde_json::from_value(value)?),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub concurrency: usize,
+    // Spaces out run starts across the whole batch, on top of the concurrency limit
+    pub runs_per_minute: Option<u32>,
+    // Shared by every batch the server runs, so concurrent batches don't add up their concurrency
+    pub run_slots: Option<Arc<Semaphore>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchEntry {
+    pub topic: String,
+    pub status: BatchStatus,
+    #[serde(default)]
+    pub run_id: Option<String>,
+    #[serde(default)]
+    pub report_file: Option<String>,
+    #[serde(default)]
+    pub error: Option<String>,
+    // The ResearchError code, e.g. "rate_limited"
+    #[serde(default)]
+    pub error_code: Option<String>,
+    #[serde(default)]
+    pub duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchIndex {
+    pub id: String,
+    pub started_at: u64,
+    #[serde(default)]
+    pub finished_at: Option<u64>,
+    pub concurrency: usize,
+    pub completed: usize,
+    pub failed: usize,
+    pub entries: Vec<BatchEntry>,
+}
+
+impl BatchIndex {
+    // A markdown table of the batch, written next to index.json
+    pub fn render_markdown(&self) -> String {
+        let mut out = format!(
+            "# Research batch {}\n\n{} completed, {} failed, {} topics\n\n| # | Topic | Status | Report | Error |\n|---|---|---|---|---|\n",
+            self.id,
+            self.completed,
+            self.failed,
+            self.entries.len()
+        );
+        for (i, entry) in self.entries.iter().enumerate() {
+            let report = entry.report_file.as_deref()
+                .map(|file| format!("[{}]({})", file, file))
+                .unwrap_or_default();
+            out.push_str(&format!(
+                "| {} | {} | {:?} | {} | {} |\n",
+                i + 1,
+                entry.topic.replace('|', "\\|"),
+                entry.status,
+                report,
+                entry.error.as_deref().unwrap_or_default().replace('|', "\\|").replace('\n', " ")
+            ));
+        }
+        out
+    }
+}
+
+// Batches live under `<history>/batches/<id>/`: one markdown report per topic plus the index
+pub struct BatchStore {
+    dir: PathBuf,
+}
+
+impl BatchStore {
+    pub async fn open(history: &RunHistory) -> Result<Self> {
+        let dir = history.dir().join("batches");
+        fs::create_dir_all(&dir).await?;
+        Ok(Self { dir })
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<BatchIndex>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        match fs::read(self.dir.join(id).join("index.json")).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    // Creates the batch directory and its pending index so the batch can be polled right away
+    pub async fn create(&self, items: &[BatchItem], options: &BatchOptions) -> Result<BatchIndex> {
+        let started_at = now();
+        let index = BatchIndex {
+            id: new_id(started_at, &items[0].topic),
+            started_at,
+            finished_at: None,
+            concurrency: options.concurrency,
+            completed: 0,
+            failed: 0,
+            entries: items.iter()
+                .map(|item| BatchEntry {
+                    topic: item.topic.clone(),
+                    status: BatchStatus::Pending,
+                    run_id: None,
+                    report_file: None,
+                    error: None,
+                    error_code: None,
+                    duration_secs: None,
+                })
+                .collect(),
+        };
+
+        fs::create_dir_all(self.dir.join(&index.id)).await?;
+        self.save(&index).await?;
+        Ok(index)
+    }
+
+    async fn save(&self, index: &BatchIndex) -> Result<()> {
+        let dir = self.dir.join(&index.id);
+        fs::write(dir.join("index.json"), serde_json::to_vec_pretty(index)?).await?;
+        fs::write(dir.join("index.md"), index.render_markdown()).await?;
+        Ok(())
+    }
+
+    async fn save_report(&self, batch_id: &str, file_name: &str, report: &str) -> Result<()> {
+        fs::write(self.dir.join(batch_id).join(file_name), report).await?;
+        Ok(())
+    }
+}
+
+// Runs every topic, at most `concurrency` at a time; failures are recorded in the index
+// rather than stopping the batch
+pub async fn run_batch(
+    index: BatchIndex,
+    items: Vec<BatchItem>,
+    base: Configuration,
+    options: BatchOptions,
+    store: Arc<BatchStore>,
+    history: Arc<RunHistory>,
+    status_tx: Option<broadcast::Sender<StatusUpdate>>,
+) -> Result<BatchIndex> {
+    let total = items.len();
+    let batch_id = index.id.clone();
+    let index = Arc::new(Mutex::new(index));
+    let start_interval = options.runs_per_minute
+        .filter(|rpm| *rpm > 0)
+        .map(|rpm| Duration::from_secs_f64(60.0 / rpm as f64));
+    let next_start = Arc::new(Mutex::new(Instant::now()));
+
+    stream::iter(items.into_iter().enumerate())
+        .for_each_concurrent(options.concurrency.max(1), |(i, item)| {
+            let index = index.clone();
+            let next_start = next_start.clone();
+            let store = store.clone();
+            let history = history.clone();
+            let status_tx = status_tx.clone();
+            let run_slots = options.run_slots.clone();
+            let base = &base;
+            let batch_id = &batch_id;
+
+            async move {
+                if let Some(interval) = start_interval {
+                    let start_at = {
+                        let mut next_start = next_start.lock().await;
+                        let start_at = (*next_start).max(Instant::now());
+                        *next_start = start_at + interval;
+                        start_at
+                    };
+                    tokio::time::sleep_until(start_at).await;
+                }
+                // The semaphore is never closed
+                let _slot = match run_slots {
+                    Some(slots) => slots.acquire_owned().await.ok(),
+                    None => None,
+                };
+
+                let label = format!("{}/{}", i + 1, total);
+                update_entry(&index, &store, i, |entry| entry.status = BatchStatus::Running).await;
+                send_batch_status(&status_tx, format!("[{}] Starting research on: {}", label, item.topic));
+
+                let started_at = now();
+                let outcome = run_item(&item, base, &label, status_tx.clone()).await;
+                let outcome = match outcome {
+                    Ok((config, output)) => {
+                        let run = ResearchRun::new(started_at, &config, output);
+                        let file_name = format!("{:03}-{}.md", i + 1, slugify(&item.topic));
+                        let saved = async {
+                            history.save(&run).await?;
+                            store.save_report(batch_id, &file_name, &run.summary).await
+                        }.await;
+                        saved.map(|_| (run.id, file_name))
+                    }
+                    Err(e) => Err(e),
+                };
+
+                let message = match &outcome {
+                    Ok(_) => format!("[{}] Finished: {}", label, item.topic),
+                    Err(e) => format!("[{}] Failed: {}: {}", label, item.topic, e),
+                };
+                update_entry(&index, &store, i, |entry| {
+                    entry.duration_secs = Some(now().saturating_sub(started_at));
+                    match outcome {
+                        Ok((run_id, file_name)) => {
+                            entry.status = BatchStatus::Completed;
+                            entry.run_id = Some(run_id);
+                            entry.report_file = Some(file_name);
+                        }
+                        Err(e) => {
+                            entry.status = BatchStatus::Failed;
+                            entry.error = Some(e.to_string());
+                            entry.error_code = Some(ResearchError::classify(&e).code().to_string());
+                        }
+                    }
+                }).await;
+                send_batch_status(&status_tx, message);
+            }
+        })
+        .await;
+
+    let mut index = index.lock().await;
+    index.finished_at = Some(now());
+    store.save(&index).await?;
+    send_batch_status(&status_tx, format!(
+        "Batch {} finished: {} completed, {} failed",
+        index.id, index.completed, index.failed
+    ));
+    Ok(index.clone())
+}
+
+async fn run_item(
+    item: &BatchItem,
+    base: &Configuration,
+    label: &str,
+    status_tx: Option<broadcast::Sender<StatusUpdate>>,
+) -> Result<(Configuration, SummaryStateOutput)> {
+    let config = item.config(base)?;
+    let mut graph = ResearchGraph::new(config.clone());
+
+    // Prefix this topic's updates so concurrent runs can be told apart on the shared stream
+    let (item_tx, mut item_rx) = broadcast::channel::<StatusUpdate>(ITEM_CHANNEL_CAPACITY);
+    graph.set_status_sender(item_tx);
+    let label = label.to_string();
+    let relay = tokio::spawn(async move {
+        loop {
+            match item_rx.recv().await {
+                Ok(status) => {
+                    if let Some(tx) = &status_tx {
+                        let _ = tx.send(StatusUpdate {
+                            message: format!("[{}] {}", label, status.message),
+                            ..status
+                        });
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let output = graph.process_research(SummaryStateInput {
+        research_topic: item.topic.clone(),
+    }).await;
+    drop(graph);
+    let _ = relay.await;
+
+    Ok((config, output?))
+}
+
+async fn update_entry(
+    index: &Mutex<BatchIndex>,
+    store: &BatchStore,
+    i: usize,
+    update: impl FnOnce(&mut BatchEntry),
+) {
+    let mut index = index.lock().await;
+    update(&mut index.entries[i]);
+    index.completed = index.entries.iter().filter(|e| e.status == BatchStatus::Completed).count();
+    index.failed = index.entries.iter().filter(|e| e.status == BatchStatus::Failed).count();
+
+    if let Err(e) = store.save(&index).await {
+        tracing::error!(batch = %index.id, error = %e, "Failed to update batch index");
+    }
+}
+
+fn send_batch_status(status_tx: &Option<broadcast::Sender<StatusUpdate>>, message: String) {
+    if let Some(tx) = status_tx {
+        let _ = tx.send(StatusUpdate {
+            phase: "batch".to_string(),
+            message,
+            timestamp: now(),
+            ..Default::default()
+        });
+    }
+}
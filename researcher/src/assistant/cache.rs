@@ -0,0 +1,245 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+use super::configuration::Configuration;
+use super::metrics::metrics;
+
+// Long enough for any write in progress to have been renamed into place
+const PARTIAL_WRITE_GRACE: Duration = Duration::from_secs(60 * 60);
+
+// A response served from the cache instead of the provider, reported to whoever is listening
+#[derive(Debug, Clone)]
+pub struct CacheHit {
+    pub provider: &'static str,
+    pub model: String,
+    pub age: Duration,
+}
+
+pub type CacheListener = Arc<dyn Fn(&CacheHit) + Send + Sync>;
+
+tokio::task_local! {
+    static CACHE_LISTENER: CacheListener;
+}
+
+// Cache hits while `future` runs are reported to `listener`
+pub async fn with_cache_listener<F: Future>(listener: CacheListener, future: F) -> F::Output {
+    CACHE_LISTENER.scope(listener, future).await
+}
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<T> {
+    stored_at: u64,
+    provider: String,
+    model: String,
+    value: T,
+}
+
+// Provider responses keyed by a hash of everything that went into the request,
+// one `<provider>/<xx>/<hash>.json` file each
+pub struct ResponseCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl ResponseCache {
+    // None when the cache is off, or the run asked to skip it
+    pub fn for_provider(config: &Configuration, provider: &str) -> Option<Self> {
+        if !config.cache.enabled || config.no_cache {
+            return None;
+        }
+        Some(Self {
+            dir: PathBuf::from(&config.cache.dir).join(provider),
+            ttl: config.cache.ttl_for(provider),
+        })
+    }
+
+    pub fn key(provider: &str, model: &str, prompt: &str, params: &Value) -> String {
+        let request = serde_json::json!({
+            "provider": provider,
+            "model": model,
+            "prompt": prompt,
+            "params": params,
+        });
+        format!("{:x}", Sha256::digest(request.to_string().as_bytes()))
+    }
+
+    // The cached value and its age, if there's a fresh one
+    async fn get<T: DeserializeOwned>(&self, provider: &str, key: &str) -> Option<(T, Duration)> {
+        let path = self.path(key);
+        let data = match fs::read(&path).await {
+            Ok(data) => data,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(path = ?path, error = %e, "Couldn't read cached response");
+                }
+                metrics().cache_lookups.with_label_values(&[provider, "miss"]).inc();
+                return None;
+            }
+        };
+
+        let entry = match serde_json::from_slice::<CacheEntry<T>>(&data) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!(path = ?path, error = %e, "Removing unreadable cached response");
+                metrics().cache_lookups.with_label_values(&[provider, "miss"]).inc();
+                remove(&path).await;
+                return None;
+            }
+        };
+
+        let age = Duration::from_secs(now().saturating_sub(entry.stored_at));
+        if age > self.ttl {
+            metrics().cache_lookups.with_label_values(&[provider, "expired"]).inc();
+            remove(&path).await;
+            return None;
+        }
+        metrics().cache_lookups.with_label_values(&[provider, "hit"]).inc();
+        Some((entry.value, age))
+    }
+
+    // Failing to store a response costs a provider call next time, so it's only logged
+    async fn put<T: Serialize>(&self, key: &str, provider: &str, model: &str, value: &T) {
+        let entry = CacheEntry {
+            stored_at: now(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            value,
+        };
+        let path = self.path(key);
+        let result: Result<()> = async {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            // Written aside and renamed, so concurrent runs never read half an entry
+            let partial = path.with_extension(format!("{}.tmp", fastrand::u32(..)));
+            fs::write(&partial, serde_json::to_vec(&entry)?).await?;
+            fs::rename(&partial, &path).await?;
+            Ok(())
+        }.await;
+
+        if let Err(e) = result {
+            tracing::warn!(path = ?path, error = %e, "Couldn't cache response");
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(&key[..2]).join(format!("{}.json", key))
+    }
+}
+
+// Runs `call` unless a fresh response for the same request is cached, and caches what it returns.
+// The flag says whether the value came from the cache. Errors are never cached.
+pub async fn cached<T, F, Fut>(
+    config: &Configuration,
+    provider: &'static str,
+    model: &str,
+    prompt: &str,
+    params: Value,
+    call: F,
+) -> Result<(T, bool)>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let Some(cache) = ResponseCache::for_provider(config, provider) else {
+        return Ok((call().await?, false));
+    };
+
+    let key = ResponseCache::key(provider, model, prompt, &params);
+    if let Some((value, age)) = cache.get(provider, &key).await {
+        tracing::debug!(provider, model, age_secs = age.as_secs(), "Using cached response");
+        let hit = CacheHit { provider, model: model.to_string(), age };
+        let _ = CACHE_LISTENER.try_with(|listener| listener(&hit));
+        return Ok((value, true));
+    }
+
+    let value = call().await?;
+    cache.put(&key, provider, model, &value).await;
+    Ok((value, false))
+}
+
+// What `prune` found in the cache directory
+#[derive(Debug, Default, Serialize)]
+pub struct PruneReport {
+    pub removed: usize,
+    pub kept: usize,
+    pub freed_bytes: u64,
+}
+
+// Entries are only removed when they're looked up again, so ones that never are pile up until pruned
+pub async fn prune(config: &Configuration) -> Result<PruneReport> {
+    let mut report = PruneReport::default();
+    let root = PathBuf::from(&config.cache.dir);
+    let mut providers = match fs::read_dir(&root).await {
+        Ok(providers) => providers,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(report),
+        Err(e) => return Err(anyhow::anyhow!("Failed to read the response cache at {:?}: {}", root, e)),
+    };
+
+    while let Some(provider) = providers.next_entry().await? {
+        if !provider.file_type().await?.is_dir() {
+            continue;
+        }
+        let ttl = config.cache.ttl_for(&provider.file_name().to_string_lossy());
+        let mut shards = fs::read_dir(provider.path()).await?;
+        while let Some(shard) = shards.next_entry().await? {
+            if !shard.file_type().await?.is_dir() {
+                continue;
+            }
+            let mut files = fs::read_dir(shard.path()).await?;
+            while let Some(file) = files.next_entry().await? {
+                let path = file.path();
+                let metadata = file.metadata().await?;
+                if is_stale(&path, &metadata, ttl).await {
+                    fs::remove_file(&path).await?;
+                    report.removed += 1;
+                    report.freed_bytes += metadata.len();
+                } else {
+                    report.kept += 1;
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+// Expired or unreadable entries, and partial writes a crashed run left behind
+async fn is_stale(path: &Path, metadata: &std::fs::Metadata, ttl: Duration) -> bool {
+    if path.extension().is_some_and(|extension| extension == "tmp") {
+        let age = metadata.modified().ok().and_then(|modified| modified.elapsed().ok()).unwrap_or_default();
+        return age > PARTIAL_WRITE_GRACE;
+    }
+    #[derive(Deserialize)]
+    struct Stored {
+        stored_at: u64,
+    }
+    match fs::read(path).await.ok().and_then(|data| serde_json::from_slice::<Stored>(&data).ok()) {
+        Some(stored) => Duration::from_secs(now().saturating_sub(stored.stored_at)) > ttl,
+        None => true,
+    }
+}
+
+// A failed removal only means the entry is tried again next time
+async fn remove(path: &Path) {
+    if let Err(e) = fs::remove_file(path).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            tracing::warn!(path = ?path, error = %e, "Couldn't remove cached response");
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
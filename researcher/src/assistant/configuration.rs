@@ -1,31 +1,412 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
-use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+use std::time::Duration;
+use serde_json::{Map, Value};
 use anyhow::Result;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+use super::state::TRACK_NAMES;
+
+// Used when RESEARCHER_CONFIG doesn't point at another file
+const DEFAULT_CONFIG_FILE: &str = "researcher.toml";
+
+// Environment variables and the configuration keys they set
+const ENV_VARS: &[(&str, &str, EnvKind)] = &[
+    ("MAX_WEB_RESEARCH_LOOPS", "max_web_research_loops", EnvKind::Integer),
+    ("LOCAL_LLM", "local_llm", EnvKind::String),
+    ("SEARCH_API", "search_api", EnvKind::Choice),
+    ("PERPLEXITY_API_KEY", "perplexity_api_key", EnvKind::String),
+    ("PERPLEXITY_BASE_URL", "perplexity_base_url", EnvKind::String),
+    ("RESEARCH_MODE", "research_mode", EnvKind::Choice),
+    ("GROQ_API_KEY", "groq_api_key", EnvKind::String),
+    ("GROQ_MODEL", "groq_model", EnvKind::String),
+    ("OPENAI_BASE_URL", "openai_base_url", EnvKind::String),
+    ("OPENAI_API_KEY", "openai_api_key", EnvKind::String),
+    ("OPENAI_MODEL", "openai_model", EnvKind::String),
+    ("LLM_FALLBACK", "llm_fallback", EnvKind::List),
+    ("PODCAST_LIBRARY_DIR", "podcast_library_dir", EnvKind::String),
+    ("RESEARCH_HISTORY_DIR", "research_history_dir", EnvKind::String),
+    ("PODCAST_TITLE", "podcast_title", EnvKind::String),
+    ("PUBLIC_BASE_URL", "public_base_url", EnvKind::String),
+    ("RESEARCH_LANGUAGE", "language", EnvKind::String),
+    ("BILINGUAL_SEARCH", "bilingual_search", EnvKind::Boolean),
+    ("IMAGE_PROVIDER", "image_provider", EnvKind::Choice),
+    ("LUMAAI_API_KEY", "luma_api_key", EnvKind::String),
+    ("NUM_PERSPECTIVES", "num_perspectives", EnvKind::Integer),
+    ("OUTPUT_FORMAT", "output_format", EnvKind::Choice),
+    ("RESEARCH_PROFILE", "profile", EnvKind::String),
+    ("MAX_COST_PER_RUN", "max_cost_per_run", EnvKind::Number),
+    ("MAX_TOKENS_PER_RUN", "max_tokens_per_run", EnvKind::Integer),
+    ("PARTIAL_RESULTS", "partial_results", EnvKind::Choice),
+    ("NO_CACHE", "no_cache", EnvKind::Boolean),
+];
+
+// Guards against runaway `inherits` chains
+const MAX_PROFILE_DEPTH: usize = 8;
+
+// Secrets and server-wide paths can't be changed by individual requests
+const SERVER_ONLY_KEYS: &[&str] = &[
+    "perplexity_api_key",
+    "perplexity_base_url",
+    "groq_api_key",
+    "openai_api_key",
+    "openai_base_url",
+    "luma_api_key",
+    "podcast_library_dir",
+    "research_history_dir",
+    "podcast_title",
+    "public_base_url",
+    "limits",
+    "prices",
+    "http",
+    "cache",
+    "prompts",
+];
+
+#[derive(Debug, Clone, Copy)]
+enum EnvKind {
+    String,
+    // One of an enum's lowercase names, matched case-insensitively
+    Choice,
+    Integer,
+    Number,
+    Boolean,
+    // Comma separated choices
+    List,
+}
+
+// Where the current value of a configuration key came from, lowest precedence first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "source", rename_all = "lowercase")]
+pub enum ConfigSource {
+    Default,
+    File { path: String },
+    Env { var: String },
+    Profile { name: String },
+    Runtime,
+    Request,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File { path } => write!(f, "file {}", path),
+            ConfigSource::Env { var } => write!(f, "env {}", var),
+            ConfigSource::Profile { name } => write!(f, "profile {}", name),
+            ConfigSource::Runtime => write!(f, "PUT /config"),
+            ConfigSource::Request => write!(f, "request"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum SearchAPI {
+    #[default]
     #[serde(rename = "perplexity")]
     Perplexity,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
 pub enum ResearchMode {
+    #[default]
     #[serde(rename = "local")]
     Local,
     #[serde(rename = "remote")]
     Remote,
 }
 
-impl Default for ResearchMode {
+// Where LLM calls can be sent; Groq and Ollama are also what the remote and local research modes use
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum LlmProvider {
+    #[serde(rename = "groq")]
+    Groq,
+    #[serde(rename = "openai")]
+    OpenAi,
+    #[serde(rename = "ollama")]
+    Ollama,
+}
+
+impl LlmProvider {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LlmProvider::Groq => "groq",
+            LlmProvider::OpenAi => "openai",
+            LlmProvider::Ollama => "ollama",
+        }
+    }
+}
+
+// How many parallel research tracks must succeed for the run to count as a success
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub enum PartialResults {
+    // Report on whatever tracks finished, as long as one did
+    #[default]
+    #[serde(rename = "any")]
+    Any,
+    #[serde(rename = "majority")]
+    Majority,
+    #[serde(rename = "all")]
+    All,
+}
+
+impl PartialResults {
+    pub fn accepts(&self, succeeded: usize, total: usize) -> bool {
+        match self {
+            PartialResults::Any => succeeded > 0,
+            PartialResults::Majority => succeeded * 2 > total,
+            PartialResults::All => succeeded == total,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum ImageProviderKind {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "luma")]
+    Luma,
+    #[serde(rename = "placeholder")]
+    Placeholder,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    #[serde(rename = "markdown")]
+    Markdown,
+    #[serde(rename = "json")]
+    Json,
+}
+
+// A named set of configuration values, optionally building on another profile
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inherits: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(flatten)]
+    pub settings: Map<String, Value>,
+}
+
+impl Profile {
+    fn builtin(description: &str, inherits: Option<&str>, settings: Value) -> Self {
+        Self {
+            inherits: inherits.map(|name| name.to_string()),
+            description: Some(description.to_string()),
+            settings: settings.as_object().cloned().unwrap_or_default(),
+        }
+    }
+}
+
+// Profiles available without any configuration; `[profiles.<name>]` tables in researcher.toml
+// add to these or replace them by name
+pub fn builtin_profiles() -> BTreeMap<String, Profile> {
+    BTreeMap::from([
+        ("fast".to_string(), Profile::builtin(
+            "Groq with a single research loop",
+            None,
+            serde_json::json!({ "research_mode": "remote", "max_web_research_loops": 1 }),
+        )),
+        ("deep".to_string(), Profile::builtin(
+            "More research loops across three perspectives",
+            None,
+            serde_json::json!({ "max_web_research_loops": 4, "num_perspectives": 3 }),
+        )),
+        ("offline".to_string(), Profile::builtin(
+            "Local Ollama model with placeholder visuals",
+            None,
+            serde_json::json!({ "research_mode": "local", "image_provider": "placeholder" }),
+        )),
+    ])
+}
+
+// What a model costs in USD, per million tokens plus a flat fee per request
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_million: f64,
+    #[serde(default)]
+    pub output_per_million: f64,
+    #[serde(default)]
+    pub per_request: f64,
+}
+
+impl ModelPrice {
+    const fn new(input_per_million: f64, output_per_million: f64, per_request: f64) -> Self {
+        Self { input_per_million, output_per_million, per_request }
+    }
+}
+
+// List prices when these models were added; `[prices.<model>]` tables in researcher.toml
+// add to these or replace them by name
+pub fn builtin_prices() -> BTreeMap<String, ModelPrice> {
+    BTreeMap::from([
+        ("sonar-pro".to_string(), ModelPrice::new(3.0, 15.0, 0.006)),
+        ("mixtral-8x7b-32768".to_string(), ModelPrice::new(0.24, 0.24, 0.0)),
+        ("llama-3.3-70b-versatile".to_string(), ModelPrice::new(0.59, 0.79, 0.0)),
+        ("llama-3.1-8b-instant".to_string(), ModelPrice::new(0.05, 0.08, 0.0)),
+        ("deepseek-r1-distill-llama-70b".to_string(), ModelPrice::new(0.75, 0.99, 0.0)),
+        ("gpt-4o-mini".to_string(), ModelPrice::new(0.15, 0.6, 0.0)),
+    ])
+}
+
+// Timeouts and retries for calls to Groq, Perplexity, Ollama and Luma
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpPolicy {
+    // Retries after the first attempt, for timeouts, connection errors, 429s and 5xx responses
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    #[serde(default = "default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    // Also caps how long a Retry-After header can make us wait
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+    // Seconds per provider, on top of the built-in ones
+    #[serde(default)]
+    pub timeouts: BTreeMap<String, u64>,
+    // Per provider, shared by every research run and batch in the process
+    #[serde(default)]
+    pub rate_limits: BTreeMap<String, RateLimit>,
+    // Records provider traffic to a fixture file, or answers from one without touching the network
+    pub fixtures: Option<FixtureConfig>,
+    // Scripted providers set by MockProviders::install, for tests. Never read from or written to a file.
+    #[cfg(feature = "testing")]
+    #[serde(skip)]
+    pub mock: Option<std::sync::Arc<super::mock::MockProviders>>,
+}
+
+impl Default for HttpPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: default_max_retries(),
+            initial_backoff_ms: default_initial_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            timeouts: BTreeMap::new(),
+            rate_limits: BTreeMap::new(),
+            fixtures: None,
+            #[cfg(feature = "testing")]
+            mock: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FixtureConfig {
+    pub mode: FixtureMode,
+    pub path: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureMode {
+    Record,
+    Replay,
+}
+
+// Unset limits don't apply
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub requests_per_minute: Option<u32>,
+    pub tokens_per_minute: Option<u32>,
+    pub max_in_flight: Option<usize>,
+}
+
+// Responses from LLM and search providers, reused while they're fresh
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_cache_dir")]
+    pub dir: String,
+    #[serde(default = "default_cache_ttl_secs")]
+    pub ttl_secs: u64,
+    // Seconds per provider, on top of the built-in ones
+    #[serde(default)]
+    pub ttls: BTreeMap<String, u64>,
+}
+
+impl Default for CacheConfig {
     fn default() -> Self {
-        ResearchMode::Local
+        Self {
+            enabled: false,
+            dir: default_cache_dir(),
+            ttl_secs: default_cache_ttl_secs(),
+            ttls: BTreeMap::new(),
+        }
+    }
+}
+
+// Prompt templates; see prompts/ for the built-in ones and the variables each gets
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptConfig {
+    // Templates found here replace the built-in ones of the same name
+    #[serde(default)]
+    pub dir: Option<String>,
+    // Saved with each run to tell prompt variants apart, defaults to "custom" when any template is replaced
+    #[serde(default)]
+    pub version: Option<String>,
+}
+
+impl CacheConfig {
+    pub fn ttl_for(&self, provider: &str) -> Duration {
+        let secs = self.ttls.get(provider).copied().unwrap_or(match provider {
+            // Search answers go stale much sooner than model output does
+            "perplexity" => 24 * 60 * 60,
+            _ => self.ttl_secs,
+        });
+        Duration::from_secs(secs)
+    }
+}
+
+impl RateLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.requests_per_minute.is_none() && self.tokens_per_minute.is_none() && self.max_in_flight.is_none()
+    }
+}
+
+impl HttpPolicy {
+    pub fn timeout_for(&self, provider: &str) -> Duration {
+        let secs = self.timeouts.get(provider).copied().unwrap_or(match provider {
+            // Local models on modest hardware can take minutes per response
+            "ollama" => 600,
+            "perplexity" => 120,
+            _ => 60,
+        });
+        Duration::from_secs(secs)
     }
 }
 
-impl Default for SearchAPI {
+// One set of values applied on top of the layers before it
+#[derive(Debug, Clone)]
+pub struct ConfigLayer {
+    pub source: ConfigSource,
+    pub values: Map<String, Value>,
+}
+
+// Upper bounds on what a single request may ask for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchLimits {
+    #[serde(default = "default_max_request_loops")]
+    pub max_web_research_loops: i32,
+    #[serde(default = "default_max_request_perspectives")]
+    pub max_perspectives: usize,
+    // Research runs a batch may have in flight at once, and all of the server's batches together
+    #[serde(default = "default_max_batch_concurrency")]
+    pub max_batch_concurrency: usize,
+}
+
+impl Default for ResearchLimits {
     fn default() -> Self {
-        SearchAPI::Perplexity
+        Self {
+            max_web_research_loops: default_max_request_loops(),
+            max_perspectives: default_max_request_perspectives(),
+            max_batch_concurrency: default_max_batch_concurrency(),
+        }
     }
 }
 
@@ -37,12 +418,110 @@ pub struct Configuration {
     pub local_llm: String,
     #[serde(default)]
     pub search_api: SearchAPI,
+    #[serde(default)]
     pub perplexity_api_key: String,
+    #[serde(default = "default_perplexity_base_url")]
+    pub perplexity_base_url: String,
     #[serde(default)]
     pub research_mode: ResearchMode,
     pub groq_api_key: Option<String>,
     #[serde(default = "default_groq_model")]
     pub groq_model: String,
+    // Any OpenAI-compatible chat completions server; the key is optional for self-hosted ones
+    #[serde(default = "default_openai_base_url")]
+    pub openai_base_url: String,
+    pub openai_api_key: Option<String>,
+    #[serde(default = "default_openai_model")]
+    pub openai_model: String,
+    // Providers tried in order when the research mode's own provider fails a call
+    #[serde(default)]
+    pub llm_fallback: Vec<LlmProvider>,
+    #[serde(default = "default_podcast_library_dir")]
+    pub podcast_library_dir: String,
+    #[serde(default = "default_research_history_dir")]
+    pub research_history_dir: String,
+    #[serde(default = "default_podcast_title")]
+    pub podcast_title: String,
+    pub public_base_url: Option<String>,
+    #[serde(default = "default_language")]
+    pub language: String,
+    #[serde(default)]
+    pub bilingual_search: bool,
+    #[serde(default)]
+    pub image_provider: ImageProviderKind,
+    pub luma_api_key: Option<String>,
+    #[serde(default = "default_num_perspectives")]
+    pub num_perspectives: usize,
+    #[serde(default)]
+    pub partial_results: PartialResults,
+    #[serde(default)]
+    pub output_format: OutputFormat,
+    #[serde(default)]
+    pub limits: ResearchLimits,
+    // Research stops once a run has spent this much (USD) or used this many tokens
+    pub max_cost_per_run: Option<f64>,
+    pub max_tokens_per_run: Option<u64>,
+    #[serde(default)]
+    pub prices: BTreeMap<String, ModelPrice>,
+    #[serde(default)]
+    pub http: HttpPolicy,
+    #[serde(default)]
+    pub cache: CacheConfig,
+    // Skips the response cache for this run, neither reading nor writing it
+    #[serde(default)]
+    pub no_cache: bool,
+    #[serde(default)]
+    pub prompts: PromptConfig,
+    pub profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    #[serde(skip)]
+    pub provenance: BTreeMap<String, ConfigSource>,
+    // Every layer applied on top of the defaults, so a profile can be swapped in later
+    #[serde(skip)]
+    pub layers: Vec<ConfigLayer>,
+}
+
+impl Default for Configuration {
+    fn default() -> Self {
+        Self {
+            max_web_research_loops: default_max_web_research_loops(),
+            local_llm: default_local_llm(),
+            search_api: SearchAPI::default(),
+            perplexity_api_key: String::new(),
+            perplexity_base_url: default_perplexity_base_url(),
+            research_mode: ResearchMode::default(),
+            groq_api_key: None,
+            groq_model: default_groq_model(),
+            openai_base_url: default_openai_base_url(),
+            openai_api_key: None,
+            openai_model: default_openai_model(),
+            llm_fallback: Vec::new(),
+            podcast_library_dir: default_podcast_library_dir(),
+            research_history_dir: default_research_history_dir(),
+            podcast_title: default_podcast_title(),
+            public_base_url: None,
+            language: default_language(),
+            bilingual_search: false,
+            image_provider: ImageProviderKind::default(),
+            luma_api_key: None,
+            num_perspectives: default_num_perspectives(),
+            partial_results: PartialResults::default(),
+            output_format: OutputFormat::default(),
+            limits: ResearchLimits::default(),
+            max_cost_per_run: None,
+            max_tokens_per_run: None,
+            prices: BTreeMap::new(),
+            http: HttpPolicy::default(),
+            cache: CacheConfig::default(),
+            no_cache: false,
+            prompts: PromptConfig::default(),
+            profile: None,
+            profiles: BTreeMap::new(),
+            provenance: BTreeMap::new(),
+            layers: Vec::new(),
+        }
+    }
 }
 
 fn default_max_web_research_loops() -> i32 {
@@ -57,69 +536,505 @@ fn default_groq_model() -> String {
     "mixtral-8x7b-32768".to_string()
 }
 
+fn default_perplexity_base_url() -> String {
+    "https://api.perplexity.ai".to_string()
+}
+
+fn default_openai_base_url() -> String {
+    "https://api.openai.com/v1".to_string()
+}
+
+fn default_openai_model() -> String {
+    "gpt-4o-mini".to_string()
+}
+
+fn default_num_perspectives() -> usize {
+    2
+}
+
+fn default_max_request_loops() -> i32 {
+    5
+}
+
+fn default_max_request_perspectives() -> usize {
+    4
+}
+
+fn default_max_batch_concurrency() -> usize {
+    4
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+fn default_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    30_000
+}
+
+fn default_language() -> String {
+    "English".to_string()
+}
+
+fn default_podcast_library_dir() -> String {
+    "episodes".to_string()
+}
+
+fn default_research_history_dir() -> String {
+    "history".to_string()
+}
+
+fn default_cache_dir() -> String {
+    "cache".to_string()
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    7 * 24 * 60 * 60
+}
+
+fn default_podcast_title() -> String {
+    "ZU-LM Research Podcast".to_string()
+}
+
 impl Configuration {
+    // Layers built-in defaults < researcher.toml < environment < the selected profile < the given JSON overrides
     pub fn from_runnable_config(config: Option<&Value>) -> Result<Self> {
-        println!("Loading configuration...");
-        
-        // Print current working directory and all environment variables for debugging
-        println!("Current directory: {:?}", std::env::current_dir()?);
-        println!("Checking environment variables...");
-        
-        // Check and print all relevant environment variables
-        let env_vars = [
-            "PERPLEXITY_API_KEY",
-            "LOCAL_LLM",
-            "MAX_WEB_RESEARCH_LOOPS",
-            "SEARCH_API",
-            "GROQ_API_KEY",
-            "GROQ_MODEL",
-            "RESEARCH_MODE",
+        tracing::debug!("Loading configuration");
+
+        let mut layers = ConfigLayers::defaults()?;
+
+        let (path, explicit) = match env::var("RESEARCHER_CONFIG") {
+            Ok(path) => (path, true),
+            Err(_) => (DEFAULT_CONFIG_FILE.to_string(), false),
+        };
+        layers.apply_file(Path::new(&path), explicit)?;
+        layers.apply_env()?;
+
+        // A profile named in the file or RESEARCH_PROFILE is applied on top of both
+        if let Some(Value::String(name)) = layers.values.get("profile").cloned() {
+            let chain = layers.build()?.profile_chain(&name)?;
+            layers.apply_profile_chain(chain)?;
+        }
+
+        if let Some(overrides) = config {
+            layers.apply(overrides, ConfigSource::Request)?;
+        }
+
+        let configuration = layers.build()?;
+
+        if configuration.perplexity_api_key.is_empty() {
+            return Err(anyhow::anyhow!(
+                "perplexity_api_key is not set - add PERPLEXITY_API_KEY to your .env file or perplexity_api_key to {}",
+                path
+            ));
+        }
+
+        for (key, source) in &configuration.provenance {
+            tracing::debug!(key = %key, source = %source, "Config value");
+        }
+
+        Ok(configuration)
+    }
+
+    // Applies per-request JSON overrides on top of this configuration
+    pub fn with_overrides(&self, overrides: &Value) -> Result<Self> {
+        self.with_layer(overrides, ConfigSource::Request)
+    }
+
+    pub fn with_layer(&self, layer: &Value, source: ConfigSource) -> Result<Self> {
+        let mut layers = ConfigLayers::from_config(self)?;
+        layers.apply(layer, source)?;
+        let config = layers.build()?;
+        Ok(self.carry_over(config))
+    }
+
+    // Checks the values a request overrode against the server-side limits. `base` is the
+    // configuration the request started from, server and profile included.
+    pub fn check_request_limits(&self, base: &Configuration) -> Result<()> {
+        let from_request = |key: &str| self.source_of(key) == ConfigSource::Request;
+
+        if from_request("max_web_research_loops")
+            && !(1..=self.limits.max_web_research_loops).contains(&self.max_web_research_loops)
+        {
+            return Err(anyhow::anyhow!(
+                "max_web_research_loops must be between 1 and {}",
+                self.limits.max_web_research_loops
+            ));
+        }
+
+        let max_perspectives = self.limits.max_perspectives.min(TRACK_NAMES.len());
+        if from_request("num_perspectives") && !(1..=max_perspectives).contains(&self.num_perspectives) {
+            return Err(anyhow::anyhow!("num_perspectives must be between 1 and {}", max_perspectives));
+        }
+
+        let names = [
+            ("local_llm", &self.local_llm),
+            ("groq_model", &self.groq_model),
+            ("openai_model", &self.openai_model),
+            ("language", &self.language),
         ];
+        for (key, value) in names {
+            if from_request(key) && value.trim().is_empty() {
+                return Err(anyhow::anyhow!("{} can't be empty", key));
+            }
+        }
+
+        // A request may tighten the run budget, never raise or remove it
+        if from_request("max_cost_per_run") {
+            match (self.max_cost_per_run, base.max_cost_per_run) {
+                (Some(cost), _) if cost <= 0.0 => return Err(anyhow::anyhow!("max_cost_per_run must be positive")),
+                (Some(cost), Some(limit)) if cost > limit => {
+                    return Err(anyhow::anyhow!("max_cost_per_run can't be raised above {}", limit));
+                }
+                (None, Some(limit)) => {
+                    return Err(anyhow::anyhow!("max_cost_per_run can't be removed, the limit is {}", limit));
+                }
+                _ => {}
+            }
+        }
+        if from_request("max_tokens_per_run") {
+            match (self.max_tokens_per_run, base.max_tokens_per_run) {
+                (Some(0), _) => return Err(anyhow::anyhow!("max_tokens_per_run must be positive")),
+                (Some(tokens), Some(limit)) if tokens > limit => {
+                    return Err(anyhow::anyhow!("max_tokens_per_run can't be raised above {}", limit));
+                }
+                (None, Some(limit)) => {
+                    return Err(anyhow::anyhow!("max_tokens_per_run can't be removed, the limit is {}", limit));
+                }
+                _ => {}
+            }
+        }
 
-        for var in env_vars.iter() {
-            match env::var(var) {
-                Ok(value) => println!("Found env var {}: {}", var, 
-                    if var.contains("KEY") { "***".to_string() } else { value.clone() }
-                ),
-                Err(_) => println!("Warning: {} not found", var),
+        if from_request("research_mode") && self.research_mode == ResearchMode::Remote && self.groq_api_key.is_none() {
+            return Err(anyhow::anyhow!("Remote research mode isn't available: GROQ_API_KEY is not configured"));
+        }
+        if from_request("llm_fallback") && self.llm_fallback.contains(&LlmProvider::Groq) && self.groq_api_key.is_none() {
+            return Err(anyhow::anyhow!("Groq can't be used as a fallback: GROQ_API_KEY is not configured"));
+        }
+
+        Ok(())
+    }
+
+    // The research mode's provider followed by the fallbacks, each once
+    pub fn llm_chain(&self) -> Vec<LlmProvider> {
+        let primary = match self.research_mode {
+            ResearchMode::Local => LlmProvider::Ollama,
+            ResearchMode::Remote => LlmProvider::Groq,
+        };
+        let mut chain = vec![primary];
+        for provider in &self.llm_fallback {
+            if !chain.contains(provider) {
+                chain.push(*provider);
             }
         }
+        chain
+    }
+
+    pub fn model_for(&self, provider: LlmProvider) -> &str {
+        match provider {
+            LlmProvider::Groq => &self.groq_model,
+            LlmProvider::OpenAi => &self.openai_model,
+            LlmProvider::Ollama => &self.local_llm,
+        }
+    }
+
+    // Prices from researcher.toml win over the built-in ones
+    pub fn price_for(&self, model: &str) -> Option<ModelPrice> {
+        self.prices.get(model).copied()
+            .or_else(|| builtin_prices().get(model).copied())
+    }
 
-        let _unused = config;
+    pub fn available_profiles(&self) -> BTreeMap<String, Profile> {
+        let mut profiles = builtin_profiles();
+        profiles.extend(self.profiles.clone());
+        profiles
+    }
+
+    // Rebuilds the configuration with `name` applied over the file and environment layers.
+    // Earlier runtime changes are dropped, so switching profiles always starts from the same base.
+    pub fn with_profile(&self, name: &str) -> Result<Self> {
+        let chain = self.profile_chain(name)?;
+
+        let mut layers = ConfigLayers::defaults()?;
+        for layer in &self.layers {
+            if matches!(layer.source, ConfigSource::File { .. } | ConfigSource::Env { .. }) {
+                layers.apply(&Value::Object(layer.values.clone()), layer.source.clone())?;
+            }
+        }
+        layers.apply_profile_chain(chain)?;
+        let config = layers.build()?;
+        Ok(self.carry_over(config))
+    }
 
-        // Check each required environment variable with specific error messages
-        let perplexity_api_key = env::var("PERPLEXITY_API_KEY")
-            .map_err(|_| anyhow::anyhow!("PERPLEXITY_API_KEY environment variable not found - please add this to your .env file"))?;
+    // Mocks aren't serialized, so they'd be lost rebuilding the layers
+    #[cfg(feature = "testing")]
+    fn carry_over(&self, mut config: Configuration) -> Configuration {
+        config.http.mock = self.http.mock.clone();
+        config
+    }
 
-        let local_llm = env::var("LOCAL_LLM")
-            .map_err(|_| anyhow::anyhow!("LOCAL_LLM environment variable not found - please add this to your .env file"))?;
+    #[cfg(not(feature = "testing"))]
+    fn carry_over(&self, config: Configuration) -> Configuration {
+        config
+    }
 
-        let max_web_research_loops = env::var("MAX_WEB_RESEARCH_LOOPS")
-            .ok()
-            .and_then(|v| v.parse().ok())
-            .unwrap_or_else(default_max_web_research_loops);
+    // The profile and its ancestors, root first
+    fn profile_chain(&self, name: &str) -> Result<Vec<(String, Profile)>> {
+        let profiles = self.available_profiles();
+        let mut chain: Vec<(String, Profile)> = Vec::new();
+        let mut next = Some(name.to_string());
 
-        // Always use Perplexity as the search API
-        let search_api = SearchAPI::Perplexity;
+        while let Some(current) = next {
+            if chain.iter().any(|(seen, _)| *seen == current) {
+                return Err(anyhow::anyhow!("Profile '{}' inherits from itself", current));
+            }
+            if chain.len() >= MAX_PROFILE_DEPTH {
+                return Err(anyhow::anyhow!("Profile '{}' has more than {} ancestors", name, MAX_PROFILE_DEPTH));
+            }
 
-        // Get optional Groq settings
-        let groq_api_key = env::var("GROQ_API_KEY").ok();
-        let groq_model = env::var("GROQ_MODEL").unwrap_or_else(|_| default_groq_model());
+            let profile = profiles.get(&current).cloned().ok_or_else(|| {
+                anyhow::anyhow!(
+                    "Unknown profile '{}' (available: {})",
+                    current,
+                    profiles.keys().cloned().collect::<Vec<String>>().join(", ")
+                )
+            })?;
+            next = profile.inherits.clone();
+            chain.push((current, profile));
+        }
 
-        // Get research mode
-        let research_mode = match env::var("RESEARCH_MODE").as_deref() {
-            Ok("remote") => ResearchMode::Remote,
-            _ => ResearchMode::Local,
+        chain.reverse();
+        Ok(chain)
+    }
+
+    pub fn source_of(&self, key: &str) -> ConfigSource {
+        self.provenance.get(key).cloned().unwrap_or(ConfigSource::Default)
+    }
+}
+
+struct ConfigLayers {
+    values: Map<String, Value>,
+    provenance: BTreeMap<String, ConfigSource>,
+    layers: Vec<ConfigLayer>,
+}
+
+impl ConfigLayers {
+    fn defaults() -> Result<Self> {
+        let mut layers = Self::from_config(&Configuration::default())?;
+        layers.provenance = layers.values.keys()
+            .map(|key| (key.clone(), ConfigSource::Default))
+            .collect();
+        Ok(layers)
+    }
+
+    fn from_config(config: &Configuration) -> Result<Self> {
+        let Value::Object(values) = serde_json::to_value(config)? else {
+            return Err(anyhow::anyhow!("Configuration did not serialize to an object"));
         };
 
-        Ok(Configuration {
-            max_web_research_loops,
-            local_llm,
-            search_api,
-            perplexity_api_key,
-            research_mode,
-            groq_api_key,
-            groq_model,
+        Ok(Self {
+            values,
+            provenance: config.provenance.clone(),
+            layers: config.layers.clone(),
         })
     }
-} 
\ No newline at end of file
+
+    fn apply_file(&mut self, path: &Path, explicit: bool) -> Result<()> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            // The file is optional unless RESEARCHER_CONFIG asked for it
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(()),
+            Err(e) => return Err(anyhow::anyhow!("Failed to read config file {:?}: {}", path, e)),
+        };
+
+        let table: toml::Table = toml::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("Failed to parse config file {:?}: {}", path, e))?;
+
+        self.apply(
+            &serde_json::to_value(table)?,
+            ConfigSource::File { path: path.display().to_string() },
+        )
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        let mut layer = Map::new();
+
+        for (var, key, kind) in ENV_VARS {
+            let Ok(raw) = env::var(var) else {
+                continue;
+            };
+            let Some(value) = env_value(var, &raw, *kind)? else {
+                continue;
+            };
+
+            // Apply each variable separately so its provenance names the variable
+            layer.clear();
+            layer.insert(key.to_string(), value);
+            self.apply(&Value::Object(layer.clone()), ConfigSource::Env { var: var.to_string() })?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_profile_chain(&mut self, chain: Vec<(String, Profile)>) -> Result<()> {
+        let Some(selected) = chain.last().map(|(name, _)| name.clone()) else {
+            return Ok(());
+        };
+
+        for (name, profile) in chain {
+            self.apply(&Value::Object(profile.settings), ConfigSource::Profile { name })?;
+        }
+
+        // Record which profile is active, rather than one of its ancestors
+        let mut active = Map::new();
+        active.insert("profile".to_string(), Value::String(selected.clone()));
+        self.apply(&Value::Object(active), ConfigSource::Profile { name: selected })
+    }
+
+    fn apply(&mut self, layer: &Value, source: ConfigSource) -> Result<()> {
+        let Some(layer) = layer.as_object() else {
+            return Err(anyhow::anyhow!("Configuration from {} must be an object", source));
+        };
+
+        for (key, value) in layer {
+            if !self.values.contains_key(key) {
+                return Err(anyhow::anyhow!("Unknown configuration key '{}' from {}", key, source));
+            }
+            if source == ConfigSource::Request && SERVER_ONLY_KEYS.contains(&key.as_str()) {
+                return Err(anyhow::anyhow!("Configuration key '{}' can't be overridden per request", key));
+            }
+            // Profiles are defined in the config file and selected by name, not set as values
+            let selects_profile = key == "profile" || key == "profiles";
+            if selects_profile && matches!(source, ConfigSource::Runtime | ConfigSource::Request) {
+                return Err(anyhow::anyhow!("Select a profile with the 'profile' option instead of setting '{}'", key));
+            }
+            if key == "profiles" && matches!(source, ConfigSource::Profile { .. }) {
+                return Err(anyhow::anyhow!("Profiles can't define other profiles"));
+            }
+
+            self.values.insert(key.clone(), value.clone());
+            self.provenance.insert(key.clone(), source.clone());
+        }
+
+        // Check the layer right away so errors point at where the bad value came from
+        serde_json::from_value::<Configuration>(Value::Object(self.values.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid configuration from {}: {}", source, e))?;
+
+        self.layers.push(ConfigLayer {
+            source,
+            values: layer.clone(),
+        });
+
+        Ok(())
+    }
+
+    fn build(&self) -> Result<Configuration> {
+        let mut config: Configuration = serde_json::from_value(Value::Object(self.values.clone()))
+            .map_err(|e| anyhow::anyhow!("Invalid configuration: {}", e))?;
+        config.provenance = self.provenance.clone();
+        config.layers = self.layers.clone();
+        Ok(config)
+    }
+}
+
+// Parses one environment variable; an empty value counts as unset, e.g. `IMAGE_PROVIDER=`
+fn env_value(var: &str, raw: &str, kind: EnvKind) -> Result<Option<Value>> {
+    let trimmed = raw.trim();
+    if trimmed.is_empty() {
+        tracing::debug!(var, "Ignoring empty environment variable");
+        return Ok(None);
+    }
+
+    let value = match kind {
+        EnvKind::String => Value::String(raw.to_string()),
+        EnvKind::Choice => Value::String(trimmed.to_lowercase()),
+        EnvKind::Integer => trimmed.parse::<i64>()
+            .map(Value::from)
+            .map_err(|_| anyhow::anyhow!("{} must be an integer, got {:?}", var, raw))?,
+        EnvKind::Number => trimmed.parse::<f64>()
+            .map(Value::from)
+            .map_err(|_| anyhow::anyhow!("{} must be a number, got {:?}", var, raw))?,
+        EnvKind::Boolean => match trimmed.to_lowercase().as_str() {
+            "1" | "true" | "yes" => Value::Bool(true),
+            "0" | "false" | "no" => Value::Bool(false),
+            _ => return Err(anyhow::anyhow!("{} must be true or false, got {:?}", var, raw)),
+        },
+        EnvKind::List => Value::Array(
+            trimmed.split(',')
+                .map(|item| item.trim().to_lowercase())
+                .filter(|item| !item.is_empty())
+                .map(Value::from)
+                .collect(),
+        ),
+    };
+
+    Ok(Some(value))
+}
+
+// Replaces every `*_api_key` value with "<set>", including those inside profiles, so a key is
+// never shown, only whether it is set
+pub fn mask_secrets(values: &mut Map<String, Value>) {
+    for (key, value) in values.iter_mut() {
+        match value {
+            Value::Null => {}
+            _ if key.ends_with("_api_key") => *value = Value::from("<set>"),
+            Value::Object(nested) => mask_secrets(nested),
+            Value::Array(items) => {
+                for item in items {
+                    if let Value::Object(nested) = item {
+                        mask_secrets(nested);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn env_choices_ignore_case() {
+        assert_eq!(env_value("RESEARCH_MODE", "REMOTE", EnvKind::Choice).unwrap(), Some(json!("remote")));
+        assert_eq!(env_value("LLM_FALLBACK", "Groq, OLLAMA,", EnvKind::List).unwrap(), Some(json!(["groq", "ollama"])));
+        assert_eq!(env_value("NO_CACHE", " Yes ", EnvKind::Boolean).unwrap(), Some(json!(true)));
+    }
+
+    #[test]
+    fn empty_env_values_count_as_unset() {
+        for kind in [EnvKind::String, EnvKind::Choice, EnvKind::Integer, EnvKind::Number, EnvKind::Boolean, EnvKind::List] {
+            assert_eq!(env_value("IMAGE_PROVIDER", "  ", kind).unwrap(), None);
+        }
+    }
+
+    #[test]
+    fn malformed_env_values_name_the_variable() {
+        let error = env_value("NUM_PERSPECTIVES", "three", EnvKind::Integer).unwrap_err();
+        assert!(error.to_string().contains("NUM_PERSPECTIVES must be an integer"), "{}", error);
+    }
+
+    #[test]
+    fn secrets_are_masked_at_any_depth() {
+        let mut values = json!({
+            "groq_api_key": "gsk-top",
+            "openai_api_key": null,
+            "profiles": { "deep": { "perplexity_api_key": "pplx-nested", "max_web_research_loops": 3 } },
+            "layers": [{ "luma_api_key": "luma-in-a-list" }],
+        });
+
+        mask_secrets(values.as_object_mut().unwrap());
+
+        assert_eq!(values["groq_api_key"], "<set>");
+        assert_eq!(values["openai_api_key"], Value::Null);
+        assert_eq!(values["profiles"]["deep"]["perplexity_api_key"], "<set>");
+        assert_eq!(values["profiles"]["deep"]["max_web_research_loops"], 3);
+        assert_eq!(values["layers"][0]["luma_api_key"], "<set>");
+    }
+}
@@ -1,63 +1,92 @@
 use serde::{Deserialize, Serialize};
 use anyhow::Result;
-use super::configuration::{Configuration, ResearchMode};
-use ollama_rs::Ollama;
-use ollama_rs::generation::completion::request::GenerationRequest;
-use crate::assistant::groq::GroqClient;
+use super::configuration::Configuration;
+use super::error::ResearchError;
+use crate::assistant::llm;
+use crate::assistant::prompts;
+use serde_json::json;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DebatePerspectives {
     pub topic: String,
     pub perspective_one: String,
     pub perspective_two: String,
+    // Perspectives beyond the second, when more than two were requested
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub additional_perspectives: Vec<String>,
 }
 
-const DEBATE_PROMPT: &str = r#"You are a debate coach helping to frame different perspectives on a topic.
-Given this research topic, generate two distinct, well-reasoned perspectives that could form the basis of a debate.
-Each perspective should be factual, balanced, and supported by evidence.
-
-Research Topic: {topic}
+impl DebatePerspectives {
+    pub fn all(&self) -> Vec<String> {
+        let mut perspectives = vec![self.perspective_one.clone()];
+        if !self.perspective_two.is_empty() {
+            perspectives.push(self.perspective_two.clone());
+        }
+        perspectives.extend(self.additional_perspectives.iter().cloned());
+        perspectives
+    }
 
-Format your response as a JSON object with these exact keys:
-{{
-    "topic": "the research topic",
-    "perspective_one": "first perspective",
-    "perspective_two": "second perspective"
-}}
+    // The perspective at `index` in the order they were asked for, so a blank one doesn't shift the rest
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let perspective = match index {
+            0 => &self.perspective_one,
+            1 => &self.perspective_two,
+            _ => self.additional_perspectives.get(index - 2)?,
+        };
+        Some(perspective.as_str()).filter(|perspective| !perspective.is_empty())
+    }
+}
 
-Ensure all quotes and special characters in the perspectives are properly escaped."#;
+#[derive(Debug, Deserialize)]
+struct PerspectiveList {
+    topic: String,
+    perspectives: Vec<String>,
+}
 
-pub async fn generate_debate_perspectives(topic: &str, config: &Configuration) -> Result<DebatePerspectives> {
-    let prompt = DEBATE_PROMPT.replace("{topic}", topic);
+pub async fn generate_debate_perspectives(topic: &str, count: usize, language: &str, config: &Configuration) -> Result<DebatePerspectives> {
+    let prompt = prompts::render(config, "debate.jinja", json!({
+        "topic": topic,
+        "count": count,
+        "language": language,
+    }))?;
     
-    let response = match config.research_mode {
-        ResearchMode::Local => {
-            let ollama = Ollama::default();
-            let request = GenerationRequest::new(config.local_llm.clone(), prompt);
-            ollama.generate(request).await?.response
-        },
-        ResearchMode::Remote => {
-            if let Some(groq_api_key) = &config.groq_api_key {
-                let groq = GroqClient::new(groq_api_key.clone());
-                groq.generate(&prompt, &config.groq_model).await?
-            } else {
-                return Err(anyhow::anyhow!("Groq API key not found"));
-            }
-        }
-    };
+    let response = llm::generate(&prompt, config).await?;
 
     // Extract JSON from response by finding the first '{' and last '}'
     let json_start = response.find('{').unwrap_or(0);
     let json_end = response.rfind('}').map(|i| i + 1).unwrap_or(response.len());
     let json_str = &response[json_start..json_end];
 
+    if count != 2 {
+        return match serde_json::from_str::<PerspectiveList>(json_str) {
+            Ok(list) if !list.perspectives.is_empty() => {
+                let mut perspectives = list.perspectives.into_iter().take(count);
+                Ok(DebatePerspectives {
+                    topic: list.topic,
+                    perspective_one: perspectives.next().unwrap_or_default(),
+                    perspective_two: perspectives.next().unwrap_or_default(),
+                    additional_perspectives: perspectives.collect(),
+                })
+            }
+            Ok(_) => Err(ResearchError::Parse {
+                what: "debate perspectives".to_string(),
+                message: "the response contained no perspectives".to_string(),
+            }.into()),
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to parse debate perspectives");
+                tracing::debug!(response = %response, "Raw debate response");
+                Err(ResearchError::Parse { what: "debate perspectives".to_string(), message: e.to_string() }.into())
+            }
+        };
+    }
+
     // Parse the response, ensuring proper JSON escaping
     match serde_json::from_str::<DebatePerspectives>(json_str) {
         Ok(perspectives) => Ok(perspectives),
         Err(e) => {
-            eprintln!("Failed to parse debate perspectives: {}", e);
-            eprintln!("Raw response: {}", response);
-            Err(anyhow::anyhow!("Failed to parse debate perspectives: {}", e))
+            tracing::warn!(error = %e, "Failed to parse debate perspectives");
+            tracing::debug!(response = %response, "Raw debate response");
+            Err(ResearchError::Parse { what: "debate perspectives".to_string(), message: e.to_string() }.into())
         }
     }
 } 
\ No newline at end of file
@@ -0,0 +1,362 @@
+use reqwest::{Client, StatusCode};
+use serde::Serialize;
+use serde_json::Value;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::configuration::{Configuration, ImageProviderKind, LlmProvider, ResearchMode, SearchAPI};
+use super::http::{HttpClient, HttpError};
+use super::prompts::{check_templates, prompt_version};
+
+// Where ollama-rs connects by default
+const OLLAMA_URL: &str = "http://localhost:11434";
+const GROQ_MODELS_URL: &str = "https://api.groq.com/openai/v1/models";
+const LUMA_GENERATIONS_URL: &str = "https://api.lumalabs.ai/dream-machine/v1/generations";
+const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    Warning,
+    Error,
+    Skipped,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "ok"),
+            CheckStatus::Warning => write!(f, "warning"),
+            CheckStatus::Error => write!(f, "error"),
+            CheckStatus::Skipped => write!(f, "skipped"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    // Models the provider reported, when it lists them
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub models: Vec<String>,
+}
+
+impl Check {
+    fn new(name: &str, status: CheckStatus, message: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            message: message.into(),
+            latency_ms: None,
+            models: Vec::new(),
+        }
+    }
+
+    fn timed(mut self, started: Instant) -> Self {
+        self.latency_ms = Some(started.elapsed().as_millis() as u64);
+        self
+    }
+
+    fn with_models(mut self, models: Vec<String>) -> Self {
+        self.models = models;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostics {
+    // False when any check failed outright; warnings don't count
+    pub ok: bool,
+    pub checks: Vec<Check>,
+}
+
+impl Diagnostics {
+    pub fn print(&self) {
+        for check in &self.checks {
+            let latency = check.latency_ms
+                .map(|ms| format!(" ({} ms)", ms))
+                .unwrap_or_default();
+            println!("[{}] {}: {}{}", check.status, check.name, check.message, latency);
+        }
+    }
+}
+
+// `live` also tries providers that bill every call, which the server never does on its own
+pub async fn run_checks(config: &Configuration, live: bool) -> Diagnostics {
+    let client = Client::builder()
+        .timeout(CHECK_TIMEOUT)
+        .build()
+        .unwrap_or_default();
+
+    let (ollama, groq, openai, search, images) = tokio::join!(
+        check_ollama(&client, config),
+        check_groq(&client, config),
+        check_openai(&client, config),
+        check_search(config, live),
+        check_image_provider(&client, config),
+    );
+
+    let checks = vec![check_configuration(config), check_prompts(config), ollama, groq, openai, search, images];
+    Diagnostics {
+        ok: checks.iter().all(|check| check.status != CheckStatus::Error),
+        checks,
+    }
+}
+
+fn check_configuration(config: &Configuration) -> Check {
+    let mut problems = Vec::new();
+
+    if config.research_mode == ResearchMode::Remote && config.groq_api_key.is_none() {
+        problems.push("remote research mode needs GROQ_API_KEY".to_string());
+    }
+    if config.llm_fallback.contains(&LlmProvider::Groq) && config.groq_api_key.is_none() {
+        problems.push("the groq fallback needs GROQ_API_KEY".to_string());
+    }
+    if config.image_provider == ImageProviderKind::Luma && config.luma_api_key.is_none() {
+        problems.push("the luma image provider needs LUMAAI_API_KEY".to_string());
+    }
+    if !(1..=config.limits.max_web_research_loops).contains(&config.max_web_research_loops) {
+        problems.push(format!(
+            "max_web_research_loops is {}, outside 1..={}",
+            config.max_web_research_loops, config.limits.max_web_research_loops
+        ));
+    }
+    if config.num_perspectives == 0 {
+        problems.push("num_perspectives must be at least 1".to_string());
+    }
+
+    if problems.is_empty() {
+        let profile = config.profile.as_deref()
+            .map(|name| format!(", profile {}", name))
+            .unwrap_or_default();
+        Check::new("configuration", CheckStatus::Ok, format!("{:?} research mode{}", config.research_mode, profile))
+    } else {
+        Check::new("configuration", CheckStatus::Error, problems.join("; "))
+    }
+}
+
+fn check_prompts(config: &Configuration) -> Check {
+    let version = check_templates(config).and_then(|_| prompt_version(config));
+    match version {
+        Ok(version) if version.custom.is_empty() => {
+            Check::new("prompts", CheckStatus::Ok, format!("{} ({})", version.version, version.fingerprint))
+        }
+        Ok(version) => Check::new(
+            "prompts",
+            CheckStatus::Ok,
+            format!(
+                "{} ({}), {} from {}",
+                version.version,
+                version.fingerprint,
+                match version.custom.len() {
+                    1 => "1 template".to_string(),
+                    count => format!("{} templates", count),
+                },
+                config.prompts.dir.as_deref().unwrap_or_default()
+            ),
+        ),
+        Err(e) => Check::new("prompts", CheckStatus::Error, e.to_string()),
+    }
+}
+
+async fn check_ollama(client: &Client, config: &Configuration) -> Check {
+    // Ollama only matters when research runs locally
+    let failure = match config.research_mode {
+        ResearchMode::Local => CheckStatus::Error,
+        ResearchMode::Remote => CheckStatus::Warning,
+    };
+
+    let started = Instant::now();
+    let response = match client.get(format!("{}/api/tags", OLLAMA_URL)).send().await {
+        Ok(response) => response,
+        Err(e) => return Check::new("ollama", failure, format!("Ollama is not reachable at {}: {}", OLLAMA_URL, e)).timed(started),
+    };
+    if !response.status().is_success() {
+        return Check::new("ollama", failure, format!("Ollama returned {}", response.status())).timed(started);
+    }
+
+    let tags: Value = match response.json().await {
+        Ok(tags) => tags,
+        Err(e) => return Check::new("ollama", failure, format!("Unexpected response from Ollama: {}", e)).timed(started),
+    };
+    let models = model_names(&tags["models"], "name");
+
+    // Ollama reports untagged models as "<name>:latest"
+    let wanted = if config.local_llm.contains(':') {
+        config.local_llm.clone()
+    } else {
+        format!("{}:latest", config.local_llm)
+    };
+
+    let check = if models.contains(&wanted) {
+        Check::new("ollama", CheckStatus::Ok, format!("{} is available", config.local_llm))
+    } else {
+        Check::new(
+            "ollama",
+            failure,
+            format!("{} is not pulled - run `ollama pull {}`", config.local_llm, config.local_llm),
+        )
+    };
+    check.timed(started).with_models(models)
+}
+
+async fn check_groq(client: &Client, config: &Configuration) -> Check {
+    let Some(api_key) = &config.groq_api_key else {
+        return match config.research_mode {
+            ResearchMode::Remote => Check::new("groq", CheckStatus::Error, "GROQ_API_KEY is not set"),
+            ResearchMode::Local => Check::new("groq", CheckStatus::Skipped, "GROQ_API_KEY is not set"),
+        };
+    };
+    let failure = match config.research_mode {
+        ResearchMode::Remote => CheckStatus::Error,
+        ResearchMode::Local => CheckStatus::Warning,
+    };
+
+    let started = Instant::now();
+    let response = match client.get(GROQ_MODELS_URL).bearer_auth(api_key).send().await {
+        Ok(response) => response,
+        Err(e) => return Check::new("groq", failure, format!("Groq is not reachable: {}", e)).timed(started),
+    };
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Check::new("groq", failure, "GROQ_API_KEY was rejected").timed(started);
+        }
+        status => return Check::new("groq", failure, format!("Groq returned {}", status)).timed(started),
+    }
+
+    let listing: Value = match response.json().await {
+        Ok(listing) => listing,
+        Err(e) => return Check::new("groq", failure, format!("Unexpected response from Groq: {}", e)).timed(started),
+    };
+    let models = model_names(&listing["data"], "id");
+
+    let check = if models.contains(&config.groq_model) {
+        Check::new("groq", CheckStatus::Ok, format!("{} is available", config.groq_model))
+    } else {
+        Check::new("groq", failure, format!("{} is not offered by Groq", config.groq_model))
+    };
+    check.timed(started).with_models(models)
+}
+
+async fn check_openai(client: &Client, config: &Configuration) -> Check {
+    // Only ever used as a fallback, so problems are warnings
+    if !config.llm_fallback.contains(&LlmProvider::OpenAi) {
+        return Check::new("openai", CheckStatus::Skipped, "not in the LLM fallback chain");
+    }
+
+    let started = Instant::now();
+    let base_url = config.openai_base_url.trim_end_matches('/');
+    let mut request = client.get(format!("{}/models", base_url));
+    if let Some(api_key) = &config.openai_api_key {
+        request = request.bearer_auth(api_key);
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => return Check::new("openai", CheckStatus::Warning, format!("{} is not reachable: {}", base_url, e)).timed(started),
+    };
+    match response.status() {
+        status if status.is_success() => {}
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => {
+            return Check::new("openai", CheckStatus::Warning, "OPENAI_API_KEY was rejected").timed(started);
+        }
+        status => return Check::new("openai", CheckStatus::Warning, format!("{} returned {}", base_url, status)).timed(started),
+    }
+
+    let listing: Value = match response.json().await {
+        Ok(listing) => listing,
+        Err(e) => return Check::new("openai", CheckStatus::Warning, format!("Unexpected response from {}: {}", base_url, e)).timed(started),
+    };
+    let models = model_names(&listing["data"], "id");
+
+    let check = if models.contains(&config.openai_model) {
+        Check::new("openai", CheckStatus::Ok, format!("{} is available", config.openai_model))
+    } else {
+        Check::new("openai", CheckStatus::Warning, format!("{} is not offered by {}", config.openai_model, base_url))
+    };
+    check.timed(started).with_models(models)
+}
+
+async fn check_search(config: &Configuration, live: bool) -> Check {
+    match config.search_api {
+        SearchAPI::Perplexity => {
+            if config.perplexity_api_key.is_empty() {
+                return Check::new("search", CheckStatus::Error, "PERPLEXITY_API_KEY is not set");
+            }
+            if !live {
+                return Check::new(
+                    "search",
+                    CheckStatus::Ok,
+                    "PERPLEXITY_API_KEY is set (not tried, `researcher check --live` sends a billed request)",
+                );
+            }
+
+            // Perplexity has no free endpoint to call, so spend a single output token
+            let started = Instant::now();
+            let http = HttpClient::new("perplexity", &config.http);
+            let response = http
+                .send(|client| client
+                    .post(format!("{}/chat/completions", config.perplexity_base_url.trim_end_matches('/')))
+                    .bearer_auth(&config.perplexity_api_key)
+                    .json(&serde_json::json!({
+                        "model": "sonar-pro",
+                        "messages": [{ "role": "user", "content": "ping" }],
+                        "max_tokens": 1
+                    })))
+                .await;
+
+            let check = match response {
+                Ok(_) => Check::new("search", CheckStatus::Ok, "Perplexity responded"),
+                Err(HttpError::Status { status: StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN, .. }) => {
+                    Check::new("search", CheckStatus::Error, "PERPLEXITY_API_KEY was rejected")
+                }
+                Err(HttpError::Status { status, .. }) => {
+                    Check::new("search", CheckStatus::Error, format!("Perplexity returned {}", status))
+                }
+                Err(e) => Check::new("search", CheckStatus::Error, format!("Perplexity is not reachable: {}", e)),
+            };
+            check.timed(started)
+        }
+    }
+}
+
+async fn check_image_provider(client: &Client, config: &Configuration) -> Check {
+    match config.image_provider {
+        ImageProviderKind::None => Check::new("images", CheckStatus::Skipped, "No image provider configured"),
+        ImageProviderKind::Placeholder => Check::new("images", CheckStatus::Ok, "Placeholder images are generated locally"),
+        ImageProviderKind::Luma => {
+            let Some(api_key) = &config.luma_api_key else {
+                return Check::new("images", CheckStatus::Error, "LUMAAI_API_KEY is not set");
+            };
+
+            // Visuals are optional, so a broken provider only degrades reports
+            let started = Instant::now();
+            let check = match client.get(LUMA_GENERATIONS_URL).query(&[("limit", "1")]).bearer_auth(api_key).send().await {
+                Ok(response) if response.status().is_success() => {
+                    Check::new("images", CheckStatus::Ok, "Luma responded")
+                }
+                Ok(response) if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                    Check::new("images", CheckStatus::Warning, "LUMAAI_API_KEY was rejected")
+                }
+                Ok(response) => Check::new("images", CheckStatus::Warning, format!("Luma returned {}", response.status())),
+                Err(e) => Check::new("images", CheckStatus::Warning, format!("Luma is not reachable: {}", e)),
+            };
+            check.timed(started)
+        }
+    }
+}
+
+fn model_names(models: &Value, field: &str) -> Vec<String> {
+    let mut names: Vec<String> = models.as_array()
+        .map(|models| models.iter()
+            .filter_map(|model| model[field].as_str().map(|name| name.to_string()))
+            .collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+}
@@ -0,0 +1,182 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::time::Instant;
+use tokio::sync::Mutex;
+
+use super::configuration::Configuration;
+use super::llm;
+use super::prompts::{self, prompt_version};
+use super::utils::perplexity_search;
+
+// Enough for a long listening session; past it the least recently used enrichment makes room
+const MAX_CACHED_ENRICHMENTS: usize = 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichmentSource {
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnrichmentFact {
+    pub fact: String,
+    // Indexes into `Enrichment::sources` for the sources backing this fact
+    pub sources: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Enrichment {
+    pub text_hash: String,
+    pub facts: Vec<EnrichmentFact>,
+    pub sources: Vec<EnrichmentSource>,
+    pub grounded: bool,
+    pub cached: bool,
+    // The facts as markdown bullets, as the podcast player displays them
+    pub enriched_content: String,
+}
+
+// Enrichments with the time each was last served
+pub struct EnrichmentCache {
+    capacity: usize,
+    entries: Mutex<HashMap<String, (Enrichment, Instant)>>,
+}
+
+impl Default for EnrichmentCache {
+    fn default() -> Self {
+        Self::with_capacity(MAX_CACHED_ENRICHMENTS)
+    }
+}
+
+impl EnrichmentCache {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn enrich(&self, text: &str, ground: bool, config: &Configuration) -> Result<Enrichment> {
+        let key = text_hash(text, ground, config)?;
+
+        if let Some((enrichment, last_used)) = self.entries.lock().await.get_mut(&key) {
+            *last_used = Instant::now();
+            return Ok(Enrichment {
+                cached: true,
+                ..enrichment.clone()
+            });
+        }
+
+        let enrichment = enrich_text(text, ground, config).await?;
+
+        let mut entries = self.entries.lock().await;
+        if entries.len() >= self.capacity && !entries.contains_key(&key) {
+            let oldest = entries.iter()
+                .min_by_key(|(_, (_, last_used))| *last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+        entries.insert(key, (enrichment.clone(), Instant::now()));
+
+        Ok(enrichment)
+    }
+}
+
+// Covers everything the facts depend on besides the text: the models that may answer, the
+// language and the prompt templates, so a changed setting never serves stale facts
+pub fn text_hash(text: &str, ground: bool, config: &Configuration) -> Result<String> {
+    let models: Vec<String> = config.llm_chain().iter()
+        .map(|provider| format!("{}/{}", provider.name(), config.model_for(*provider)))
+        .collect();
+    let key = json!({
+        "text": text.trim(),
+        "ground": ground,
+        "models": models,
+        "search": config.search_api,
+        "language": config.language,
+        "prompts": prompt_version(config)?.fingerprint,
+    });
+    Ok(format!("{:x}", Sha256::digest(key.to_string().as_bytes())))
+}
+
+pub async fn enrich_text(text: &str, ground: bool, config: &Configuration) -> Result<Enrichment> {
+    // Grounding runs a single search and asks the model to cite it
+    let (prompt, sources) = if ground {
+        let search_results = perplexity_search(text, 0, config).await?;
+        let sources: Vec<EnrichmentSource> = search_results.results.iter()
+            .map(|result| EnrichmentSource {
+                title: result.title.clone(),
+                url: result.url.clone(),
+            })
+            .collect();
+        let context = search_results.results.iter()
+            .filter_map(|result| result.raw_content.clone())
+            .collect::<Vec<String>>()
+            .join("\n\n");
+
+        let prompt = prompts::render(config, "grounded_enrichment.jinja", json!({
+            "text": text,
+            "search_results": context,
+            "sources": sources,
+        }))?;
+        (prompt, sources)
+    } else {
+        (prompts::render(config, "enrichment.jinja", json!({ "text": text }))?, Vec::new())
+    };
+
+    let response = llm::generate(&prompt, config).await?;
+
+    let facts = parse_facts(&response, sources.len());
+    let enriched_content = facts.iter()
+        .map(|fact| format!("* {}", fact.fact))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    Ok(Enrichment {
+        text_hash: text_hash(text, ground, config)?,
+        facts,
+        sources,
+        grounded: ground,
+        cached: false,
+        enriched_content,
+    })
+}
+
+// Reads "* fact [1][2]" bullets, dropping any <think> block and citations to unknown sources
+fn parse_facts(response: &str, source_count: usize) -> Vec<EnrichmentFact> {
+    let response = match (response.find("<think>"), response.find("</think>")) {
+        (Some(start), Some(end)) => format!("{}{}", &response[..start], &response[end + 8..]),
+        _ => response.to_string(),
+    };
+
+    response.lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            line.strip_prefix("* ").or_else(|| line.strip_prefix("- "))
+        })
+        .take(2)
+        .map(|bullet| {
+            let mut fact = bullet.to_string();
+            let mut sources = Vec::new();
+
+            while let (Some(start), Some(end)) = (fact.rfind('['), fact.rfind(']')) {
+                if end != fact.len() - 1 || start > end {
+                    break;
+                }
+                match fact[start + 1..end].parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= source_count => sources.insert(0, n - 1),
+                    Ok(_) => {}
+                    Err(_) => break,
+                }
+                fact.truncate(start);
+                fact = fact.trim_end().to_string();
+            }
+
+            EnrichmentFact { fact, sources }
+        })
+        .collect()
+}
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use super::http::HttpError;
+
+// Failures a research run can end with, as told to API clients and /status subscribers
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum ResearchError {
+    // Options in the request are invalid
+    #[error("{0}")]
+    Config(String),
+    // The server itself is set up wrong, such as a missing API key or a broken prompts directory
+    #[error("{0}")]
+    Misconfigured(String),
+    #[error("{provider} rejected the API key: {message}")]
+    ProviderAuth { provider: String, message: String },
+    #[error("{provider} is rate limiting requests: {message}")]
+    RateLimited {
+        provider: String,
+        retry_after: Option<Duration>,
+        message: String,
+    },
+    #[error("{provider} timed out: {message}")]
+    Timeout { provider: String, message: String },
+    #[error("{provider} failed: {message}")]
+    Provider { provider: String, message: String },
+    #[error("Couldn't parse {what}: {message}")]
+    Parse { what: String, message: String },
+    #[error("Search found nothing for \"{query}\"")]
+    SearchEmpty { query: String },
+    #[error("Research was cancelled")]
+    Cancelled,
+    #[error("{0}")]
+    BudgetExceeded(String),
+    #[error("{0}")]
+    Internal(String),
+}
+
+// The JSON body of an error response and of typed `error` events on /status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorBody {
+    pub code: String,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub provider: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_after_secs: Option<u64>,
+}
+
+impl ResearchError {
+    // Finds the most specific error anywhere in the chain, so context added on the way up doesn't hide it
+    pub fn classify(error: &anyhow::Error) -> Self {
+        for cause in error.chain() {
+            if let Some(research) = cause.downcast_ref::<ResearchError>() {
+                return research.clone();
+            }
+            if let Some(http) = cause.downcast_ref::<HttpError>() {
+                return Self::from(http);
+            }
+            if let Some(json) = cause.downcast_ref::<serde_json::Error>() {
+                return ResearchError::Parse { what: "a response".to_string(), message: json.to_string() };
+            }
+        }
+        ResearchError::Internal(error.to_string())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResearchError::Config(_) => "config",
+            ResearchError::Misconfigured(_) => "misconfigured",
+            ResearchError::ProviderAuth { .. } => "provider_auth",
+            ResearchError::RateLimited { .. } => "rate_limited",
+            ResearchError::Timeout { .. } => "timeout",
+            ResearchError::Provider { .. } => "provider",
+            ResearchError::Parse { .. } => "parse",
+            ResearchError::SearchEmpty { .. } => "search_empty",
+            ResearchError::Cancelled => "cancelled",
+            ResearchError::BudgetExceeded(_) => "budget_exceeded",
+            ResearchError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ResearchError::Config(_) => 400,
+            ResearchError::BudgetExceeded(_) => 402,
+            ResearchError::SearchEmpty { .. } => 422,
+            ResearchError::RateLimited { .. } => 429,
+            // The client closed the request, as nginx reports it
+            ResearchError::Cancelled => 499,
+            ResearchError::Internal(_) => 500,
+            // Nothing the caller can change, but it works again once an operator fixes the setup
+            ResearchError::Misconfigured(_) => 503,
+            // The upstream provider failed us rather than the caller doing anything wrong
+            ResearchError::ProviderAuth { .. } | ResearchError::Provider { .. } | ResearchError::Parse { .. } => 502,
+            ResearchError::Timeout { .. } => 504,
+        }
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            ResearchError::RateLimited { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    pub fn body(&self) -> ErrorBody {
+        let provider = match self {
+            ResearchError::ProviderAuth { provider, .. }
+            | ResearchError::RateLimited { provider, .. }
+            | ResearchError::Timeout { provider, .. }
+            | ResearchError::Provider { provider, .. } => Some(provider.clone()),
+            _ => None,
+        };
+        ErrorBody {
+            code: self.code().to_string(),
+            message: self.to_string(),
+            provider,
+            retry_after_secs: self.retry_after().map(|delay| delay.as_secs().max(1)),
+        }
+    }
+}
+
+impl From<&HttpError> for ResearchError {
+    fn from(error: &HttpError) -> Self {
+        let message = error.to_string();
+        match error {
+            HttpError::Status { provider, status, retry_after, .. } => {
+                let provider = provider.to_string();
+                match status.as_u16() {
+                    401 | 403 => ResearchError::ProviderAuth { provider, message },
+                    429 => ResearchError::RateLimited { provider, retry_after: *retry_after, message },
+                    408 | 504 => ResearchError::Timeout { provider, message },
+                    _ => ResearchError::Provider { provider, message },
+                }
+            }
+            HttpError::Timeout { provider, .. } => ResearchError::Timeout { provider: provider.to_string(), message },
+            HttpError::Transport { provider, .. } | HttpError::Fixture { provider, .. } | HttpError::Mock { provider, .. } => {
+                ResearchError::Provider { provider: provider.to_string(), message }
+            }
+        }
+    }
+}
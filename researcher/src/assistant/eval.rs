@@ -0,0 +1,413 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::time::Instant;
+use tokio::sync::broadcast::Sender;
+
+use super::configuration::Configuration;
+use super::error::ResearchError;
+use super::graph::ResearchGraph;
+use super::llm;
+use super::prompts;
+use super::state::{StatusUpdate, SummaryState, SummaryStateInput};
+
+// Longer reports are cut before they go to the judge, to keep within its context window
+const MAX_JUDGED_REPORT_CHARS: usize = 12_000;
+
+// One topic of an evaluation suite, with facts a good report should mention
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub topic: String,
+    #[serde(default)]
+    pub facts: Vec<String>,
+}
+
+// A JSONL file, one case per line
+pub fn parse_eval_suite(contents: &str) -> Result<Vec<EvalCase>> {
+    let cases = contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str::<EvalCase>(line).map_err(|e| anyhow::anyhow!("Line {}: {}", i + 1, e))
+        })
+        .collect::<Result<Vec<EvalCase>>>()?;
+
+    if let Some(i) = cases.iter().position(|case| case.topic.trim().is_empty()) {
+        return Err(anyhow::anyhow!("Topic {} is empty", i + 1));
+    }
+    if cases.is_empty() {
+        return Err(anyhow::anyhow!("The evaluation suite has no topics"));
+    }
+    Ok(cases)
+}
+
+// Scores are fractions between 0 and 1, except for the counts
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct EvalScores {
+    // Paragraphs of the report that cite a source inline
+    pub citation_coverage: f64,
+    // Reference facts the report mentions
+    pub factual_recall: f64,
+    pub facts_found: usize,
+    pub facts_missed: Vec<String>,
+    // Distinct domains per source, and how many there were
+    pub source_diversity: f64,
+    pub domains: usize,
+    pub sources: usize,
+    pub words: usize,
+    pub latency_secs: f64,
+    pub cost_usd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub judge: Option<JudgeVerdict>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JudgeVerdict {
+    // 0 to 10
+    pub score: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalResult {
+    pub configuration: String,
+    pub topic: String,
+    // The prompt version and fingerprint the run recorded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scores: Option<EvalScores>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// Averages over the runs of one configuration that finished
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalSummary {
+    pub configuration: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompts: Option<String>,
+    pub runs: usize,
+    pub failed: usize,
+    pub citation_coverage: f64,
+    pub factual_recall: f64,
+    pub source_diversity: f64,
+    pub words: f64,
+    pub latency_secs: f64,
+    pub cost_usd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub judge_score: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalReport {
+    pub summaries: Vec<EvalSummary>,
+    pub results: Vec<EvalResult>,
+}
+
+// Runs every case under every configuration, one run at a time so latencies stay comparable.
+// With a judge configuration, its LLM also grades each report.
+pub async fn run_eval(
+    cases: &[EvalCase],
+    configurations: &[(String, Configuration)],
+    judge: Option<&Configuration>,
+    status_tx: Option<Sender<StatusUpdate>>,
+) -> EvalReport {
+    let mut results = Vec::new();
+
+    for (name, config) in configurations {
+        for case in cases {
+            tracing::info!(configuration = %name, topic = %case.topic, "Evaluating");
+            let mut graph = ResearchGraph::new(config.clone());
+            if let Some(tx) = &status_tx {
+                graph.set_status_sender(tx.clone());
+            }
+
+            let started = Instant::now();
+            let output = graph.process_research(SummaryStateInput { research_topic: case.topic.clone() }).await;
+            let latency_secs = started.elapsed().as_secs_f64();
+
+            let result = match output {
+                Ok(output) => {
+                    let prompt_version = output.state.prompts.as_ref()
+                        .map(|version| format!("{} ({})", version.version, version.fingerprint));
+                    let mut scores = score_report(&output.running_summary, &output.state, &case.facts);
+                    scores.latency_secs = latency_secs;
+                    if let Some(judge_config) = judge {
+                        match judge_report(case, &output.running_summary, judge_config).await {
+                            Ok(verdict) => scores.judge = Some(verdict),
+                            Err(e) => tracing::warn!(topic = %case.topic, error = %e, "Judge failed"),
+                        }
+                    }
+                    EvalResult {
+                        configuration: name.clone(),
+                        topic: case.topic.clone(),
+                        prompts: prompt_version,
+                        scores: Some(scores),
+                        error: None,
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(configuration = %name, topic = %case.topic, error = %e, "Evaluation run failed");
+                    EvalResult {
+                        configuration: name.clone(),
+                        topic: case.topic.clone(),
+                        prompts: None,
+                        scores: None,
+                        error: Some(e.to_string()),
+                    }
+                }
+            };
+            results.push(result);
+        }
+    }
+
+    let summaries = configurations.iter()
+        .map(|(name, _)| summarize(name, &results))
+        .collect();
+    EvalReport { summaries, results }
+}
+
+// Everything but latency and the judge, which need the run itself
+pub fn score_report(report: &str, state: &SummaryState, facts: &[String]) -> EvalScores {
+    let body = report_body(report);
+
+    let paragraphs: Vec<&str> = body.split("\n\n")
+        .map(|paragraph| paragraph.trim())
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    let cited = paragraphs.iter().filter(|paragraph| cites_source(paragraph)).count();
+
+    let lowercase = body.to_lowercase();
+    let facts_missed: Vec<String> = facts.iter()
+        .filter(|fact| !mentions(&lowercase, fact))
+        .cloned()
+        .collect();
+    let facts_found = facts.len() - facts_missed.len();
+
+    let urls: Vec<String> = state.tracks.iter()
+        .flat_map(|track| track.sources.iter())
+        .flat_map(|source| extract_urls(source))
+        .collect();
+    let domains: BTreeSet<String> = urls.iter().filter_map(|url| domain(url)).collect();
+
+    EvalScores {
+        citation_coverage: ratio(cited, paragraphs.len()),
+        factual_recall: ratio(facts_found, facts.len()),
+        facts_found,
+        facts_missed,
+        source_diversity: ratio(domains.len(), urls.len()),
+        domains: domains.len(),
+        sources: urls.len(),
+        words: body.split_whitespace().count(),
+        latency_secs: 0.0,
+        cost_usd: state.usage.as_ref().map(|usage| usage.total.cost_usd).unwrap_or_default(),
+        judge: None,
+    }
+}
+
+async fn judge_report(case: &EvalCase, report: &str, config: &Configuration) -> Result<JudgeVerdict> {
+    let report: String = report.chars().take(MAX_JUDGED_REPORT_CHARS).collect();
+    let prompt = prompts::render(config, "judge.jinja", json!({
+        "topic": case.topic,
+        "facts": case.facts,
+        "report": report,
+    }))?;
+
+    let response = llm::generate(&prompt, config).await?;
+    let verdict = parse_verdict(&response)?;
+    Ok(JudgeVerdict { score: verdict.score.clamp(0.0, 10.0), reason: verdict.reason })
+}
+
+// The first JSON object in the reply after any <think> block; prose around it is ignored
+fn parse_verdict(response: &str) -> Result<JudgeVerdict> {
+    let parse_error = |message: String| ResearchError::Parse { what: "the judge's verdict".to_string(), message };
+    let answer = match response.find("</think>") {
+        Some(end) => &response[end + "</think>".len()..],
+        None => response,
+    };
+    let start = answer.find('{').ok_or_else(|| parse_error("no JSON object in the reply".to_string()))?;
+    let verdict = serde_json::Deserializer::from_str(&answer[start..])
+        .into_iter::<JudgeVerdict>()
+        .next()
+        .ok_or_else(|| parse_error("no JSON object in the reply".to_string()))?
+        .map_err(|e| parse_error(e.to_string()))?;
+    Ok(verdict)
+}
+
+fn summarize(configuration: &str, results: &[EvalResult]) -> EvalSummary {
+    let runs: Vec<&EvalResult> = results.iter().filter(|result| result.configuration == configuration).collect();
+    let scores: Vec<&EvalScores> = runs.iter().filter_map(|result| result.scores.as_ref()).collect();
+    let average = |value: fn(&EvalScores) -> f64| {
+        if scores.is_empty() {
+            return 0.0;
+        }
+        scores.iter().map(|scores| value(scores)).sum::<f64>() / scores.len() as f64
+    };
+    let verdicts: Vec<f64> = scores.iter().filter_map(|scores| scores.judge.as_ref().map(|judge| judge.score)).collect();
+
+    EvalSummary {
+        configuration: configuration.to_string(),
+        prompts: runs.iter().find_map(|result| result.prompts.clone()),
+        runs: runs.len(),
+        failed: runs.len() - scores.len(),
+        citation_coverage: average(|scores| scores.citation_coverage),
+        factual_recall: average(|scores| scores.factual_recall),
+        source_diversity: average(|scores| scores.source_diversity),
+        words: average(|scores| scores.words as f64),
+        latency_secs: average(|scores| scores.latency_secs),
+        cost_usd: average(|scores| scores.cost_usd),
+        judge_score: (!verdicts.is_empty()).then(|| verdicts.iter().sum::<f64>() / verdicts.len() as f64),
+    }
+}
+
+impl EvalReport {
+    pub fn render_markdown(&self) -> String {
+        let judged = self.summaries.iter().any(|summary| summary.judge_score.is_some());
+        let judge_header = if judged { " Judge |" } else { "" };
+        let judge_rule = if judged { "---|" } else { "" };
+
+        let mut out = format!(
+            "# Research evaluation\n\n\
+            | Configuration | Prompts | Runs | Failed | Recall | Citations | Diversity | Words | Latency | Cost |{}\n\
+            |---|---|---|---|---|---|---|---|---|---|{}\n",
+            judge_header, judge_rule
+        );
+        for summary in &self.summaries {
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} | {:.0} | {:.1}s | ${:.4} |",
+                escape(&summary.configuration),
+                escape(summary.prompts.as_deref().unwrap_or_default()),
+                summary.runs,
+                summary.failed,
+                percent(summary.factual_recall),
+                percent(summary.citation_coverage),
+                percent(summary.source_diversity),
+                summary.words,
+                summary.latency_secs,
+                summary.cost_usd
+            ));
+            if judged {
+                out.push_str(&format!(" {} |", summary.judge_score.map(|score| format!("{:.1}", score)).unwrap_or_default()));
+            }
+            out.push('\n');
+        }
+
+        out.push_str(&format!(
+            "\n## By topic\n\n\
+            | Configuration | Topic | Recall | Citations | Diversity | Words | Latency |{} Notes |\n\
+            |---|---|---|---|---|---|---|{}---|\n",
+            judge_header, judge_rule
+        ));
+        for result in &self.results {
+            let row = match &result.scores {
+                Some(scores) => {
+                    let judge = match (judged, &scores.judge) {
+                        (true, Some(verdict)) => format!(" {:.1} |", verdict.score),
+                        (true, None) => " |".to_string(),
+                        (false, _) => String::new(),
+                    };
+                    let notes = match scores.facts_missed.is_empty() {
+                        true => String::new(),
+                        false => format!("Missed: {}", scores.facts_missed.join("; ")),
+                    };
+                    format!(
+                        "| {} | {} | {}/{} | {} | {} ({} domains) | {} | {:.1}s |{} {} |\n",
+                        escape(&result.configuration),
+                        escape(&result.topic),
+                        scores.facts_found,
+                        scores.facts_found + scores.facts_missed.len(),
+                        percent(scores.citation_coverage),
+                        percent(scores.source_diversity),
+                        scores.domains,
+                        scores.words,
+                        scores.latency_secs,
+                        judge,
+                        escape(&notes)
+                    )
+                }
+                None => format!(
+                    "| {} | {} | | | | | |{} Failed: {} |\n",
+                    escape(&result.configuration),
+                    escape(&result.topic),
+                    if judged { " |" } else { "" },
+                    escape(result.error.as_deref().unwrap_or_default())
+                ),
+            };
+            out.push_str(&row);
+        }
+        out
+    }
+}
+
+// The report without headings or the source lists the finalizer appends, which would count as citations
+fn report_body(report: &str) -> String {
+    let mut body = Vec::new();
+    let mut in_sources = false;
+    for line in report.lines() {
+        if line.starts_with('#') {
+            in_sources = line.trim_end().ends_with("Sources:");
+            continue;
+        }
+        if !in_sources {
+            body.push(line);
+        }
+    }
+    body.join("\n")
+}
+
+// A URL, a markdown link or a numbered reference like [2]
+fn cites_source(paragraph: &str) -> bool {
+    if paragraph.contains("http://") || paragraph.contains("https://") || paragraph.contains("](") {
+        return true;
+    }
+    paragraph.match_indices('[').any(|(start, _)| {
+        let rest = &paragraph[start + 1..];
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        digits > 0 && rest[digits..].starts_with(']')
+    })
+}
+
+// Case-insensitive, and a fact counts when the report has it verbatim or most of its terms
+fn mentions(lowercase_report: &str, fact: &str) -> bool {
+    let fact = fact.to_lowercase();
+    if lowercase_report.contains(fact.trim()) {
+        return true;
+    }
+
+    let terms: Vec<&str> = fact.split_whitespace()
+        .map(|term| term.trim_matches(|c: char| !c.is_alphanumeric()))
+        .filter(|term| term.len() > 3 || term.chars().any(|c| c.is_ascii_digit()))
+        .collect();
+    let found = terms.iter().filter(|term| lowercase_report.contains(*term)).count();
+    !terms.is_empty() && found * 5 >= terms.len() * 4
+}
+
+fn extract_urls(text: &str) -> Vec<String> {
+    text.split(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '<' || c == '>')
+        .filter(|word| word.starts_with("http://") || word.starts_with("https://"))
+        .map(|word| word.trim_end_matches(['.', ',', ';']).to_string())
+        .collect()
+}
+
+fn domain(url: &str) -> Option<String> {
+    let url = url::Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(host.trim_start_matches("www.").to_string())
+}
+
+fn ratio(part: usize, whole: usize) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    part as f64 / whole as f64
+}
+
+fn percent(fraction: f64) -> String {
+    format!("{:.0}%", fraction * 100.0)
+}
+
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|").replace('\n', " ")
+}
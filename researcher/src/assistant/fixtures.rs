@@ -0,0 +1,208 @@
+use anyhow::Result;
+use reqwest::{Request, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use super::configuration::{FixtureConfig, FixtureMode};
+
+// Response headers worth keeping; the rest only describe the original connection
+const RECORDED_HEADERS: &[&str] = &["content-type", "retry-after"];
+
+static CASSETTES: OnceLock<Mutex<HashMap<PathBuf, Arc<Cassette>>>> = OnceLock::new();
+
+// Bodies are kept as JSON where they are JSON, so fixture files stay readable and diffable
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FixtureBody {
+    Empty,
+    Json(Value),
+    Text(String),
+}
+
+impl FixtureBody {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        if bytes.is_empty() {
+            return FixtureBody::Empty;
+        }
+        match serde_json::from_slice(bytes) {
+            Ok(json) => FixtureBody::Json(json),
+            Err(_) => FixtureBody::Text(String::from_utf8_lossy(bytes).into_owned()),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            FixtureBody::Empty => Vec::new(),
+            FixtureBody::Json(json) => json.to_string().into_bytes(),
+            FixtureBody::Text(text) => text.clone().into_bytes(),
+        }
+    }
+}
+
+// One request to a provider and what it answered. Request headers aren't kept, so API keys never
+// end up in a fixture.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interaction {
+    pub provider: String,
+    pub method: String,
+    pub url: String,
+    pub request: FixtureBody,
+    pub status: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    pub response: FixtureBody,
+}
+
+impl Interaction {
+    fn matches(&self, method: &str, url: &str, request: &FixtureBody) -> bool {
+        self.method == method && self.url == url && &self.request == request
+    }
+
+    fn to_response(&self) -> Result<Response> {
+        let mut response = http02::Response::builder().status(self.status);
+        for (name, value) in &self.headers {
+            response = response.header(name.as_str(), value.as_str());
+        }
+        Ok(Response::from(response.body(self.response.to_bytes())?))
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct FixtureFile {
+    interactions: Vec<Interaction>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    replayed: Vec<bool>,
+}
+
+// A fixture file being recorded or replayed, shared by every client pointed at it
+pub struct Cassette {
+    path: PathBuf,
+    mode: FixtureMode,
+    state: Mutex<CassetteState>,
+    // Why the file couldn't be loaded for replay, reported on every request
+    load_error: Option<String>,
+}
+
+impl Cassette {
+    fn open(config: &FixtureConfig) -> Self {
+        let path = PathBuf::from(&config.path);
+        let (interactions, load_error) = match config.mode {
+            // Recording starts a fresh file
+            FixtureMode::Record => (Vec::new(), None),
+            FixtureMode::Replay => match load(&path) {
+                Ok(file) => (file.interactions, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            },
+        };
+
+        Self {
+            path,
+            mode: config.mode,
+            state: Mutex::new(CassetteState {
+                replayed: vec![false; interactions.len()],
+                interactions,
+            }),
+            load_error,
+        }
+    }
+
+    pub fn mode(&self) -> FixtureMode {
+        self.mode
+    }
+
+    // The recorded answer to `request`. Identical requests are answered in the order they were
+    // recorded, and the last answer is repeated once they run out.
+    pub fn replay(&self, request: &Request) -> Result<Response> {
+        if let Some(error) = &self.load_error {
+            return Err(anyhow::anyhow!("Couldn't load fixtures from {:?}: {}", self.path, error));
+        }
+
+        let method = request.method().as_str();
+        let url = request.url().as_str();
+        let body = request_body(request);
+
+        let mut state = self.state.lock().unwrap();
+        let matching: Vec<usize> = state.interactions.iter()
+            .enumerate()
+            .filter(|(_, interaction)| interaction.matches(method, url, &body))
+            .map(|(index, _)| index)
+            .collect();
+        let index = matching.iter().copied()
+            .find(|index| !state.replayed[*index])
+            .or_else(|| matching.last().copied())
+            .ok_or_else(|| anyhow::anyhow!("No recorded response for {} {} in {:?}", method, url, self.path))?;
+
+        state.replayed[index] = true;
+        state.interactions[index].to_response()
+    }
+
+    // Reads the whole response so it can be written down, and hands back an identical one
+    pub async fn record(&self, provider: &str, request: &Request, response: Response) -> Result<Response> {
+        let status = response.status().as_u16();
+        let headers = response.headers().iter()
+            .filter(|(name, _)| RECORDED_HEADERS.contains(&name.as_str()))
+            .filter_map(|(name, value)| Some((name.as_str().to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let bytes = response.bytes().await?;
+
+        let interaction = Interaction {
+            provider: provider.to_string(),
+            method: request.method().as_str().to_string(),
+            url: request.url().as_str().to_string(),
+            request: request_body(request),
+            status,
+            headers,
+            response: FixtureBody::from_bytes(&bytes),
+        };
+        let replayed = interaction.to_response()?;
+
+        // Saved after every interaction, so a run that fails halfway still leaves what it got
+        let file = {
+            let mut state = self.state.lock().unwrap();
+            state.interactions.push(interaction);
+            state.replayed.push(false);
+            serde_json::to_vec_pretty(&FixtureFile { interactions: state.interactions.clone() })?
+        };
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let partial = self.path.with_extension("json.tmp");
+        std::fs::write(&partial, file)?;
+        std::fs::rename(&partial, &self.path)?;
+
+        Ok(replayed)
+    }
+}
+
+// Clients configured with the same fixture file share one cassette, so parallel tracks record into
+// (or replay from) the same place
+pub fn cassette_for(config: Option<&FixtureConfig>) -> Option<Arc<Cassette>> {
+    let config = config?;
+    let mut cassettes = CASSETTES.get_or_init(Default::default).lock().unwrap();
+    let path = PathBuf::from(&config.path);
+    if let Some(cassette) = cassettes.get(&path).filter(|cassette| cassette.mode == config.mode) {
+        return Some(cassette.clone());
+    }
+
+    let cassette = Arc::new(Cassette::open(config));
+    cassettes.insert(path, cassette.clone());
+    Some(cassette)
+}
+
+fn load(path: &Path) -> Result<FixtureFile> {
+    let data = std::fs::read(path)?;
+    Ok(serde_json::from_slice(&data)?)
+}
+
+fn request_body(request: &Request) -> FixtureBody {
+    request.body()
+        .and_then(|body| body.as_bytes())
+        .map(FixtureBody::from_bytes)
+        .unwrap_or(FixtureBody::Empty)
+}
@@ -1,37 +1,40 @@
 use anyhow::Result;
 use async_trait::async_trait;
-use serde_json::Value;
+use serde_json::{json, Value};
+use std::future::Future;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use ollama_rs::Ollama;
-use ollama_rs::generation::completion::request::GenerationRequest;
 use tokio::sync::broadcast::Sender;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 use std::time::UNIX_EPOCH;
-use std::time::Duration;
-use std::fmt::Display;
+use tracing::{field, Instrument};
 
 use super::configuration::Configuration;
-use super::prompts::{
-    format_query_writer_instructions,
-    format_reflection_instructions,
-    SUMMARIZER_INSTRUCTIONS,
-};
-use super::state::{SummaryState, SummaryStateInput, SummaryStateOutput, StatusUpdate, ResearchTrack};
-use super::utils::{perplexity_search, format_sources};
-use super::groq::GroqClient;
+use super::error::ResearchError;
+use super::prompts::{self, is_english, prompt_version};
+use super::state::{SummaryState, SummaryStateInput, SummaryStateOutput, StatusUpdate, TRACK_NAMES};
+use super::utils::perplexity_search;
+use super::llm;
+use super::metrics::metrics;
+use super::usage::{with_usage_scope, UsageLedger, UsageReport};
+use super::http::{with_retry_listener, RetryListener};
+use super::cache::{with_cache_listener, CacheListener};
 use super::configuration::ResearchMode;
 use super::debate::{generate_debate_perspectives, DebatePerspectives};
+use super::images::{generate_visual_prompts, provider_from_config, render_visuals, ImageReference};
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct ResearchSource {
     title: String,
     url: String,
     content: String,
+    #[serde(default)]
+    language: Option<String>,
 }
 
 #[async_trait]
 pub trait Node: Send + Sync {
+    fn name(&self) -> &'static str;
     async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, track: &str) -> Result<String>;
 }
 
@@ -43,35 +46,19 @@ pub struct FinalizerNode;
 
 #[async_trait]
 impl Node for QueryGeneratorNode {
-    async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, track: &str) -> Result<String> {
+    fn name(&self) -> &'static str {
+        "query_generator"
+    }
+
+    async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, _track: &str) -> Result<String> {
         let research_topic = {
             let state = state.lock().await;
             state.research_topic.clone()
         };
         
-        let instructions = format_query_writer_instructions(&research_topic);
-        let prompt = format!("{}\n\nEnhance this search query while preserving its core meaning. Original query: {}", 
-            instructions, research_topic);
-
-        let response = match config.research_mode {
-            ResearchMode::Local => {
-                println!("Initializing Ollama with model: {}", config.local_llm);
-                let ollama = Ollama::default();
-                let request = GenerationRequest::new(config.local_llm.clone(), prompt);
-                ollama.generate(request).await
-                    .map_err(|e| anyhow::anyhow!("Ollama request failed: {}", e))?
-                    .response
-            },
-            ResearchMode::Remote => {
-                if let Some(groq_api_key) = &config.groq_api_key {
-                    println!("Using Groq with model: {}", config.groq_model);
-                    let groq = GroqClient::new(groq_api_key.clone());
-                    groq.generate(&prompt, &config.groq_model).await?
-                } else {
-                    return Err(anyhow::anyhow!("Groq API key not found"));
-                }
-            }
-        };
+        let prompt = prompts::render(config, "query_writer.jinja", json!({ "research_topic": research_topic }))?;
+
+        let response = llm::generate(&prompt, config).await?;
         
         // Try to parse as JSON, if fails, use the original topic
         let search_query = match serde_json::from_str::<Value>(&response) {
@@ -88,14 +75,30 @@ impl Node for QueryGeneratorNode {
 
 #[async_trait]
 impl Node for WebResearchNode {
-    async fn process(&self, state: Arc<Mutex<SummaryState>>, _config: &Configuration, track: &str) -> Result<String> {
-        let (query, loop_count) = {
+    fn name(&self) -> &'static str {
+        "web_research"
+    }
+
+    async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, track: &str) -> Result<String> {
+        let (query, loop_count, language) = {
             let state = state.lock().await;
             let track_state = state.get_track(track);
-            (track_state.search_query.clone(), track_state.research_loop_count as i32)
+            (track_state.search_query.clone(), track_state.research_loop_count as i32, state.language.clone())
         };
         
-        let search_results = perplexity_search(&query, loop_count).await?;
+        let mut search_results = perplexity_search(&query, loop_count, config).await?;
+
+        // Optionally run the same search again restricted to sources in the output language
+        if config.bilingual_search && !is_english(&language) {
+            let localized_query = format!(
+                "{} (search for sources written in {} and answer in {})",
+                query, language, language
+            );
+            match perplexity_search(&localized_query, loop_count, config).await {
+                Ok(localized) => search_results.results.extend(localized.results),
+                Err(e) => tracing::warn!(language = %language, error = %e, "Localized search failed, continuing with English results"),
+            }
+        }
         
         // Convert search results to structured sources
         let sources: Vec<ResearchSource> = search_results.results.iter()
@@ -103,6 +106,7 @@ impl Node for WebResearchNode {
                 title: result.title.clone(),
                 url: result.url.clone(),
                 content: result.content.clone(),
+                language: result.language.clone(),
             })
             .collect();
         
@@ -110,15 +114,21 @@ impl Node for WebResearchNode {
         {
             let mut state_lock = state.lock().await;
             for source in &sources {
-                state_lock.add_source(track, format!("- {} ({})", source.title, source.url));
+                let line = match &source.language {
+                    Some(language) => format!("- {} ({}) [{}]", source.title, source.url, language),
+                    None => format!("- {} ({})", source.title, source.url),
+                };
+                state_lock.add_source(track, line);
             }
         }
         
         // Format results as JSON with structured content and sources
+        let content = search_results.results.iter()
+            .filter_map(|result| result.raw_content.clone())
+            .collect::<Vec<String>>()
+            .join("\n\n");
         let results_json = serde_json::json!({
-            "content": sources.first()
-                .map(|source| source.content.clone())
-                .unwrap_or_default(),
+            "content": content,
             "sources": sources,
         });
         
@@ -138,12 +148,17 @@ impl Node for WebResearchNode {
 
 #[async_trait]
 impl Node for SummarizerNode {
+    fn name(&self) -> &'static str {
+        "summarizer"
+    }
+
     async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, track: &str) -> Result<String> {
-        let (research_topic, track_state) = {
+        let (research_topic, track_state, language) = {
             let state = state.lock().await;
             (
                 state.research_topic.clone(),
                 state.get_track(track).clone(),
+                state.language.clone(),
             )
         };
         
@@ -162,45 +177,13 @@ impl Node for SummarizerNode {
             (last_research, Vec::new())
         };
         
-        let human_message = if !track_state.running_summary.is_empty() {
-            format!(
-                "<User Input> \n {} \n </User Input>\n\n\
-                <Existing Summary> \n {} \n </Existing Summary>\n\n\
-                <New Search Results> \n {} \n </New Search Results>",
-                research_topic,
-                track_state.running_summary,
-                research_content
-            )
-        } else {
-            format!(
-                "<User Input> \n {} \n </User Input>\n\n\
-                <Search Results> \n {} \n </Search Results>",
-                research_topic,
-                research_content
-            )
-        };
-
-        let response = match config.research_mode {
-            ResearchMode::Local => {
-                let ollama = Ollama::default();
-                let request = GenerationRequest::new(
-                    config.local_llm.clone(),
-                    format!("{}\n\n{}", SUMMARIZER_INSTRUCTIONS, human_message),
-                );
-                ollama.generate(request).await?.response
-            },
-            ResearchMode::Remote => {
-                if let Some(groq_api_key) = &config.groq_api_key {
-                    let groq = GroqClient::new(groq_api_key.clone());
-                    groq.generate(
-                        &format!("{}\n\n{}", SUMMARIZER_INSTRUCTIONS, human_message),
-                        &config.groq_model
-                    ).await?
-                } else {
-                    return Err(anyhow::anyhow!("Groq API key not found"));
-                }
-            }
-        };
+        let prompt = prompts::render(config, "summarizer.jinja", json!({
+            "research_topic": research_topic,
+            "language": language,
+            "existing_summary": track_state.running_summary,
+            "search_results": research_content,
+        }))?;
+        let response = llm::generate(&prompt, config).await?;
         
         let mut summary = response.clone();
         
@@ -228,6 +211,10 @@ impl Node for SummarizerNode {
 
 #[async_trait]
 impl Node for ReflectionNode {
+    fn name(&self) -> &'static str {
+        "reflection"
+    }
+
     async fn process(&self, state: Arc<Mutex<SummaryState>>, config: &Configuration, track: &str) -> Result<String> {
         let (research_topic, track_state) = {
             let state = state.lock().await;
@@ -237,33 +224,11 @@ impl Node for ReflectionNode {
             )
         };
         
-        let instructions = format_reflection_instructions(&research_topic);
-        let human_message = format!(
-            "Identify a knowledge gap and generate a follow-up web search query based on our existing knowledge: {}",
-            track_state.running_summary
-        );
-
-        let response = match config.research_mode {
-            ResearchMode::Local => {
-                let ollama = Ollama::default();
-                let request = GenerationRequest::new(
-                    config.local_llm.clone(),
-                    format!("{}\n\n{}", instructions, human_message),
-                );
-                ollama.generate(request).await?.response
-            },
-            ResearchMode::Remote => {
-                if let Some(groq_api_key) = &config.groq_api_key {
-                    let groq = GroqClient::new(groq_api_key.clone());
-                    groq.generate(
-                        &format!("{}\n\n{}", instructions, human_message),
-                        &config.groq_model
-                    ).await?
-                } else {
-                    return Err(anyhow::anyhow!("Groq API key not found"));
-                }
-            }
-        };
+        let prompt = prompts::render(config, "reflection.jinja", json!({
+            "research_topic": research_topic,
+            "running_summary": track_state.running_summary,
+        }))?;
+        let response = llm::generate(&prompt, config).await?;
         
         // Try to parse as JSON, if fails, use a fallback query
         let query = match serde_json::from_str::<Value>(&response) {
@@ -280,32 +245,55 @@ impl Node for ReflectionNode {
 
 #[async_trait]
 impl Node for FinalizerNode {
+    fn name(&self) -> &'static str {
+        "finalizer"
+    }
+
     async fn process(&self, state: Arc<Mutex<SummaryState>>, _config: &Configuration, _track: &str) -> Result<String> {
         let state = state.lock().await;
-        let track_one = state.get_track("one");
-        let track_two = state.get_track("two");
         
-        let final_summary = format!(
-            "### Track One\n{}\n\n### Track One Sources:\n{}\n\n### Track Two\n{}\n\n### Track Two Sources:\n{}",
-            track_one.running_summary,
-            track_one.sources.join("\n"),
-            track_two.running_summary,
-            track_two.sources.join("\n")
-        );
+        let final_summary = state.tracks.iter()
+            .map(|track| {
+                let title = track_title(&track.name);
+                if let Some(error) = &track.error {
+                    return format!("### {}\n*This track failed and is left out of the report: {}*", title, error.message);
+                }
+                format!(
+                    "### {}\n{}\n\n### {} Sources:\n{}",
+                    title,
+                    track.running_summary,
+                    title,
+                    track.sources.join("\n")
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
         
         Ok(final_summary)
     }
 }
 
+// "one" -> "Track One"
+fn track_title(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => format!("Track {}{}", first.to_uppercase(), chars.as_str()),
+        None => "Track".to_string(),
+    }
+}
+
 pub struct ResearchGraph {
     config: Configuration,
     status_tx: Option<Sender<StatusUpdate>>,
     nodes: Vec<Box<dyn Node>>,
+    // Replaced at the start of every run
+    usage: Arc<UsageLedger>,
 }
 
 impl ResearchGraph {
     pub fn new(config: Configuration) -> Self {
         Self {
+            usage: Arc::new(UsageLedger::new(&config)),
             config,
             status_tx: None,
             nodes: vec![
@@ -318,6 +306,19 @@ impl ResearchGraph {
         }
     }
     
+    // Usage of the current or most recent run
+    pub fn usage(&self) -> UsageReport {
+        self.usage.report()
+    }
+
+    pub fn config(&self) -> &Configuration {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: Configuration) {
+        self.config = config;
+    }
+
     pub fn get_llm_model(&self) -> &str {
         &self.config.local_llm
     }
@@ -351,32 +352,96 @@ impl ResearchGraph {
                 .unwrap_or_default()
                 .as_secs();
 
-            let mut status = StatusUpdate::default();
-            status.phase = phase.to_string();
-            status.message = message.to_string();
-            status.elapsed_time = 0.0;
-            status.timestamp = now;
-            status.chain_of_thought = None;
-            status.track = None;
-            status.perspectives = perspectives;
+            let status = StatusUpdate {
+                phase: phase.to_string(),
+                message: message.to_string(),
+                elapsed_time: 0.0,
+                timestamp: now,
+                chain_of_thought: None,
+                track: None,
+                perspectives,
+                usage: Some(self.usage.totals()),
+                error: None,
+            };
 
             match tx.send(status) {
-                Ok(_) => println!("Sent status update: phase={}, message={}", phase, message),
-                Err(e) => {
-                    eprintln!("Failed to send status update: {} (phase={}, message={})", e, phase, message);
+                Ok(_) => tracing::debug!(phase, message, "Sent status update"),
+                Err(_) => {
+                    metrics().status_updates_dropped.inc();
+                    tracing::debug!(phase, message, "No subscribers for status update");
                 }
             }
-        } else {
-            eprintln!("No status sender available for update: phase={}, message={}", phase, message);
         }
     }
     
     pub async fn process_research(&mut self, input: SummaryStateInput) -> Result<SummaryStateOutput> {
+        let model = match self.config.research_mode {
+            ResearchMode::Local => self.config.local_llm.clone(),
+            ResearchMode::Remote => self.config.groq_model.clone(),
+        };
+        let span = tracing::info_span!(
+            "research_run",
+            topic = %input.research_topic,
+            mode = ?self.config.research_mode,
+            model = %model,
+            latency_ms = field::Empty,
+        );
+
+        async move {
+            let started = Instant::now();
+            let result = self.run_research(input).await;
+            let elapsed = started.elapsed();
+            tracing::Span::current().record("latency_ms", elapsed.as_millis() as u64);
+            metrics().research_run_duration.observe(elapsed.as_secs_f64());
+            let outcome = match &result {
+                Ok(output) if output.state.stopped_early.is_some() => {
+                    tracing::info!("Research run stopped early");
+                    "stopped"
+                }
+                Ok(output) if !output.state.failed_tracks().is_empty() => {
+                    tracing::info!(failed_tracks = output.state.failed_tracks().len(), "Research run completed with failed tracks");
+                    "partial"
+                }
+                Ok(_) => {
+                    tracing::info!("Research run completed");
+                    "completed"
+                }
+                Err(e) => {
+                    let error = ResearchError::classify(e);
+                    tracing::error!(error = %e, code = error.code(), "Research run failed");
+                    self.send_error(&error);
+                    "failed"
+                }
+            };
+            metrics().research_runs.with_label_values(&[outcome]).inc();
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    async fn run_research(&mut self, input: SummaryStateInput) -> Result<SummaryStateOutput> {
+        self.usage = Arc::new(UsageLedger::new(&self.config));
+        let language = self.config.language.clone();
         let state = Arc::new(Mutex::new(SummaryState::with_research_topic(input.research_topic.clone())));
+        {
+            let mut state_lock = state.lock().await;
+            state_lock.set_language(language.clone());
+            // Local runs research a single track, whatever the debate comes up with
+            state_lock.set_track_count(match self.config.research_mode {
+                ResearchMode::Remote => self.config.num_perspectives,
+                ResearchMode::Local => 1,
+            });
+            state_lock.set_prompts(prompt_version(&self.config)?);
+        }
 
         // Generate debate perspectives first
         self.send_status("init", "Generating debate perspectives...", None);
-        let perspectives = generate_debate_perspectives(&input.research_topic, &self.config).await?;
+        let perspectives = self.scoped(
+            None,
+            "debate",
+            generate_debate_perspectives(&input.research_topic, self.config.num_perspectives, &language, &self.config),
+        ).await?;
         
         // Update state with perspectives
         {
@@ -388,47 +453,113 @@ impl ResearchGraph {
         self.send_status("perspectives", "Generated debate perspectives", Some(perspectives));
 
         if let ResearchMode::Remote = self.config.research_mode {
-            // Process both tracks in parallel using tokio::join!
-            let track_one_future = self.process_track(state.clone(), "one");
-            let track_two_future = self.process_track(state.clone(), "two");
-
-            let (track_one_result, track_two_result) = tokio::join!(track_one_future, track_two_future);
-
-            // Check results from both tracks
-            track_one_result?;
-            track_two_result?;
+            // Process one track per perspective in parallel
+            let track_names = state.lock().await.track_names();
+            let track_results = futures::future::join_all(
+                track_names.iter().map(|track| self.process_track(state.clone(), track))
+            ).await;
+
+            // A failed track is recorded and left out; the policy decides whether the rest is enough
+            let total = track_names.len();
+            let mut failures = Vec::new();
+            for (track, result) in track_names.iter().zip(track_results) {
+                if let Err(e) = result {
+                    let error = ResearchError::classify(&e);
+                    tracing::warn!(track = %track, error = %e, code = error.code(), "Research track failed");
+                    self.send_status_with_track("track_failed", &format!("Track {} failed: {}", track, e), None, Some(track));
+                    state.lock().await.set_track_error(track, error.body());
+                    failures.push((track.as_str(), e));
+                }
+            }
+            if !failures.is_empty() && !self.config.partial_results.accepts(total - failures.len(), total) {
+                let failed = failures.iter().map(|(track, _)| *track).collect::<Vec<&str>>().join(", ");
+                let (_, error) = failures.remove(0);
+                let message = format!("{} of {} research tracks failed ({}): {}", failures.len() + 1, total, failed, error);
+                return Err(error.context(message));
+            }
 
             // Generate final summary combining both perspectives
-            let finalizer_node = &self.nodes[4];
             self.send_status("final", "Starting final summary compilation...", None);
-            let response = finalizer_node.process(state.clone(), &self.config, "one").await?;
+            let response = self.run_node(4, state.clone(), "one").await?;
             self.send_status("final", "Completed final summary compilation", None);
 
+            let images = self.illustrate_report(&response).await;
+
             let mut final_state = state.lock().await;
             final_state.set_final_summary(response.clone());
-            drop(final_state);
+            final_state.set_images(images.clone());
+            final_state.set_usage(self.usage.report());
+            final_state.set_served_by(self.usage.served_by());
+            let final_state = final_state.clone();
             
             self.send_status("complete", &response, None);
             
             Ok(SummaryStateOutput {
                 running_summary: response,
+                images,
+                state: final_state,
             })
         } else {
             // Original single-track research process
             let track = "one";
             self.process_track(state.clone(), track).await?;
 
-            let final_state = state.lock().await;
-            let summary = final_state.track_one.running_summary.clone();
+            let summary = state.lock().await.get_track(track).running_summary.clone();
+            let images = self.illustrate_report(&summary).await;
+
+            let mut final_state = state.lock().await;
+            final_state.set_final_summary(summary.clone());
+            final_state.set_images(images.clone());
+            final_state.set_usage(self.usage.report());
+            final_state.set_served_by(self.usage.served_by());
+            let final_state = final_state.clone();
             
             self.send_status("complete", &summary, None);
             
             Ok(SummaryStateOutput {
                 running_summary: summary,
+                images,
+                state: final_state,
             })
         }
     }
 
+    // Visuals are a nice-to-have, so failures are reported but never fail the run
+    async fn illustrate_report(&self, report: &str) -> Vec<ImageReference> {
+        let provider = match provider_from_config(&self.config) {
+            Ok(Some(provider)) => provider,
+            Ok(None) => return Vec::new(),
+            Err(e) => {
+                tracing::warn!(error = %e, "Image provider unavailable");
+                return Vec::new();
+            }
+        };
+
+        if let Err(e) = self.usage.check_budget() {
+            self.send_status("visuals", &format!("Skipped visuals: {}", e), None);
+            return Vec::new();
+        }
+
+        self.send_status("visuals", "Generating visuals for the report...", None);
+        let visual_prompts = generate_visual_prompts(report, &self.config);
+        let prompts = match self.scoped(None, "visuals", visual_prompts).await {
+            Ok(prompts) => prompts,
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to generate visual prompts");
+                self.send_status("visuals", "Skipped visuals: failed to generate image prompts", None);
+                return Vec::new();
+            }
+        };
+
+        let images = self.scoped(None, "visuals", render_visuals(&prompts, provider.as_ref())).await;
+        self.send_status(
+            "visuals",
+            &format!("Generated {} of {} visuals with {}", images.len(), prompts.len(), provider.name()),
+            None,
+        );
+        images
+    }
+
     pub fn update_research_mode(&mut self, mode: ResearchMode) {
         self.config = Configuration {
             research_mode: mode,
@@ -444,7 +575,24 @@ impl ResearchGraph {
         &self.config.groq_model
     }
 
+    pub fn update_language(&mut self, language: String) {
+        self.config = Configuration {
+            language,
+            ..self.config.clone()
+        };
+    }
+
+    pub fn get_language(&self) -> &str {
+        &self.config.language
+    }
+
     async fn process_track(&self, state: Arc<Mutex<SummaryState>>, track: &str) -> Result<()> {
+        self.run_track(state, track)
+            .instrument(tracing::info_span!("track", track))
+            .await
+    }
+
+    async fn run_track(&self, state: Arc<Mutex<SummaryState>>, track: &str) -> Result<()> {
         let mut loop_count = 0;
         let max_loops = self.config.max_web_research_loops;
 
@@ -452,11 +600,10 @@ impl ResearchGraph {
         {
             let state_lock = state.lock().await;
             if let Some(perspectives) = &state_lock.debate_perspectives {
-                let initial_query = if track == "one" {
-                    perspectives.perspective_one.clone()
-                } else {
-                    perspectives.perspective_two.clone()
-                };
+                let index = TRACK_NAMES.iter().position(|name| *name == track).unwrap_or(0);
+                let initial_query = perspectives.get(index)
+                    .unwrap_or(&perspectives.perspective_one)
+                    .to_string();
                 drop(state_lock);
                 
                 let mut state_lock = state.lock().await;
@@ -467,45 +614,9 @@ impl ResearchGraph {
 
         while loop_count < max_loops {
             loop_count += 1;
-            self.send_status_with_track(
-                "loop",
-                &format!("Starting research loop {} of {} for track {}", loop_count, max_loops, track),
-                None,
-                Some(track),
-            );
-
-            // Web Research Phase
-            let web_research_node = &self.nodes[0];
-            self.send_status_with_track("research", "Starting web research...", None, Some(track));
-            let response = web_research_node.process(state.clone(), &self.config, track).await?;
-            self.send_status_with_track("research", "Completed web research", Some(response), Some(track));
-
-            // Summarization Phase
-            let summarizer_node = &self.nodes[1];
-            self.send_status_with_track("summary", "Starting summarization...", None, Some(track));
-            let response = summarizer_node.process(state.clone(), &self.config, track).await?;
-            self.send_status_with_track("summary", "Completed summarization", Some(response), Some(track));
-
-            // Reflection Phase
-            let reflection_node = &self.nodes[2];
-            self.send_status_with_track("reflection", "Starting reflection...", None, Some(track));
-            let response = reflection_node.process(state.clone(), &self.config, track).await?;
-            self.send_status_with_track("reflection", "Completed reflection", Some(response), Some(track));
-
-            // Query Generation Phase
-            let query_node = &self.nodes[3];
-            self.send_status_with_track("query", "Starting query generation...", None, Some(track));
-            let response = query_node.process(state.clone(), &self.config, track).await?;
-            self.send_status_with_track("query", "Generated next query", Some(response), Some(track));
-
-            // Check if we should continue
-            let state_lock = state.lock().await;
-            let should_continue = if track == "one" {
-                state_lock.track_one.should_continue_research()
-            } else {
-                state_lock.track_two.should_continue_research()
-            };
-            drop(state_lock);
+            let should_continue = self.research_loop(state.clone(), track, loop_count, max_loops)
+                .instrument(tracing::info_span!("loop", "loop" = loop_count, max_loops))
+                .await?;
 
             if !should_continue {
                 self.send_status_with_track(
@@ -521,6 +632,165 @@ impl ResearchGraph {
         Ok(())
     }
 
+    // Runs one pass of the node pipeline and reports whether the track wants another
+    async fn research_loop(&self, state: Arc<Mutex<SummaryState>>, track: &str, loop_count: i32, max_loops: i32) -> Result<bool> {
+        self.send_status_with_track(
+            "loop",
+            &format!("Starting research loop {} of {} for track {}", loop_count, max_loops, track),
+            None,
+            Some(track),
+        );
+
+        // Web Research Phase
+        if self.out_of_budget(&state, track).await {
+            return Ok(false);
+        }
+        self.send_status_with_track("research", "Starting web research...", None, Some(track));
+        let response = self.run_node(0, state.clone(), track).await?;
+        self.send_status_with_track("research", "Completed web research", Some(response), Some(track));
+
+        // Summarization Phase
+        if self.out_of_budget(&state, track).await {
+            return Ok(false);
+        }
+        self.send_status_with_track("summary", "Starting summarization...", None, Some(track));
+        let response = self.run_node(1, state.clone(), track).await?;
+        self.send_status_with_track("summary", "Completed summarization", Some(response), Some(track));
+
+        // Reflection Phase
+        if self.out_of_budget(&state, track).await {
+            return Ok(false);
+        }
+        self.send_status_with_track("reflection", "Starting reflection...", None, Some(track));
+        let response = self.run_node(2, state.clone(), track).await?;
+        self.send_status_with_track("reflection", "Completed reflection", Some(response), Some(track));
+
+        // Query Generation Phase
+        if self.out_of_budget(&state, track).await {
+            return Ok(false);
+        }
+        self.send_status_with_track("query", "Starting query generation...", None, Some(track));
+        let response = self.run_node(3, state.clone(), track).await?;
+        self.send_status_with_track("query", "Generated next query", Some(response), Some(track));
+
+        let state_lock = state.lock().await;
+        Ok(state_lock.get_track(track).should_continue_research())
+    }
+
+    // A spent budget ends the track like its last loop would, keeping what it has gathered for the report
+    async fn out_of_budget(&self, state: &Arc<Mutex<SummaryState>>, track: &str) -> bool {
+        let Err(e) = self.usage.check_budget() else {
+            return false;
+        };
+        tracing::info!(track, reason = %e, "Stopping research early");
+        self.send_status_with_track("budget", &format!("Stopping track {}: {}", track, e), None, Some(track));
+        state.lock().await.set_stopped_early(e.to_string());
+        true
+    }
+
+    async fn run_node(&self, index: usize, state: Arc<Mutex<SummaryState>>, track: &str) -> Result<String> {
+        let node = &self.nodes[index];
+        let span = tracing::info_span!("node", node = node.name(), track, latency_ms = field::Empty);
+
+        async move {
+            let started = Instant::now();
+            let result = self.scoped(Some(track), node.name(), node.process(state, &self.config, track)).await;
+            let elapsed = started.elapsed();
+            tracing::Span::current().record("latency_ms", elapsed.as_millis() as u64);
+            metrics().node_duration
+                .with_label_values(&[node.name(), track])
+                .observe(elapsed.as_secs_f64());
+            match &result {
+                Ok(_) => tracing::debug!("Node finished"),
+                Err(e) => tracing::warn!(error = %e, "Node failed"),
+            }
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    // Charges usage to the track and node, and reports provider retries and cache hits on the status stream
+    async fn scoped<F: Future>(&self, track: Option<&str>, node: &'static str, future: F) -> F::Output {
+        let future = with_retry_listener(self.retry_listener(track), future);
+        let future = with_cache_listener(self.cache_listener(track), future);
+        with_usage_scope(self.usage.clone(), track, node, future).await
+    }
+
+    fn retry_listener(&self, track: Option<&str>) -> RetryListener {
+        let status_tx = self.status_tx.clone();
+        let usage = self.usage.clone();
+        let track = track.map(|track| track.to_string());
+
+        Arc::new(move |retry| {
+            let Some(tx) = &status_tx else { return };
+            let _ = tx.send(StatusUpdate {
+                phase: "retry".to_string(),
+                message: format!(
+                    "Retrying {} in {:.1}s (attempt {} of {}): {}",
+                    retry.provider,
+                    retry.delay.as_secs_f64(),
+                    retry.attempt,
+                    retry.max_retries,
+                    retry.reason
+                ),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                track: track.clone(),
+                usage: Some(usage.totals()),
+                ..Default::default()
+            });
+        })
+    }
+
+    fn cache_listener(&self, track: Option<&str>) -> CacheListener {
+        let status_tx = self.status_tx.clone();
+        let usage = self.usage.clone();
+        let track = track.map(|track| track.to_string());
+
+        Arc::new(move |hit| {
+            let Some(tx) = &status_tx else { return };
+            let _ = tx.send(StatusUpdate {
+                phase: "cache".to_string(),
+                message: format!(
+                    "Using a cached {} response ({}) from {} ago",
+                    hit.provider,
+                    hit.model,
+                    format_age(hit.age)
+                ),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                track: track.clone(),
+                usage: Some(usage.totals()),
+                ..Default::default()
+            });
+        })
+    }
+
+    // A typed `error` event for /status subscribers
+    fn send_error(&self, error: &ResearchError) {
+        if let Some(tx) = &self.status_tx {
+            let status = StatusUpdate {
+                phase: "error".to_string(),
+                message: format!("Error: {}", error),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs(),
+                usage: Some(self.usage.totals()),
+                error: Some(error.body()),
+                ..Default::default()
+            };
+            if tx.send(status).is_err() {
+                metrics().status_updates_dropped.inc();
+            }
+        }
+    }
+
     fn send_status_with_track(&self, phase: &str, message: &str, chain_of_thought: Option<String>, track: Option<&str>) {
         if let Some(tx) = &self.status_tx {
             let now = SystemTime::now()
@@ -528,23 +798,35 @@ impl ResearchGraph {
                 .unwrap_or_default()
                 .as_secs();
 
-            let mut status = StatusUpdate::default();
-            status.phase = phase.to_string();
-            status.message = message.to_string();
-            status.elapsed_time = 0.0;
-            status.timestamp = now;
-            status.chain_of_thought = chain_of_thought;
-            status.track = track.map(|t| t.to_string());
-            status.perspectives = None;
+            let status = StatusUpdate {
+                phase: phase.to_string(),
+                message: message.to_string(),
+                elapsed_time: 0.0,
+                timestamp: now,
+                chain_of_thought,
+                track: track.map(|t| t.to_string()),
+                perspectives: None,
+                usage: Some(self.usage.totals()),
+                error: None,
+            };
 
             match tx.send(status) {
-                Ok(_) => println!("Sent status update: phase={}, message={}, track={:?}", phase, message, track),
-                Err(e) => {
-                    eprintln!("Failed to send status update: {} (phase={}, message={}, track={:?})", e, phase, message, track);
+                Ok(_) => tracing::debug!(phase, message, track, "Sent status update"),
+                Err(_) => {
+                    metrics().status_updates_dropped.inc();
+                    tracing::debug!(phase, message, track, "No subscribers for status update");
                 }
             }
-        } else {
-            eprintln!("No status sender available for update: phase={}, message={}, track={:?}", phase, message, track);
         }
     }
-} 
\ No newline at end of file
+}
+
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
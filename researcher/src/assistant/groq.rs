@@ -1,56 +1,31 @@
 use anyhow::Result;
-use serde_json::json;
-use reqwest::Client;
+use super::configuration::HttpPolicy;
+use super::openai::OpenAiClient;
+
+pub use super::openai::ChatCompletion as GroqCompletion;
+
+const GROQ_BASE_URL: &str = "https://api.groq.com/openai/v1";
 
 pub struct GroqClient {
-    api_key: String,
-    client: Client,
+    client: OpenAiClient,
 }
 
 impl GroqClient {
     pub fn new(api_key: String) -> Self {
+        Self::with_policy(api_key, &HttpPolicy::default())
+    }
+
+    pub fn with_policy(api_key: String, policy: &HttpPolicy) -> Self {
         Self {
-            api_key,
-            client: Client::new(),
+            client: OpenAiClient::new("groq", GROQ_BASE_URL, Some(api_key), policy),
         }
     }
 
     pub async fn generate(&self, prompt: &str, model: &str) -> Result<String> {
-        let response = self.client
-            .post("https://api.groq.com/openai/v1/chat/completions")
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&json!({
-                "model": model,
-                "messages": [
-                    {
-                        "role": "system",
-                        "content": "You are a helpful research assistant. Provide clear, accurate, and well-structured responses."
-                    },
-                    {
-                        "role": "user",
-                        "content": prompt
-                    }
-                ],
-                "temperature": 0.7,
-                "max_tokens": 2048
-            }))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await?;
-            return Err(anyhow::anyhow!("Groq API error: {}", error_text));
-        }
-
-        let data = response.json::<serde_json::Value>().await?;
-        
-        let content = data.get("choices")
-            .and_then(|choices| choices.get(0))
-            .and_then(|choice| choice.get("message"))
-            .and_then(|message| message.get("content"))
-            .and_then(|content| content.as_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid response structure from Groq API"))?;
+        Ok(self.complete(prompt, model).await?.content)
+    }
 
-        Ok(content.to_string())
+    pub async fn complete(&self, prompt: &str, model: &str) -> Result<GroqCompletion> {
+        self.client.complete(prompt, model).await
     }
-} 
\ No newline at end of file
+}
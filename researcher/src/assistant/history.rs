@@ -0,0 +1,125 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+use super::configuration::{Configuration, ResearchMode};
+use super::images::ImageReference;
+use super::state::{SummaryState, SummaryStateOutput};
+use crate::podcast::library::{is_valid_id, new_id};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResearchRun {
+    pub id: String,
+    pub topic: String,
+    pub started_at: u64,
+    pub finished_at: u64,
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub research_mode: ResearchMode,
+    // The model that wrote the report, local or Groq depending on the mode
+    pub model: String,
+    pub language: String,
+    pub summary: String,
+    #[serde(default)]
+    pub images: Vec<ImageReference>,
+    pub state: SummaryState,
+}
+
+impl ResearchRun {
+    pub fn new(started_at: u64, config: &Configuration, output: SummaryStateOutput) -> Self {
+        let finished_at = now();
+        let topic = output.state.research_topic.clone();
+        let model = match config.research_mode {
+            ResearchMode::Local => config.local_llm.clone(),
+            ResearchMode::Remote => config.groq_model.clone(),
+        };
+
+        Self {
+            id: new_id(finished_at, &topic),
+            topic,
+            started_at,
+            finished_at,
+            profile: config.profile.clone(),
+            research_mode: config.research_mode.clone(),
+            model,
+            language: config.language.clone(),
+            summary: output.running_summary,
+            images: output.images,
+            state: output.state,
+        }
+    }
+
+    pub fn duration_secs(&self) -> u64 {
+        self.finished_at.saturating_sub(self.started_at)
+    }
+}
+
+// Finished research runs, one `<id>.json` file each
+pub struct RunHistory {
+    dir: PathBuf,
+}
+
+impl RunHistory {
+    pub async fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await
+            .map_err(|e| anyhow::anyhow!("Failed to create research history at {:?}: {}", dir, e))?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub async fn list(&self) -> Result<Vec<ResearchRun>> {
+        let mut runs = Vec::new();
+        let mut entries = fs::read_dir(&self.dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let data = fs::read(&path).await?;
+            match serde_json::from_slice::<ResearchRun>(&data) {
+                Ok(run) => runs.push(run),
+                Err(e) => tracing::warn!(path = ?path, error = %e, "Skipping unreadable research run"),
+            }
+        }
+
+        runs.sort_by_key(|run| std::cmp::Reverse(run.finished_at));
+        Ok(runs)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<ResearchRun>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        match fs::read(self.run_path(id)).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, run: &ResearchRun) -> Result<()> {
+        let data = serde_json::to_vec_pretty(run)?;
+        fs::write(self.run_path(&run.id), data).await?;
+        Ok(())
+    }
+
+    fn run_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,333 @@
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, SystemTime};
+
+use super::configuration::{FixtureMode, HttpPolicy};
+use super::fixtures::{cassette_for, Cassette};
+#[cfg(feature = "testing")]
+use super::mock::MockProviders;
+use super::metrics::metrics;
+use super::ratelimit::{limiter_for, ProviderLimiter};
+
+// Error bodies are included in messages, but some providers return whole HTML pages
+const MAX_ERROR_BODY_CHARS: usize = 500;
+
+static CLIENT: OnceLock<Client> = OnceLock::new();
+
+// One connection pool for every provider; timeouts are set per request
+fn shared_client() -> Client {
+    CLIENT.get_or_init(Client::new).clone()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HttpError {
+    #[error("{provider} returned {status}: {body}")]
+    Status {
+        provider: &'static str,
+        status: StatusCode,
+        body: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("{provider} didn't respond within {}s", timeout.as_secs())]
+    Timeout {
+        provider: &'static str,
+        timeout: Duration,
+    },
+    #[error("{provider} request failed: {source}")]
+    Transport {
+        provider: &'static str,
+        #[source]
+        source: reqwest::Error,
+    },
+    // Recording or replaying a fixture failed
+    #[error("{provider} fixture: {message}")]
+    Fixture {
+        provider: &'static str,
+        message: String,
+    },
+    // A mock provider had no scripted reply for the request
+    #[error("{provider} mock: {message}")]
+    Mock {
+        provider: &'static str,
+        message: String,
+    },
+}
+
+impl HttpError {
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            HttpError::Status { status, .. } => matches!(status.as_u16(), 408 | 425 | 429 | 500 | 502 | 503 | 504),
+            HttpError::Timeout { .. } => true,
+            HttpError::Transport { source, .. } => source.is_connect() || source.is_request(),
+            HttpError::Fixture { .. } | HttpError::Mock { .. } => false,
+        }
+    }
+
+    // HTTP status, "timeout" or "error", for metric labels
+    pub fn status_label(&self) -> String {
+        match self {
+            HttpError::Status { status, .. } => status.as_u16().to_string(),
+            HttpError::Timeout { .. } => "timeout".to_string(),
+            HttpError::Transport { .. } => "error".to_string(),
+            HttpError::Fixture { .. } => "fixture".to_string(),
+            HttpError::Mock { .. } => "mock".to_string(),
+        }
+    }
+
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            HttpError::Status { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+// A retry about to happen, reported to whoever is listening (the status stream during research)
+#[derive(Debug, Clone)]
+pub struct RetryAttempt {
+    pub provider: &'static str,
+    pub attempt: u32,
+    pub max_retries: u32,
+    pub delay: Duration,
+    pub reason: String,
+}
+
+pub type RetryListener = Arc<dyn Fn(&RetryAttempt) + Send + Sync>;
+
+tokio::task_local! {
+    static RETRY_LISTENER: RetryListener;
+}
+
+// Retries made while `future` runs are reported to `listener`
+pub async fn with_retry_listener<F: Future>(listener: RetryListener, future: F) -> F::Output {
+    RETRY_LISTENER.scope(listener, future).await
+}
+
+pub struct HttpClient {
+    provider: &'static str,
+    policy: HttpPolicy,
+    client: Client,
+    limiter: Option<Arc<ProviderLimiter>>,
+    cassette: Option<Arc<Cassette>>,
+    #[cfg(feature = "testing")]
+    mock: Option<Arc<MockProviders>>,
+}
+
+impl HttpClient {
+    pub fn new(provider: &'static str, policy: &HttpPolicy) -> Self {
+        Self {
+            provider,
+            policy: policy.clone(),
+            client: shared_client(),
+            limiter: limiter_for(provider, policy.rate_limits.get(provider)),
+            cassette: cassette_for(policy.fixtures.as_ref()),
+            #[cfg(feature = "testing")]
+            mock: policy.mock.clone(),
+        }
+    }
+
+    pub async fn send(&self, build: impl Fn(&Client) -> RequestBuilder) -> Result<Response, HttpError> {
+        self.send_metered(0, build).await
+    }
+
+    // Settles the token estimate passed to send_metered once the provider reports real usage
+    pub fn reconcile_tokens(&self, estimated: u64, actual: u64) {
+        if let Some(limiter) = &self.limiter {
+            limiter.reconcile_tokens(estimated, actual);
+        }
+    }
+
+    // Sends the request built by `build`, rebuilding it for each retry. Every attempt waits for the
+    // provider's rate limits, counting `estimated_tokens` against its tokens per minute. Only
+    // successful responses are returned; anything else becomes an HttpError once retries run out.
+    pub async fn send_metered(
+        &self,
+        estimated_tokens: u64,
+        build: impl Fn(&Client) -> RequestBuilder,
+    ) -> Result<Response, HttpError> {
+        let timeout = self.policy.timeout_for(self.provider);
+        let mut attempt = 0;
+
+        loop {
+            let permit = match &self.limiter {
+                Some(limiter) => limiter.acquire(estimated_tokens).await,
+                None => None,
+            };
+            let error = match self.execute(build(&self.client).timeout(timeout), timeout).await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = response.headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_retry_after);
+                    let body: String = response.text().await
+                        .unwrap_or_default()
+                        .chars()
+                        .take(MAX_ERROR_BODY_CHARS)
+                        .collect();
+                    HttpError::Status { provider: self.provider, status, body, retry_after }
+                }
+                Err(e) => e,
+            };
+            // The in-flight slot is free for other requests while this one backs off
+            drop(permit);
+
+            if attempt >= self.policy.max_retries || !error.is_retryable() {
+                return Err(error);
+            }
+            attempt += 1;
+
+            let delay = self.retry_delay(&error, attempt);
+            let retry = RetryAttempt {
+                provider: self.provider,
+                attempt,
+                max_retries: self.policy.max_retries,
+                delay,
+                reason: error.to_string(),
+            };
+            tracing::warn!(
+                provider = self.provider,
+                attempt,
+                max_retries = retry.max_retries,
+                delay_ms = delay.as_millis() as u64,
+                error = %error,
+                "Retrying request"
+            );
+            metrics().http_retries.with_label_values(&[self.provider, &error.status_label()]).inc();
+            let _ = RETRY_LISTENER.try_with(|listener| listener(&retry));
+
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    // Sends the request, unless a mock or a fixture file is standing in for the provider
+    async fn execute(&self, builder: RequestBuilder, timeout: Duration) -> Result<Response, HttpError> {
+        let transport = |e: reqwest::Error| {
+            if e.is_timeout() {
+                HttpError::Timeout { provider: self.provider, timeout }
+            } else {
+                HttpError::Transport { provider: self.provider, source: e }
+            }
+        };
+        #[cfg(feature = "testing")]
+        if let Some(mock) = &self.mock {
+            let request = builder.build().map_err(transport)?;
+            return mock.respond(self.provider, &request, timeout).await;
+        }
+        let Some(cassette) = &self.cassette else {
+            return builder.send().await.map_err(transport);
+        };
+
+        let fixture = |e: anyhow::Error| HttpError::Fixture { provider: self.provider, message: e.to_string() };
+        let request = builder.build().map_err(transport)?;
+        match cassette.mode() {
+            FixtureMode::Replay => cassette.replay(&request).map_err(fixture),
+            FixtureMode::Record => {
+                let recorded = request.try_clone()
+                    .ok_or_else(|| fixture(anyhow::anyhow!("Streaming request bodies can't be recorded")))?;
+                let response = self.client.execute(request).await.map_err(transport)?;
+                cassette.record(self.provider, &recorded, response).await.map_err(fixture)
+            }
+        }
+    }
+
+    // The provider's Retry-After when it sent one, otherwise our own backoff; never more than max_backoff_ms
+    fn retry_delay(&self, error: &HttpError, attempt: u32) -> Duration {
+        let max_delay = Duration::from_millis(self.policy.max_backoff_ms);
+        error.retry_after()
+            .unwrap_or_else(|| self.backoff(attempt))
+            .min(max_delay)
+    }
+
+    // Exponential backoff, jittered between half and all of it so parallel tracks don't retry in lockstep
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self.policy.initial_backoff_ms
+            .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+            .min(self.policy.max_backoff_ms);
+        Duration::from_millis(fastrand::u64(ceiling / 2..=ceiling.max(1)))
+    }
+}
+
+// Retry-After is either a number of seconds or an HTTP date
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(secs) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let at = httpdate::parse_http_date(value.trim()).ok()?;
+    Some(at.duration_since(SystemTime::now()).unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(initial_backoff_ms: u64, max_backoff_ms: u64) -> HttpClient {
+        HttpClient::new("test", &HttpPolicy { initial_backoff_ms, max_backoff_ms, ..HttpPolicy::default() })
+    }
+
+    fn too_many_requests(retry_after: Option<Duration>) -> HttpError {
+        HttpError::Status {
+            provider: "test",
+            status: StatusCode::TOO_MANY_REQUESTS,
+            body: String::new(),
+            retry_after,
+        }
+    }
+
+    #[test]
+    fn retry_after_is_read_as_seconds_or_a_date() {
+        assert_eq!(parse_retry_after("20"), Some(Duration::from_secs(20)));
+        assert_eq!(parse_retry_after(" 3 "), Some(Duration::from_secs(3)));
+
+        let at = SystemTime::now() + Duration::from_secs(30);
+        let delay = parse_retry_after(&httpdate::fmt_http_date(at)).unwrap();
+        // HTTP dates only have whole seconds
+        assert!(delay > Duration::from_secs(28) && delay <= Duration::from_secs(30), "{:?}", delay);
+
+        // A date that already passed means retry right away
+        let past = httpdate::fmt_http_date(SystemTime::now() - Duration::from_secs(60));
+        assert_eq!(parse_retry_after(&past), Some(Duration::ZERO));
+
+        assert_eq!(parse_retry_after("soon"), None);
+        assert_eq!(parse_retry_after("-5"), None);
+    }
+
+    #[test]
+    fn backoff_doubles_within_its_jitter_range() {
+        let client = client(100, 60_000);
+
+        for _ in 0..50 {
+            for (attempt, ceiling) in [(1, 100), (2, 200), (3, 400), (4, 800)] {
+                let delay = client.backoff(attempt).as_millis() as u64;
+                assert!((ceiling / 2..=ceiling).contains(&delay), "attempt {}: {} ms", attempt, delay);
+            }
+        }
+    }
+
+    #[test]
+    fn backoff_is_capped_by_max_backoff_ms() {
+        let client = client(100, 1_000);
+
+        for _ in 0..50 {
+            let delay = client.backoff(30).as_millis() as u64;
+            assert!((500..=1_000).contains(&delay), "{} ms", delay);
+        }
+    }
+
+    #[test]
+    fn retry_after_wins_over_backoff_up_to_the_cap() {
+        let client = client(100, 5_000);
+
+        let delay = client.retry_delay(&too_many_requests(Some(Duration::from_secs(2))), 1);
+        assert_eq!(delay, Duration::from_secs(2));
+
+        // A provider asking for longer than we're willing to wait gets max_backoff_ms
+        let delay = client.retry_delay(&too_many_requests(Some(Duration::from_secs(600))), 1);
+        assert_eq!(delay, Duration::from_millis(5_000));
+
+        let delay = client.retry_delay(&too_many_requests(None), 1);
+        assert!(delay <= Duration::from_millis(100), "{:?}", delay);
+    }
+}
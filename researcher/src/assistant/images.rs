@@ -0,0 +1,330 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::configuration::{Configuration, HttpPolicy, ImageProviderKind};
+use super::http::HttpClient;
+use super::llm;
+use super::prompts;
+
+const LUMA_API_URL: &str = "https://api.lumalabs.ai/dream-machine/v1/generations";
+const LUMA_POLL_INTERVAL: Duration = Duration::from_secs(3);
+const LUMA_MAX_POLLS: u32 = 60;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VisualPrompt {
+    pub section: String,
+    pub prompt: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageReference {
+    pub section: String,
+    pub prompt: String,
+    pub url: String,
+    pub provider: String,
+}
+
+#[async_trait]
+pub trait ImageProvider: Send + Sync {
+    fn name(&self) -> &str;
+
+    // Returns a URL the generated image can be fetched from
+    async fn generate(&self, prompt: &str) -> Result<String>;
+}
+
+pub struct LumaImageProvider {
+    api_key: String,
+    http: HttpClient,
+}
+
+impl LumaImageProvider {
+    pub fn new(api_key: String, policy: &HttpPolicy) -> Self {
+        Self {
+            api_key,
+            http: HttpClient::new("luma", policy),
+        }
+    }
+}
+
+#[async_trait]
+impl ImageProvider for LumaImageProvider {
+    fn name(&self) -> &str {
+        "luma"
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        let body = serde_json::json!({
+            "prompt": prompt,
+            "aspect_ratio": "16:9",
+        });
+        let response = self.http
+            .send(|client| client
+                .post(format!("{}/image", LUMA_API_URL))
+                .header("Authorization", format!("Bearer {}", self.api_key))
+                .json(&body))
+            .await?;
+
+        let generation = response.json::<Value>().await?;
+        let id = generation["id"].as_str()
+            .ok_or_else(|| anyhow::anyhow!("Luma API response is missing a generation id"))?
+            .to_string();
+
+        // Generations are asynchronous, so poll until the image is ready. Only successful responses
+        // come back from the client; a generation that is gone or a provider error ends the wait.
+        for _ in 0..LUMA_MAX_POLLS {
+            let generation = self.http
+                .send(|client| client
+                    .get(format!("{}/{}", LUMA_API_URL, id))
+                    .header("Authorization", format!("Bearer {}", self.api_key)))
+                .await
+                .map_err(|e| anyhow::Error::from(e).context(format!("Polling Luma generation {} failed", id)))?
+                .json::<Value>()
+                .await?;
+
+            match luma_image_url(&id, &generation)? {
+                Some(url) => return Ok(url),
+                None => tokio::time::sleep(LUMA_POLL_INTERVAL).await,
+            }
+        }
+
+        Err(anyhow::anyhow!("Timed out waiting for Luma generation {}", id))
+    }
+}
+
+// The image once a polled generation has completed, or None while it is still queued or rendering
+fn luma_image_url(id: &str, generation: &Value) -> Result<Option<String>> {
+    match generation["state"].as_str() {
+        Some("queued") | Some("dreaming") => Ok(None),
+        Some("completed") => generation["assets"]["image"].as_str()
+            .map(|url| Some(url.to_string()))
+            .ok_or_else(|| anyhow::anyhow!("Luma generation {} completed without an image", id)),
+        Some("failed") => Err(anyhow::anyhow!(
+            "Luma generation {} failed: {}",
+            id,
+            generation["failure_reason"].as_str().unwrap_or("unknown reason")
+        )),
+        state => Err(anyhow::anyhow!("Luma generation {} is in an unexpected state: {:?}", id, state)),
+    }
+}
+
+// Renders the prompt onto a plain SVG card so reports keep their layout without network access
+pub struct PlaceholderImageProvider {
+    output_dir: PathBuf,
+    url_prefix: String,
+}
+
+impl PlaceholderImageProvider {
+    pub fn new(output_dir: impl Into<PathBuf>, url_prefix: impl Into<String>) -> Self {
+        Self {
+            output_dir: output_dir.into(),
+            url_prefix: url_prefix.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl ImageProvider for PlaceholderImageProvider {
+    fn name(&self) -> &str {
+        "placeholder"
+    }
+
+    async fn generate(&self, prompt: &str) -> Result<String> {
+        // Named after the prompt, so the same prompt maps to the same file across builds
+        let digest = Sha256::digest(prompt.as_bytes());
+        let hash = u64::from_be_bytes(digest[..8].try_into()?);
+
+        let file_name = format!("{:016x}.svg", hash);
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        tokio::fs::write(self.output_dir.join(&file_name), render_placeholder_svg(prompt, hash)).await?;
+
+        Ok(format!("{}/{}", self.url_prefix.trim_end_matches('/'), file_name))
+    }
+}
+
+pub fn provider_from_config(config: &Configuration) -> Result<Option<Arc<dyn ImageProvider>>> {
+    match config.image_provider {
+        ImageProviderKind::None => Ok(None),
+        ImageProviderKind::Luma => {
+            let api_key = config.luma_api_key.clone()
+                .ok_or_else(|| anyhow::anyhow!("LUMAAI_API_KEY is required for the luma image provider"))?;
+            Ok(Some(Arc::new(LumaImageProvider::new(api_key, &config.http))))
+        }
+        ImageProviderKind::Placeholder => Ok(Some(Arc::new(PlaceholderImageProvider::new(
+            PathBuf::from(&config.podcast_library_dir).join("images"),
+            "/media/images",
+        )))),
+    }
+}
+
+// Splits the final report on its markdown headings, skipping the source listings
+pub fn report_sections(report: &str) -> Vec<(String, String)> {
+    let mut sections: Vec<(String, String)> = Vec::new();
+    let mut heading = "Summary".to_string();
+    let mut body = String::new();
+
+    for line in report.lines() {
+        if let Some(title) = line.trim().strip_prefix('#') {
+            if !body.trim().is_empty() {
+                sections.push((heading.clone(), body.trim().to_string()));
+            }
+            heading = title.trim_start_matches('#').trim().to_string();
+            body.clear();
+        } else {
+            body.push_str(line);
+            body.push('\n');
+        }
+    }
+    if !body.trim().is_empty() {
+        sections.push((heading, body.trim().to_string()));
+    }
+
+    sections.into_iter()
+        .filter(|(heading, _)| !heading.to_lowercase().contains("sources"))
+        .collect()
+}
+
+pub async fn generate_visual_prompts(report: &str, config: &Configuration) -> Result<Vec<VisualPrompt>> {
+    let sections = report_sections(report);
+    if sections.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let section_context: Vec<Value> = sections.iter()
+        .map(|(title, body)| json!({ "title": title, "body": body }))
+        .collect();
+    let prompt = prompts::render(config, "image_prompts.jinja", json!({ "sections": section_context }))?;
+
+    let response = llm::generate(&prompt, config).await?;
+
+    match llm::json_array::<VisualPrompt>(&response) {
+        Some(prompts) if !prompts.is_empty() => Ok(prompts),
+        _ => {
+            tracing::warn!("Failed to parse visual prompts, deriving them from the report sections");
+            Ok(sections.iter()
+                .map(|(heading, body)| VisualPrompt {
+                    section: heading.clone(),
+                    prompt: fallback_prompt(body),
+                })
+                .collect())
+        }
+    }
+}
+
+// One prompt per podcast segment, for illustrating episodes as they play
+pub fn visual_prompts_for_segments<'a>(segments: impl IntoIterator<Item = &'a str>) -> Vec<VisualPrompt> {
+    segments.into_iter()
+        .enumerate()
+        .map(|(i, text)| VisualPrompt {
+            section: format!("Segment {}", i + 1),
+            prompt: fallback_prompt(text),
+        })
+        .collect()
+}
+
+pub async fn render_visuals(prompts: &[VisualPrompt], provider: &dyn ImageProvider) -> Vec<ImageReference> {
+    let mut images = Vec::new();
+
+    for visual in prompts {
+        match provider.generate(&visual.prompt).await {
+            Ok(url) => images.push(ImageReference {
+                section: visual.section.clone(),
+                prompt: visual.prompt.clone(),
+                url,
+                provider: provider.name().to_string(),
+            }),
+            Err(e) => tracing::warn!(section = %visual.section, error = %e, "Failed to generate image"),
+        }
+    }
+
+    images
+}
+
+fn fallback_prompt(text: &str) -> String {
+    let first_sentence = text.split(['.', '!', '?'])
+        .map(|s| s.trim())
+        .find(|s| !s.is_empty())
+        .unwrap_or(text);
+    format!("Editorial illustration, clean modern style, no text: {}", first_sentence)
+}
+
+fn render_placeholder_svg(prompt: &str, hash: u64) -> String {
+    let hue = hash % 360;
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in prompt.split_whitespace() {
+        if current.len() + word.len() + 1 > 48 {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+
+    let text = lines.iter()
+        .take(8)
+        .enumerate()
+        .map(|(i, line)| format!(
+            "<text x=\"80\" y=\"{}\" font-family=\"sans-serif\" font-size=\"36\" fill=\"#ffffff\">{}</text>",
+            200 + i * 52,
+            line.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+        ))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"1280\" height=\"720\" viewBox=\"0 0 1280 720\">\n\
+        <defs><linearGradient id=\"bg\" x1=\"0\" y1=\"0\" x2=\"1\" y2=\"1\">\
+        <stop offset=\"0\" stop-color=\"hsl({}, 60%, 35%)\"/><stop offset=\"1\" stop-color=\"hsl({}, 60%, 15%)\"/>\
+        </linearGradient></defs>\n\
+        <rect width=\"1280\" height=\"720\" fill=\"url(#bg)\"/>\n{}\n</svg>\n",
+        hue,
+        (hue + 40) % 360,
+        text
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn placeholder_names_are_stable() {
+        let dir = std::env::temp_dir().join(format!("researcher-images-{}", std::process::id()));
+        let provider = PlaceholderImageProvider::new(&dir, "/media/images/");
+
+        let url = provider.generate("A heat pump in the snow").await.unwrap();
+
+        // The first eight bytes of the prompt's SHA-256
+        let expected = format!("{:x}", Sha256::digest(b"A heat pump in the snow"))[..16].to_string();
+        assert_eq!(url, format!("/media/images/{}.svg", expected));
+        assert_eq!(provider.generate("A heat pump in the snow").await.unwrap(), url);
+        assert!(dir.join(format!("{}.svg", expected)).exists());
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn luma_polls_wait_only_while_the_generation_is_pending() {
+        assert_eq!(luma_image_url("g1", &json!({ "state": "dreaming" })).unwrap(), None);
+        assert_eq!(
+            luma_image_url("g1", &json!({ "state": "completed", "assets": { "image": "https://cdn.example/a.jpg" } })).unwrap(),
+            Some("https://cdn.example/a.jpg".to_string())
+        );
+
+        let failed = luma_image_url("g1", &json!({ "state": "failed", "failure_reason": "nsfw" })).unwrap_err();
+        assert!(failed.to_string().contains("failed: nsfw"), "{}", failed);
+        assert!(luma_image_url("g1", &json!({ "state": "completed", "assets": {} })).is_err());
+        // An error body without a state would otherwise be polled until the timeout
+        let unexpected = luma_image_url("g1", &json!({ "detail": "Not found" })).unwrap_err();
+        assert!(unexpected.to_string().contains("unexpected state"), "{}", unexpected);
+    }
+}
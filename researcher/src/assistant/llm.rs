@@ -0,0 +1,199 @@
+use anyhow::Result;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Instant;
+use tracing::{field, Instrument};
+
+use super::cache::cached;
+use super::configuration::{Configuration, LlmProvider};
+use super::error::ResearchError;
+use super::groq::GroqClient;
+use super::http::HttpClient;
+use super::metrics::metrics;
+use super::openai::{request_params, OpenAiClient};
+use super::ratelimit::estimate_tokens;
+use super::usage::{record_served_by, record_usage};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
+const OLLAMA_URL: &str = "http://127.0.0.1:11434";
+
+#[derive(Debug, Deserialize)]
+struct OllamaResponse {
+    response: String,
+    prompt_eval_count: Option<u64>,
+    eval_count: Option<u64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub text: String,
+    pub provider: &'static str,
+    pub model: String,
+    pub usage: Option<TokenUsage>,
+    // Answered from the response cache rather than by the provider
+    pub cached: bool,
+}
+
+// The first JSON array of `T` in a reply, skipping any reasoning before `</think>` and stray
+// brackets in the text around the array
+pub fn json_array<T: DeserializeOwned>(response: &str) -> Option<Vec<T>> {
+    let answer = match response.find("</think>") {
+        Some(end) => &response[end + "</think>".len()..],
+        None => response,
+    };
+
+    answer.match_indices('[').find_map(|(start, _)| {
+        serde_json::Deserializer::from_str(&answer[start..])
+            .into_iter::<Vec<T>>()
+            .next()
+            .and_then(|parsed| parsed.ok())
+    })
+}
+
+// Sends a prompt to whichever model the research mode selects
+pub async fn generate(prompt: &str, config: &Configuration) -> Result<String> {
+    Ok(complete(prompt, config).await?.text)
+}
+
+// Tries the research mode's provider, then each fallback in turn, until one answers
+pub async fn complete(prompt: &str, config: &Configuration) -> Result<Completion> {
+    let chain = config.llm_chain();
+    let mut failed = Vec::new();
+
+    for (index, provider) in chain.iter().copied().enumerate() {
+        let error = match complete_with(provider, prompt, config).await {
+            Ok(completion) => {
+                record_served_by(completion.provider, &completion.model, index > 0);
+                return Ok(completion);
+            }
+            Err(e) => e,
+        };
+
+        failed.push(provider.name());
+        match chain.get(index + 1) {
+            Some(next) => tracing::warn!(
+                provider = provider.name(),
+                fallback = next.name(),
+                error = %error,
+                "LLM call failed, falling back"
+            ),
+            // The last provider's error is what the caller sees, so it can still be downcast
+            None if failed.len() > 1 => {
+                let message = format!("Every LLM provider failed ({}), last with: {}", failed.join(", "), error);
+                return Err(error.context(message));
+            }
+            None => return Err(error),
+        }
+    }
+
+    Err(anyhow::anyhow!("No LLM provider configured"))
+}
+
+async fn complete_with(provider: LlmProvider, prompt: &str, config: &Configuration) -> Result<Completion> {
+    let name = provider.name();
+    let model = config.model_for(provider).to_string();
+    let span = tracing::info_span!(
+        "llm",
+        provider = name,
+        model = %model,
+        latency_ms = field::Empty,
+        prompt_tokens = field::Empty,
+        completion_tokens = field::Empty,
+    );
+
+    async move {
+        let started = Instant::now();
+        // Ollama's request has no settings besides the model and prompt
+        let params = match provider {
+            LlmProvider::Ollama => serde_json::json!({}),
+            LlmProvider::Groq | LlmProvider::OpenAi => request_params(),
+        };
+        let result: Result<((String, Option<TokenUsage>), bool)> = cached(config, name, &model, prompt, params, || async {
+            match provider {
+                LlmProvider::Ollama => ollama_generate(prompt, &model, config).await,
+                LlmProvider::Groq => {
+                    let groq_api_key = config.groq_api_key.as_ref()
+                        .ok_or_else(|| ResearchError::Misconfigured("GROQ_API_KEY is not set".to_string()))?;
+                    let groq = GroqClient::with_policy(groq_api_key.clone(), &config.http);
+                    let response = groq.complete(prompt, &model).await?;
+                    Ok((response.content, response.usage))
+                }
+                LlmProvider::OpenAi => {
+                    let client = OpenAiClient::new(name, &config.openai_base_url, config.openai_api_key.clone(), &config.http);
+                    let response = client.complete(prompt, &model).await?;
+                    Ok((response.content, response.usage))
+                }
+            }
+        }).await;
+
+        let elapsed = started.elapsed();
+        let status = match &result {
+            Ok((_, true)) => "cached",
+            Ok(_) => "ok",
+            Err(_) => "error",
+        };
+        metrics().llm_requests.with_label_values(&[name, &model, status]).inc();
+        let ((text, usage), cached) = result?;
+
+        let span = tracing::Span::current();
+        span.record("latency_ms", elapsed.as_millis() as u64);
+        // A cached response cost nothing this time, so it's left out of usage and provider metrics
+        if cached {
+            tracing::debug!("LLM call answered from the cache");
+            return Ok(Completion { text, provider: name, model, usage, cached });
+        }
+
+        metrics().llm_request_duration
+            .with_label_values(&[name, &model])
+            .observe(elapsed.as_secs_f64());
+        record_usage(name, &model, usage.unwrap_or_default());
+        if let Some(usage) = &usage {
+            span.record("prompt_tokens", usage.prompt_tokens);
+            span.record("completion_tokens", usage.completion_tokens);
+            let tokens = &metrics().llm_tokens;
+            tokens.with_label_values(&[name, &model, "prompt"]).inc_by(usage.prompt_tokens);
+            tokens.with_label_values(&[name, &model, "completion"]).inc_by(usage.completion_tokens);
+        }
+        tracing::debug!("LLM call finished");
+
+        Ok(Completion { text, provider: name, model, usage, cached })
+    }
+    .instrument(span)
+    .await
+}
+
+async fn ollama_generate(prompt: &str, model: &str, config: &Configuration) -> Result<(String, Option<TokenUsage>)> {
+    let body = serde_json::json!({
+        "model": model,
+        "prompt": prompt,
+        "stream": false,
+    });
+    let http = HttpClient::new("ollama", &config.http);
+    let estimated_tokens = estimate_tokens(prompt);
+    let response = http
+        .send_metered(estimated_tokens, |client| client.post(format!("{}/api/generate", OLLAMA_URL)).json(&body))
+        .await?
+        .json::<OllamaResponse>()
+        .await
+        .map_err(|e| ResearchError::Parse { what: "the Ollama response".to_string(), message: e.to_string() })?;
+
+    let usage = response.eval_count.map(|eval_count| TokenUsage {
+        prompt_tokens: response.prompt_eval_count.unwrap_or(0),
+        completion_tokens: eval_count,
+    });
+    if let Some(usage) = &usage {
+        http.reconcile_tokens(estimated_tokens, usage.total());
+    }
+    Ok((response.response, usage))
+}
@@ -0,0 +1,170 @@
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+// Research runs and LLM calls take seconds to minutes, so the buckets go well past the defaults
+const SLOW_BUCKETS: &[f64] = &[0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0, 300.0, 600.0];
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub struct Metrics {
+    registry: Registry,
+    pub research_runs: IntCounterVec,
+    pub research_run_duration: Histogram,
+    pub node_duration: HistogramVec,
+    pub llm_requests: IntCounterVec,
+    pub llm_request_duration: HistogramVec,
+    pub llm_tokens: IntCounterVec,
+    pub search_requests: IntCounterVec,
+    pub search_request_duration: HistogramVec,
+    pub http_retries: IntCounterVec,
+    pub rate_limit_wait: HistogramVec,
+    pub cache_lookups: IntCounterVec,
+    pub sse_subscribers: IntGauge,
+    pub sse_lagged_updates: IntCounter,
+    pub sse_receive_errors: IntCounterVec,
+    pub status_updates_dropped: IntCounter,
+}
+
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(|| Metrics::new().expect("metric definitions are valid"))
+}
+
+impl Metrics {
+    fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new_custom(Some("researcher".to_string()), None)?;
+
+        let research_runs = IntCounterVec::new(
+            Opts::new("research_runs_total", "Research runs by outcome"),
+            &["outcome"],
+        )?;
+        let research_run_duration = Histogram::with_opts(
+            HistogramOpts::new("research_run_duration_seconds", "Wall clock time of a research run")
+                .buckets(SLOW_BUCKETS.to_vec()),
+        )?;
+        let node_duration = HistogramVec::new(
+            HistogramOpts::new("node_duration_seconds", "Time spent in each graph node")
+                .buckets(SLOW_BUCKETS.to_vec()),
+            &["node", "track"],
+        )?;
+        let llm_requests = IntCounterVec::new(
+            Opts::new("llm_requests_total", "LLM calls by provider, model and outcome"),
+            &["provider", "model", "status"],
+        )?;
+        let llm_request_duration = HistogramVec::new(
+            HistogramOpts::new("llm_request_duration_seconds", "LLM call latency")
+                .buckets(SLOW_BUCKETS.to_vec()),
+            &["provider", "model"],
+        )?;
+        let llm_tokens = IntCounterVec::new(
+            Opts::new("llm_tokens_total", "Tokens reported by the LLM provider"),
+            &["provider", "model", "kind"],
+        )?;
+        let search_requests = IntCounterVec::new(
+            Opts::new("search_requests_total", "Search calls by provider and HTTP status"),
+            &["provider", "status"],
+        )?;
+        let search_request_duration = HistogramVec::new(
+            HistogramOpts::new("search_request_duration_seconds", "Search call latency")
+                .buckets(SLOW_BUCKETS.to_vec()),
+            &["provider"],
+        )?;
+        let http_retries = IntCounterVec::new(
+            Opts::new("http_retries_total", "Retried provider requests by provider and the status that caused them"),
+            &["provider", "status"],
+        )?;
+        let rate_limit_wait = HistogramVec::new(
+            HistogramOpts::new(
+                "rate_limit_wait_seconds",
+                "Time provider requests spent queued behind client-side rate and concurrency limits",
+            )
+            .buckets(vec![0.001, 0.01, 0.1, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0]),
+            &["provider"],
+        )?;
+        let cache_lookups = IntCounterVec::new(
+            Opts::new("cache_lookups_total", "Response cache lookups by provider and result (hit, miss or expired)"),
+            &["provider", "result"],
+        )?;
+        let sse_subscribers = IntGauge::new("sse_subscribers", "Clients connected to GET /status")?;
+        let sse_lagged_updates = IntCounter::new(
+            "sse_lagged_updates_total",
+            "Status updates skipped because a /status subscriber fell behind the broadcast channel",
+        )?;
+        let sse_receive_errors = IntCounterVec::new(
+            Opts::new("sse_receive_errors_total", "Broadcast receive errors seen by /status subscribers"),
+            &["kind"],
+        )?;
+        let status_updates_dropped = IntCounter::new(
+            "status_updates_dropped_total",
+            "Status updates sent while nobody was subscribed",
+        )?;
+
+        registry.register(Box::new(research_runs.clone()))?;
+        registry.register(Box::new(research_run_duration.clone()))?;
+        registry.register(Box::new(node_duration.clone()))?;
+        registry.register(Box::new(llm_requests.clone()))?;
+        registry.register(Box::new(llm_request_duration.clone()))?;
+        registry.register(Box::new(llm_tokens.clone()))?;
+        registry.register(Box::new(search_requests.clone()))?;
+        registry.register(Box::new(search_request_duration.clone()))?;
+        registry.register(Box::new(http_retries.clone()))?;
+        registry.register(Box::new(rate_limit_wait.clone()))?;
+        registry.register(Box::new(cache_lookups.clone()))?;
+        registry.register(Box::new(sse_subscribers.clone()))?;
+        registry.register(Box::new(sse_lagged_updates.clone()))?;
+        registry.register(Box::new(sse_receive_errors.clone()))?;
+        registry.register(Box::new(status_updates_dropped.clone()))?;
+
+        Ok(Self {
+            registry,
+            research_runs,
+            research_run_duration,
+            node_duration,
+            llm_requests,
+            llm_request_duration,
+            llm_tokens,
+            search_requests,
+            search_request_duration,
+            http_retries,
+            rate_limit_wait,
+            cache_lookups,
+            sse_subscribers,
+            sse_lagged_updates,
+            sse_receive_errors,
+            status_updates_dropped,
+        })
+    }
+
+    // Prometheus text exposition format
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+// Keeps the subscriber gauge right however a /status stream ends
+pub struct SubscriberGuard;
+
+impl SubscriberGuard {
+    pub fn new() -> Self {
+        metrics().sse_subscribers.inc();
+        Self
+    }
+}
+
+impl Default for SubscriberGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for SubscriberGuard {
+    fn drop(&mut self) {
+        metrics().sse_subscribers.dec();
+    }
+}
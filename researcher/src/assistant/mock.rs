@@ -0,0 +1,266 @@
+use reqwest::{Request, Response};
+use serde_json::{json, Value};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::configuration::Configuration;
+use super::http::HttpError;
+
+// What a mock provider answers with. Replies are sent in the provider's own wire format, so the
+// real clients parse them, retry them and classify their errors exactly as they would live.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockReply {
+    Text(String),
+    // Perplexity answers come with the URLs they cite
+    Search { answer: String, citations: Vec<String> },
+    // A successful response whose body isn't the JSON the provider promises
+    Malformed,
+    Status { status: u16, body: String, retry_after_secs: Option<u64> },
+    Timeout,
+}
+
+impl MockReply {
+    pub fn text(text: &str) -> Self {
+        MockReply::Text(text.to_string())
+    }
+
+    pub fn json(value: Value) -> Self {
+        MockReply::Text(value.to_string())
+    }
+
+    // The way reasoning models such as deepseek-r1 answer
+    pub fn thinking(thoughts: &str, answer: &str) -> Self {
+        MockReply::Text(format!("<think>{}</think>{}", thoughts, answer))
+    }
+
+    pub fn search(answer: &str, citations: &[&str]) -> Self {
+        MockReply::Search {
+            answer: answer.to_string(),
+            citations: citations.iter().map(|citation| citation.to_string()).collect(),
+        }
+    }
+
+    pub fn error(status: u16, body: &str) -> Self {
+        MockReply::Status { status, body: body.to_string(), retry_after_secs: None }
+    }
+
+    pub fn rate_limited(retry_after_secs: u64) -> Self {
+        MockReply::Status {
+            status: 429,
+            body: "rate limit exceeded".to_string(),
+            retry_after_secs: Some(retry_after_secs),
+        }
+    }
+}
+
+struct Rule {
+    pattern: String,
+    reply: MockReply,
+    // Rules with a count are used up, letting a later rule take over
+    remaining: Option<usize>,
+}
+
+// Replies picked by the first rule whose pattern appears in the prompt, with every prompt kept
+#[derive(Default)]
+struct Script {
+    rules: Mutex<Vec<Rule>>,
+    fallback: Option<MockReply>,
+    latency: Duration,
+    calls: Mutex<Vec<String>>,
+}
+
+impl Script {
+    fn add(&mut self, pattern: &str, reply: MockReply, remaining: Option<usize>) {
+        self.rules.get_mut().unwrap().push(Rule { pattern: pattern.to_string(), reply, remaining });
+    }
+
+    fn reply(&self, prompt: &str) -> Option<MockReply> {
+        self.calls.lock().unwrap().push(prompt.to_string());
+        let mut rules = self.rules.lock().unwrap();
+        let rule = rules.iter_mut()
+            .find(|rule| rule.remaining != Some(0) && prompt.contains(&rule.pattern));
+        match rule {
+            Some(rule) => {
+                if let Some(remaining) = &mut rule.remaining {
+                    *remaining -= 1;
+                }
+                Some(rule.reply.clone())
+            }
+            None => self.fallback.clone(),
+        }
+    }
+
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
+
+// Stands in for Ollama, Groq and OpenAI, matching on the prompt
+#[derive(Default)]
+pub struct MockLlm {
+    script: Script,
+}
+
+impl MockLlm {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn when(mut self, pattern: &str, reply: MockReply) -> Self {
+        self.script.add(pattern, reply, None);
+        self
+    }
+
+    // Answers the first `times` matching prompts, then leaves them to the other rules
+    pub fn times(mut self, pattern: &str, times: usize, reply: MockReply) -> Self {
+        self.script.add(pattern, reply, Some(times));
+        self
+    }
+
+    // For prompts no rule matches; without one they fail the call
+    pub fn otherwise(mut self, reply: MockReply) -> Self {
+        self.script.fallback = Some(reply);
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.script.latency = latency;
+        self
+    }
+
+    pub fn prompts(&self) -> Vec<String> {
+        self.script.calls()
+    }
+}
+
+// Stands in for Perplexity, matching on the search query
+#[derive(Default)]
+pub struct MockSearch {
+    script: Script,
+}
+
+impl MockSearch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn when(mut self, pattern: &str, reply: MockReply) -> Self {
+        self.script.add(pattern, reply, None);
+        self
+    }
+
+    pub fn times(mut self, pattern: &str, times: usize, reply: MockReply) -> Self {
+        self.script.add(pattern, reply, Some(times));
+        self
+    }
+
+    pub fn otherwise(mut self, reply: MockReply) -> Self {
+        self.script.fallback = Some(reply);
+        self
+    }
+
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.script.latency = latency;
+        self
+    }
+
+    pub fn queries(&self) -> Vec<String> {
+        self.script.calls()
+    }
+}
+
+pub struct MockProviders {
+    pub llm: MockLlm,
+    pub search: MockSearch,
+}
+
+// Kept short, as configurations holding the mocks are logged
+impl fmt::Debug for MockProviders {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MockProviders")
+    }
+}
+
+impl MockProviders {
+    pub fn new(llm: MockLlm, search: MockSearch) -> Arc<Self> {
+        Arc::new(Self { llm, search })
+    }
+
+    // Points every HTTP client built from `config` at these mocks. Configurations derived from it
+    // with overrides or a profile keep them, and tests running side by side never share scripts.
+    pub fn install(self: &Arc<Self>, config: &mut Configuration) {
+        config.http.mock = Some(self.clone());
+    }
+
+    pub async fn respond(&self, provider: &'static str, request: &Request, timeout: Duration) -> Result<Response, HttpError> {
+        let unanswered = |message: String| HttpError::Mock { provider, message };
+        let body: Value = request.body()
+            .and_then(|body| body.as_bytes())
+            .and_then(|bytes| serde_json::from_slice(bytes).ok())
+            .unwrap_or_default();
+
+        let (script, prompt) = match provider {
+            "ollama" => (&self.llm.script, body["prompt"].as_str()),
+            "groq" | "openai" => (&self.llm.script, last_message(&body)),
+            "perplexity" => (&self.search.script, last_message(&body)),
+            _ => return Err(unanswered(format!("no mock answers {} {}", request.method(), request.url()))),
+        };
+        let prompt = prompt.unwrap_or_default();
+        let reply = script.reply(prompt)
+            .ok_or_else(|| unanswered(format!("no mock reply for {:?}", truncate(prompt))))?;
+
+        if !script.latency.is_zero() {
+            tokio::time::sleep(script.latency).await;
+        }
+
+        let (status, body, retry_after) = match reply {
+            MockReply::Timeout => return Err(HttpError::Timeout { provider, timeout }),
+            MockReply::Malformed => (200, "{\"choices\": [".to_string(), None),
+            MockReply::Status { status, body, retry_after_secs } => (status, body, retry_after_secs),
+            MockReply::Text(text) => (200, wire_format(provider, &body, prompt, &text, &[]).to_string(), None),
+            MockReply::Search { answer, citations } => {
+                (200, wire_format(provider, &body, prompt, &answer, &citations).to_string(), None)
+            }
+        };
+
+        let mut response = http02::Response::builder()
+            .status(status)
+            .header("content-type", "application/json");
+        if let Some(secs) = retry_after {
+            response = response.header("retry-after", secs.to_string());
+        }
+        let response = response.body(body.into_bytes())
+            .map_err(|e| unanswered(e.to_string()))?;
+        Ok(Response::from(response))
+    }
+}
+
+// Token counts are made up from the text length, like the rate limiter's estimates
+fn wire_format(provider: &str, request: &Value, prompt: &str, text: &str, citations: &[String]) -> Value {
+    let prompt_tokens = (prompt.len() / 4) as u64;
+    let completion_tokens = (text.len() / 4) as u64;
+    match provider {
+        "ollama" => json!({
+            "model": request["model"],
+            "response": text,
+            "done": true,
+            "prompt_eval_count": prompt_tokens,
+            "eval_count": completion_tokens,
+        }),
+        _ => json!({
+            "model": request["model"],
+            "choices": [{ "index": 0, "message": { "role": "assistant", "content": text } }],
+            "citations": citations,
+            "usage": { "prompt_tokens": prompt_tokens, "completion_tokens": completion_tokens },
+        }),
+    }
+}
+
+fn last_message(body: &Value) -> Option<&str> {
+    body["messages"].as_array()?.last()?["content"].as_str()
+}
+
+fn truncate(prompt: &str) -> String {
+    prompt.chars().take(80).collect()
+}
@@ -1,7 +1,25 @@
+pub mod batch;
+pub mod cache;
 pub mod configuration;
 pub mod debate;
+pub mod diagnostics;
+pub mod enrich;
+pub mod eval;
+pub mod error;
+pub mod fixtures;
 pub mod graph;
+pub mod history;
+pub mod http;
 pub mod groq;
+pub mod images;
+pub mod llm;
+pub mod metrics;
+#[cfg(feature = "testing")]
+pub mod mock;
+pub mod openai;
 pub mod prompts;
+pub mod ratelimit;
+pub mod report;
 pub mod state;
+pub mod usage;
 pub mod utils; 
\ No newline at end of file
@@ -0,0 +1,102 @@
+use anyhow::Result;
+use serde_json::json;
+use super::configuration::HttpPolicy;
+use super::error::ResearchError;
+use super::http::HttpClient;
+use super::llm::TokenUsage;
+use super::ratelimit::estimate_tokens;
+
+const MAX_COMPLETION_TOKENS: u64 = 2048;
+const TEMPERATURE: f64 = 0.7;
+const SYSTEM_PROMPT: &str = "You are a helpful research assistant. Provide clear, accurate, and well-structured responses.";
+
+// Everything besides the model and prompt that shapes a completion, for cache keys
+pub fn request_params() -> serde_json::Value {
+    json!({
+        "system": SYSTEM_PROMPT,
+        "temperature": TEMPERATURE,
+        "max_tokens": MAX_COMPLETION_TOKENS,
+    })
+}
+
+pub struct ChatCompletion {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+}
+
+// Any server speaking the OpenAI chat completions API: OpenAI itself, Groq, vLLM, LM Studio, ...
+pub struct OpenAiClient {
+    provider: &'static str,
+    base_url: String,
+    api_key: Option<String>,
+    http: HttpClient,
+}
+
+impl OpenAiClient {
+    pub fn new(provider: &'static str, base_url: &str, api_key: Option<String>, policy: &HttpPolicy) -> Self {
+        Self {
+            provider,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            http: HttpClient::new(provider, policy),
+        }
+    }
+
+    pub async fn complete(&self, prompt: &str, model: &str) -> Result<ChatCompletion> {
+        let body = json!({
+            "model": model,
+            "messages": [
+                {
+                    "role": "system",
+                    "content": SYSTEM_PROMPT
+                },
+                {
+                    "role": "user",
+                    "content": prompt
+                }
+            ],
+            "temperature": TEMPERATURE,
+            "max_tokens": MAX_COMPLETION_TOKENS
+        });
+        // Providers count the completion limit against tokens per minute until the response is in
+        let estimated_tokens = estimate_tokens(prompt) + MAX_COMPLETION_TOKENS;
+        let response = self.http
+            .send_metered(estimated_tokens, |client| {
+                let request = client
+                    .post(format!("{}/chat/completions", self.base_url))
+                    .json(&body);
+                match &self.api_key {
+                    Some(api_key) => request.header("Authorization", format!("Bearer {}", api_key)),
+                    None => request,
+                }
+            })
+            .await?;
+
+        let data = response.json::<serde_json::Value>().await
+            .map_err(|e| ResearchError::Parse { what: format!("the {} response", self.provider), message: e.to_string() })?;
+
+        let content = data.get("choices")
+            .and_then(|choices| choices.get(0))
+            .and_then(|choice| choice.get("message"))
+            .and_then(|message| message.get("content"))
+            .and_then(|content| content.as_str())
+            .ok_or_else(|| ResearchError::Parse {
+                what: format!("the {} response", self.provider),
+                message: "no message content".to_string(),
+            })?;
+
+        let usage = data.get("usage").map(|usage| TokenUsage {
+            prompt_tokens: usage["prompt_tokens"].as_u64().unwrap_or(0),
+            completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+        });
+
+        if let Some(usage) = &usage {
+            self.http.reconcile_tokens(estimated_tokens, usage.total());
+        }
+
+        Ok(ChatCompletion {
+            content: content.to_string(),
+            usage,
+        })
+    }
+}
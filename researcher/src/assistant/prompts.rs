@@ -1,20 +1,150 @@
-pub const QUERY_WRITER_INSTRUCTIONS: &str = r#"You are a research assistant tasked with generating effective search queries.
-Your goal is to create a search query that will help gather relevant information about the research topic.
-Format your response as JSON with a single key 'query' containing the search query."#;
+use anyhow::Result;
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::Path;
 
-pub const SUMMARIZER_INSTRUCTIONS: &str = r#"You are a research assistant tasked with summarizing information.
-Analyze the search results and create a comprehensive summary that addresses the research topic.
-If there's an existing summary, integrate the new information with it."#;
+use super::configuration::Configuration;
+use super::error::ResearchError;
 
-pub const REFLECTION_INSTRUCTIONS: &str = r#"You are a research assistant tasked with identifying knowledge gaps.
-Analyze the current summary and identify what important aspects of {research_topic} still need to be explored.
-Generate a follow-up search query to fill these gaps.
-Format your response as JSON with a single key 'follow_up_query' containing the search query."#;
+// Bump when a built-in template changes, so runs before and after can be told apart
+pub const BUILTIN_PROMPTS_VERSION: &str = "builtin-2";
 
-pub fn format_query_writer_instructions(research_topic: &str) -> String {
-    QUERY_WRITER_INSTRUCTIONS.replace("{research_topic}", research_topic)
+// Compiled in, so the binary runs from any directory. A `[prompts] dir` replaces any of them by name.
+const BUILTIN_TEMPLATES: &[(&str, &str)] = &[
+    ("query_writer.jinja", include_str!("../../prompts/query_writer.jinja")),
+    ("summarizer.jinja", include_str!("../../prompts/summarizer.jinja")),
+    ("reflection.jinja", include_str!("../../prompts/reflection.jinja")),
+    ("debate.jinja", include_str!("../../prompts/debate.jinja")),
+    ("image_prompts.jinja", include_str!("../../prompts/image_prompts.jinja")),
+    ("enrichment.jinja", include_str!("../../prompts/enrichment.jinja")),
+    ("grounded_enrichment.jinja", include_str!("../../prompts/grounded_enrichment.jinja")),
+    ("podcast_script.jinja", include_str!("../../prompts/podcast_script.jinja")),
+    ("judge.jinja", include_str!("../../prompts/judge.jinja")),
+    ("partials/language.jinja", include_str!("../../prompts/partials/language.jinja")),
+];
+
+// Which prompts a run used, saved with its state
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PromptVersion {
+    pub version: String,
+    // Of every template in effect, so edited prompts show up even when the version wasn't bumped
+    pub fingerprint: String,
+    // Templates read from the prompts directory, replacing built-in ones or added as partials
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom: Vec<String>,
+}
+
+// Renders `name` with the variables in `context`, a JSON object. Templates are read on every call,
+// so edits to a prompts directory apply from the next run on.
+pub fn render(config: &Configuration, name: &str, context: Value) -> Result<String> {
+    let env = environment(config);
+    let rendered = env.get_template(name)
+        .and_then(|template| template.render(context))
+        .map_err(|e| template_error(name, e))?;
+    Ok(rendered)
+}
+
+pub fn prompt_version(config: &Configuration) -> Result<PromptVersion> {
+    let custom = match &config.prompts.dir {
+        Some(dir) => custom_templates(Path::new(dir))
+            .map_err(|e| ResearchError::Misconfigured(format!("Failed to read the prompt templates in {}: {}", dir, e)))?,
+        None => BTreeMap::new(),
+    };
+    let mut templates: BTreeMap<String, String> = BUILTIN_TEMPLATES.iter()
+        .map(|(name, source)| (name.to_string(), source.to_string()))
+        .collect();
+    templates.extend(custom.clone());
+
+    let mut hasher = Sha256::new();
+    for (name, source) in &templates {
+        hasher.update(name.as_bytes());
+        hasher.update([0]);
+        hasher.update(source.as_bytes());
+        hasher.update([0]);
+    }
+    let fingerprint: String = hasher.finalize().iter().take(6).map(|byte| format!("{:02x}", byte)).collect();
+
+    let version = match (&config.prompts.version, custom.is_empty()) {
+        (Some(version), _) => version.clone(),
+        (None, true) => BUILTIN_PROMPTS_VERSION.to_string(),
+        (None, false) => "custom".to_string(),
+    };
+    Ok(PromptVersion { version, fingerprint, custom: custom.into_keys().collect() })
 }
 
-pub fn format_reflection_instructions(research_topic: &str) -> String {
-    REFLECTION_INSTRUCTIONS.replace("{research_topic}", research_topic)
-} 
\ No newline at end of file
+// Compiles every template, so a broken prompts directory is caught before a run needs it
+pub fn check_templates(config: &Configuration) -> Result<()> {
+    let env = environment(config);
+    for (name, _) in BUILTIN_TEMPLATES {
+        env.get_template(name).map_err(|e| template_error(name, e))?;
+    }
+    Ok(())
+}
+
+pub fn is_english(language: &str) -> bool {
+    matches!(language.trim().to_lowercase().as_str(), "" | "en" | "eng" | "english")
+}
+
+fn environment(config: &Configuration) -> Environment<'static> {
+    let mut env = Environment::new();
+    env.set_trim_blocks(true);
+    env.set_lstrip_blocks(true);
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    // Custom templates fail on a misspelled variable instead of leaving a blank in the prompt
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.add_test("english", |language: &str| is_english(language));
+
+    let dir = config.prompts.dir.clone();
+    env.set_loader(move |name| {
+        let custom = read_custom(dir.as_deref(), name).map_err(|e| {
+            minijinja::Error::new(minijinja::ErrorKind::InvalidOperation, "failed to read the template").with_source(e)
+        })?;
+        Ok(custom.or_else(|| {
+            BUILTIN_TEMPLATES.iter()
+                .find(|(builtin, _)| *builtin == name)
+                .map(|(_, source)| source.to_string())
+        }))
+    });
+    env
+}
+
+// Every .jinja file under `dir`, named by its path relative to it
+fn custom_templates(dir: &Path) -> std::io::Result<BTreeMap<String, String>> {
+    let mut templates = BTreeMap::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if path.extension().is_some_and(|extension| extension == "jinja") {
+                let name = path.strip_prefix(dir).unwrap_or(&path)
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                templates.insert(name, std::fs::read_to_string(&path)?);
+            }
+        }
+    }
+    Ok(templates)
+}
+
+// A template missing from the prompts directory falls back to the built-in one
+fn read_custom(dir: Option<&str>, name: &str) -> std::io::Result<Option<String>> {
+    let Some(dir) = dir else {
+        return Ok(None);
+    };
+    match std::fs::read_to_string(Path::new(dir).join(name)) {
+        Ok(source) => Ok(Some(source)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn template_error(name: &str, e: minijinja::Error) -> anyhow::Error {
+    ResearchError::Misconfigured(format!("Prompt template {}: {}", name, e)).into()
+}
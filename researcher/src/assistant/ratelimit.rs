@@ -0,0 +1,201 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use super::configuration::RateLimit;
+use super::metrics::metrics;
+
+static LIMITERS: OnceLock<Mutex<HashMap<&'static str, Arc<ProviderLimiter>>>> = OnceLock::new();
+
+// Roughly four characters per token for the English-heavy prompts we send
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.len() / 4) as u64
+}
+
+// Holds `capacity` units and refills them evenly over a minute
+struct TokenBucket {
+    capacity: f64,
+    per_second: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32) -> Self {
+        let capacity = limit.max(1) as f64;
+        Self {
+            capacity,
+            per_second: capacity / 60.0,
+            available: capacity,
+            updated: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_second).min(self.capacity);
+        self.updated = now;
+    }
+
+    // Takes `amount` now, or says how long until it can be taken
+    fn take(&mut self, amount: f64) -> Result<(), Duration> {
+        self.refill();
+        // Anything larger than the whole bucket would never fit, so it waits for a full bucket instead
+        let amount = amount.min(self.capacity);
+        if self.available >= amount {
+            self.available -= amount;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((amount - self.available) / self.per_second))
+        }
+    }
+
+    // Estimates are settled once the real usage is known; the bucket may go into debt
+    fn adjust(&mut self, amount: f64) {
+        self.refill();
+        self.available = (self.available + amount).min(self.capacity);
+    }
+}
+
+pub struct ProviderLimiter {
+    provider: &'static str,
+    limit: RateLimit,
+    requests: Option<Mutex<TokenBucket>>,
+    tokens: Option<Mutex<TokenBucket>>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl ProviderLimiter {
+    fn new(provider: &'static str, limit: &RateLimit) -> Self {
+        Self {
+            provider,
+            limit: limit.clone(),
+            requests: limit.requests_per_minute.map(|rpm| Mutex::new(TokenBucket::per_minute(rpm))),
+            tokens: limit.tokens_per_minute.map(|tpm| Mutex::new(TokenBucket::per_minute(tpm))),
+            in_flight: limit.max_in_flight.map(|max| Arc::new(Semaphore::new(max.max(1)))),
+        }
+    }
+
+    // Waits for a request slot, `estimated_tokens` of token budget and a free in-flight slot.
+    // The returned permit holds the in-flight slot until it is dropped.
+    pub async fn acquire(&self, estimated_tokens: u64) -> Option<OwnedSemaphorePermit> {
+        let started = Instant::now();
+
+        if let Some(requests) = &self.requests {
+            wait_for(requests, 1.0).await;
+        }
+        if let Some(tokens) = &self.tokens {
+            wait_for(tokens, estimated_tokens as f64).await;
+        }
+        let permit = match &self.in_flight {
+            // The semaphore is never closed
+            Some(in_flight) => in_flight.clone().acquire_owned().await.ok(),
+            None => None,
+        };
+
+        let waited = started.elapsed();
+        metrics().rate_limit_wait
+            .with_label_values(&[self.provider])
+            .observe(waited.as_secs_f64());
+        if waited >= Duration::from_secs(1) {
+            tracing::debug!(provider = self.provider, waited_ms = waited.as_millis() as u64, "Waited for rate limit");
+        }
+        permit
+    }
+
+    pub fn reconcile_tokens(&self, estimated: u64, actual: u64) {
+        if let Some(tokens) = &self.tokens {
+            tokens.lock().unwrap().adjust(estimated as f64 - actual as f64);
+        }
+    }
+}
+
+async fn wait_for(bucket: &Mutex<TokenBucket>, amount: f64) {
+    loop {
+        let wait = match bucket.lock().unwrap().take(amount) {
+            Ok(()) => return,
+            Err(wait) => wait,
+        };
+        tokio::time::sleep(wait).await;
+    }
+}
+
+// The limiter for a provider, shared across every caller in the process. Changing the configured
+// limits starts a fresh limiter; requests already holding the old one finish under it.
+pub fn limiter_for(provider: &'static str, limit: Option<&RateLimit>) -> Option<Arc<ProviderLimiter>> {
+    let limit = limit.filter(|limit| !limit.is_unlimited())?;
+    let mut limiters = LIMITERS.get_or_init(Default::default).lock().unwrap();
+    if let Some(limiter) = limiters.get(provider).filter(|limiter| &limiter.limit == limit) {
+        return Some(limiter.clone());
+    }
+
+    let limiter = Arc::new(ProviderLimiter::new(provider, limit));
+    limiters.insert(provider, limiter.clone());
+    Some(limiter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(requests_per_minute: u32) -> RateLimit {
+        RateLimit { requests_per_minute: Some(requests_per_minute), ..RateLimit::default() }
+    }
+
+    #[test]
+    fn a_bucket_hands_out_its_capacity_then_says_how_long_to_wait() {
+        let mut bucket = TokenBucket::per_minute(60);
+
+        assert!(bucket.take(60.0).is_ok());
+        let wait = bucket.take(1.0).unwrap_err();
+        // One unit refills every second
+        assert!(wait > Duration::from_millis(900) && wait <= Duration::from_secs(1), "{:?}", wait);
+    }
+
+    #[test]
+    fn more_than_the_capacity_waits_for_a_full_bucket() {
+        let mut bucket = TokenBucket::per_minute(60);
+        bucket.take(60.0).unwrap();
+
+        let wait = bucket.take(600.0).unwrap_err();
+
+        assert!(wait > Duration::from_secs(59) && wait <= Duration::from_secs(60), "{:?}", wait);
+    }
+
+    #[test]
+    fn adjusting_settles_estimates_and_may_go_into_debt() {
+        let mut bucket = TokenBucket::per_minute(60);
+        bucket.take(60.0).unwrap();
+
+        // The estimate was 10 too high, so those come back
+        bucket.adjust(10.0);
+        assert!(bucket.take(10.0).is_ok());
+
+        // It was 30 too low, so the next caller waits for the debt as well
+        bucket.adjust(-30.0);
+        let wait = bucket.take(1.0).unwrap_err();
+        assert!(wait > Duration::from_secs(30) && wait <= Duration::from_secs(31), "{:?}", wait);
+
+        // Refunds never overfill the bucket
+        let mut bucket = TokenBucket::per_minute(60);
+        bucket.adjust(100.0);
+        assert!(bucket.take(60.0).is_ok());
+        assert!(bucket.take(1.0).is_err());
+    }
+
+    #[test]
+    fn limiters_are_shared_until_the_limits_change() {
+        let first = limiter_for("reuse-test", Some(&limit(60))).unwrap();
+        let second = limiter_for("reuse-test", Some(&limit(60))).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let changed = limiter_for("reuse-test", Some(&limit(30))).unwrap();
+        assert!(!Arc::ptr_eq(&first, &changed));
+        assert!(Arc::ptr_eq(&changed, &limiter_for("reuse-test", Some(&limit(30))).unwrap()));
+
+        assert!(limiter_for("reuse-test", Some(&RateLimit::default())).is_none());
+        assert!(limiter_for("reuse-test", None).is_none());
+    }
+}
@@ -0,0 +1,631 @@
+use anyhow::Result;
+use base64::Engine;
+use pulldown_cmark::{Event, HeadingLevel, Options, Parser, Tag, TagEnd};
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::history::ResearchRun;
+use super::images::ImageReference;
+use super::usage::UsageTotals;
+
+const REPORT_CSS: &str = "body { font-family: Georgia, serif; max-width: 46em; margin: 2em auto; padding: 0 1em; line-height: 1.6; color: #222; }
+h1, h2, h3 { font-family: Helvetica, Arial, sans-serif; line-height: 1.25; }
+img { max-width: 100%; height: auto; }
+a { color: #0b5cad; word-break: break-word; }";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+    Json,
+    Docx,
+    Epub,
+}
+
+impl ReportFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "md" | "markdown" => Some(ReportFormat::Markdown),
+            "html" | "htm" => Some(ReportFormat::Html),
+            "json" => Some(ReportFormat::Json),
+            "docx" => Some(ReportFormat::Docx),
+            "epub" => Some(ReportFormat::Epub),
+            _ => None,
+        }
+    }
+
+    // Picks the supported type the Accept header ranks highest. Ties go to the more specific range,
+    // then to the client's order; wildcards and text/plain only stand in for markdown.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        const ALL: &[ReportFormat] = &[
+            ReportFormat::Markdown,
+            ReportFormat::Html,
+            ReportFormat::Json,
+            ReportFormat::Docx,
+            ReportFormat::Epub,
+        ];
+
+        let ranges: Vec<(String, f32)> = accept.split(',')
+            .map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next().unwrap_or_default().trim().to_lowercase();
+                let quality = parts
+                    .filter_map(|param| param.trim().strip_prefix("q="))
+                    .find_map(|q| q.trim().parse::<f32>().ok())
+                    .unwrap_or(1.0)
+                    .clamp(0.0, 1.0);
+                (media_type, quality)
+            })
+            .collect();
+        let candidates = |media_type: &str| -> Option<(u8, &'static [ReportFormat])> {
+            match media_type {
+                "text/markdown" => Some((3, &ALL[0..1])),
+                "text/html" | "application/xhtml+xml" => Some((3, &ALL[1..2])),
+                "application/json" => Some((3, &ALL[2..3])),
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some((3, &ALL[3..4])),
+                "application/epub+zip" => Some((3, &ALL[4..5])),
+                "text/plain" => Some((2, &ALL[0..1])),
+                "text/*" => Some((1, &ALL[0..2])),
+                "*/*" => Some((0, ALL)),
+                _ => None,
+            }
+        };
+
+        // q=0 on a specific type rules it out, even where a wildcard would match it
+        let refused: Vec<ReportFormat> = ranges.iter()
+            .filter(|(_, quality)| *quality == 0.0)
+            .filter_map(|(media_type, _)| candidates(media_type).filter(|(specificity, _)| *specificity == 3))
+            .flat_map(|(_, formats)| formats.iter().copied())
+            .collect();
+
+        let mut best: Option<(f32, u8, ReportFormat)> = None;
+        for (media_type, quality) in &ranges {
+            if *quality == 0.0 {
+                continue;
+            }
+            let Some((specificity, formats)) = candidates(media_type) else {
+                continue;
+            };
+            let Some(format) = formats.iter().find(|format| !refused.contains(format)) else {
+                continue;
+            };
+            let better = best.is_none_or(|(best_quality, best_specificity, _)| {
+                *quality > best_quality || (*quality == best_quality && specificity > best_specificity)
+            });
+            if better {
+                best = Some((*quality, specificity, *format));
+            }
+        }
+
+        best.map(|(_, _, format)| format)
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "md",
+            ReportFormat::Html => "html",
+            ReportFormat::Json => "json",
+            ReportFormat::Docx => "docx",
+            ReportFormat::Epub => "epub",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Html => "text/html; charset=utf-8",
+            ReportFormat::Json => "application/json",
+            ReportFormat::Docx => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            ReportFormat::Epub => "application/epub+zip",
+        }
+    }
+
+    // Binary formats are served as downloads
+    pub fn is_binary(&self) -> bool {
+        matches!(self, ReportFormat::Docx | ReportFormat::Epub)
+    }
+}
+
+// A generated image stored in the media directory, so it can be bundled into the report
+struct EmbeddedImage {
+    file_name: String,
+    media_type: &'static str,
+    data: Vec<u8>,
+}
+
+pub struct Report<'a> {
+    run: &'a ResearchRun,
+    images: Vec<(&'a ImageReference, Option<EmbeddedImage>)>,
+}
+
+impl<'a> Report<'a> {
+    // Images served from `/media` are read from `media_dir`; remote images stay links
+    pub async fn load(run: &'a ResearchRun, media_dir: Option<&Path>) -> Self {
+        let mut images = Vec::new();
+        for image in &run.images {
+            let embedded = match media_dir {
+                Some(media_dir) => load_media_image(media_dir, &image.url).await,
+                None => None,
+            };
+            images.push((image, embedded));
+        }
+
+        Self { run, images }
+    }
+
+    pub fn render(&self, format: ReportFormat) -> Result<Vec<u8>> {
+        match format {
+            ReportFormat::Markdown => Ok(self.markdown(ImageLinks::Original).into_bytes()),
+            ReportFormat::Html => Ok(self.html().into_bytes()),
+            ReportFormat::Json => Ok(serde_json::to_vec_pretty(self.run)?),
+            ReportFormat::Docx => self.docx(),
+            ReportFormat::Epub => self.epub(),
+        }
+    }
+
+    fn markdown(&self, links: ImageLinks) -> String {
+        let run = self.run;
+        let mut out = format!("# {}\n\n", run.topic.trim());
+        out.push_str(&format!("*{}*\n\n", self.byline()));
+
+        if let Some(reason) = &run.state.stopped_early {
+            out.push_str(&format!("> **Stopped early:** {}. The report covers the research done until then.\n\n", reason));
+        }
+
+        let failed = run.state.failed_tracks();
+        if !failed.is_empty() {
+            out.push_str(&format!(
+                "> **Partial result:** {} of {} research tracks failed and are left out.\n",
+                failed.len(),
+                run.state.tracks.len()
+            ));
+            for track in failed {
+                if let Some(error) = &track.error {
+                    out.push_str(&format!("> - Track {} ({}): {}\n", track.name, error.code, error.message));
+                }
+            }
+            out.push('\n');
+        }
+
+        if let Some(perspectives) = &run.state.debate_perspectives {
+            out.push_str("## Perspectives\n\n");
+            for perspective in perspectives.all() {
+                out.push_str(&format!("- {}\n", perspective));
+            }
+            out.push('\n');
+        }
+
+        out.push_str(run.summary.trim());
+        out.push('\n');
+
+        if !self.images.is_empty() {
+            out.push_str("\n## Illustrations\n\n");
+            for (image, embedded) in &self.images {
+                let line = match (links, embedded) {
+                    (ImageLinks::DataUri, Some(embedded)) => format!(
+                        "![{}](data:{};base64,{})",
+                        image.section,
+                        embedded.media_type,
+                        base64::engine::general_purpose::STANDARD.encode(&embedded.data)
+                    ),
+                    (ImageLinks::Bundled, Some(embedded)) => format!("![{}](images/{})", image.section, embedded.file_name),
+                    (ImageLinks::Original, _) | (ImageLinks::DataUri, None) => format!("![{}]({})", image.section, image.url),
+                    (ImageLinks::Bundled, None) | (ImageLinks::Text, _) => format!("- {}: <{}>", image.section, image.url),
+                };
+                out.push_str(&line);
+                out.push_str("\n\n");
+            }
+        }
+
+        if let Some(usage) = &run.state.usage {
+            out.push_str("\n## Usage\n\n");
+            out.push_str(&format!("- Total: {}\n", usage_line(&usage.total)));
+            for (track, totals) in &usage.by_track {
+                out.push_str(&format!("- Track {}: {}\n", track, usage_line(totals)));
+            }
+            for (model, totals) in &usage.by_model {
+                out.push_str(&format!("- {}: {}\n", model, usage_line(totals)));
+            }
+            if !usage.unpriced_models.is_empty() {
+                let models: Vec<&str> = usage.unpriced_models.iter().map(|m| m.as_str()).collect();
+                out.push_str(&format!("- No price configured for {}\n", models.join(", ")));
+            }
+            for served in run.state.served_by.iter().filter(|served| served.fallback) {
+                let node = match &served.track {
+                    Some(track) => format!("track {} {}", track, served.node),
+                    None => served.node.clone(),
+                };
+                out.push_str(&format!(
+                    "- Fallback: {} served by {}/{} ({} calls)\n",
+                    node, served.provider, served.model, served.calls
+                ));
+            }
+        }
+
+        out
+    }
+
+    fn byline(&self) -> String {
+        let run = self.run;
+        format!(
+            "Researched {} with {} ({:?} mode) in {}",
+            httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(run.finished_at)),
+            run.model,
+            run.research_mode,
+            run.language
+        )
+    }
+
+    fn html(&self) -> String {
+        format!(
+            "<!DOCTYPE html>\n<html lang=\"{}\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<style>\n{}\n</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            language_code(&self.run.language),
+            escape_xml(&self.run.topic),
+            REPORT_CSS,
+            markdown_to_html(&self.markdown(ImageLinks::DataUri))
+        )
+    }
+
+    fn docx(&self) -> Result<Vec<u8>> {
+        let body = markdown_to_docx_body(&self.markdown(ImageLinks::Text));
+        let document = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <w:document xmlns:w=\"http://schemas.openxmlformats.org/wordprocessingml/2006/main\"><w:body>{}<w:sectPr/></w:body></w:document>",
+            body
+        );
+        let core = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"yes\"?>\n\
+            <cp:coreProperties xmlns:cp=\"http://schemas.openxmlformats.org/package/2006/metadata/core-properties\" \
+            xmlns:dc=\"http://purl.org/dc/elements/1.1/\" xmlns:dcterms=\"http://purl.org/dc/terms/\" \
+            xmlns:xsi=\"http://www.w3.org/2001/XMLSchema-instance\">\
+            <dc:title>{}</dc:title><dc:creator>ZU-LM Researcher</dc:creator><dc:language>{}</dc:language>\
+            <dcterms:created xsi:type=\"dcterms:W3CDTF\">{}</dcterms:created></cp:coreProperties>",
+            escape_xml(&self.run.topic),
+            language_code(&self.run.language),
+            iso8601(self.run.finished_at)
+        );
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let files = [
+            ("[Content_Types].xml", DOCX_CONTENT_TYPES.to_string()),
+            ("_rels/.rels", DOCX_RELS.to_string()),
+            ("word/_rels/document.xml.rels", DOCX_DOCUMENT_RELS.to_string()),
+            ("word/styles.xml", DOCX_STYLES.to_string()),
+            ("word/document.xml", document),
+            ("docProps/core.xml", core),
+        ];
+        for (name, contents) in files {
+            zip.start_file(name, options)?;
+            zip.write_all(contents.as_bytes())?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    fn epub(&self) -> Result<Vec<u8>> {
+        let run = self.run;
+        let title = escape_xml(&run.topic);
+        let language = language_code(&run.language);
+
+        let chapter = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+            <html xmlns=\"http://www.w3.org/1999/xhtml\" xml:lang=\"{}\" lang=\"{}\">\n\
+            <head><meta charset=\"utf-8\"/><title>{}</title><link rel=\"stylesheet\" href=\"style.css\"/></head>\n\
+            <body>\n{}</body>\n</html>\n",
+            language,
+            language,
+            title,
+            markdown_to_html(&self.markdown(ImageLinks::Bundled))
+        );
+        let nav = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n\
+            <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+            <head><meta charset=\"utf-8\"/><title>{}</title></head>\n\
+            <body><nav epub:type=\"toc\"><ol><li><a href=\"report.xhtml\">{}</a></li></ol></nav></body>\n</html>\n",
+            title,
+            title
+        );
+
+        let embedded: Vec<&EmbeddedImage> = self.images.iter()
+            .filter_map(|(_, embedded)| embedded.as_ref())
+            .collect();
+        let image_items = embedded.iter()
+            .enumerate()
+            .map(|(i, image)| format!(
+                "<item id=\"image{}\" href=\"images/{}\" media-type=\"{}\"/>",
+                i + 1,
+                escape_xml(&image.file_name),
+                image.media_type
+            ))
+            .collect::<String>();
+        let package = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+            <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"run-id\">\n\
+            <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+            <dc:identifier id=\"run-id\">urn:zu-lm:research:{}</dc:identifier><dc:title>{}</dc:title>\
+            <dc:language>{}</dc:language><dc:creator>ZU-LM Researcher</dc:creator>\
+            <meta property=\"dcterms:modified\">{}</meta></metadata>\n\
+            <manifest><item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\
+            <item id=\"report\" href=\"report.xhtml\" media-type=\"application/xhtml+xml\"/>\
+            <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>{}</manifest>\n\
+            <spine><itemref idref=\"report\"/></spine>\n</package>\n",
+            escape_xml(&run.id),
+            title,
+            language,
+            iso8601(run.finished_at),
+            image_items
+        );
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        // The mimetype entry has to come first and be stored uncompressed
+        zip.start_file("mimetype", SimpleFileOptions::default().compression_method(CompressionMethod::Stored))?;
+        zip.write_all(b"application/epub+zip")?;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let files = [
+            ("META-INF/container.xml", EPUB_CONTAINER.as_bytes()),
+            ("OEBPS/content.opf", package.as_bytes()),
+            ("OEBPS/nav.xhtml", nav.as_bytes()),
+            ("OEBPS/report.xhtml", chapter.as_bytes()),
+            ("OEBPS/style.css", REPORT_CSS.as_bytes()),
+        ];
+        for (name, contents) in files {
+            zip.start_file(name, options)?;
+            zip.write_all(contents)?;
+        }
+        for image in embedded {
+            zip.start_file(format!("OEBPS/images/{}", image.file_name), options)?;
+            zip.write_all(&image.data)?;
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ImageLinks {
+    Original,
+    DataUri,
+    Bundled,
+    Text,
+}
+
+async fn load_media_image(media_dir: &Path, url: &str) -> Option<EmbeddedImage> {
+    let relative = url.strip_prefix("/media/")?;
+    // Only plain relative paths, never anything that climbs out of the media directory
+    if relative.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return None;
+    }
+
+    let path: PathBuf = media_dir.join(relative);
+    let media_type = match path.extension()?.to_str()?.to_lowercase().as_str() {
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "webp" => "image/webp",
+        "gif" => "image/gif",
+        _ => return None,
+    };
+    let data = tokio::fs::read(&path).await.ok()?;
+
+    Some(EmbeddedImage {
+        file_name: path.file_name()?.to_string_lossy().to_string(),
+        media_type,
+        data,
+    })
+}
+
+fn usage_line(totals: &UsageTotals) -> String {
+    format!(
+        "{} tokens ({} prompt, {} completion) in {} calls, ${:.4}",
+        totals.total_tokens(),
+        totals.prompt_tokens,
+        totals.completion_tokens,
+        totals.calls,
+        totals.cost_usd
+    )
+}
+
+// Reports come from model output, so raw HTML in them is shown as text rather than rendered
+fn markdown_to_html(markdown: &str) -> String {
+    let events = Parser::new_ext(markdown, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH)
+        .map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, events);
+    html
+}
+
+// Builds WordprocessingML paragraphs from the markdown, with headings and bullets mapped to styles
+fn markdown_to_docx_body(markdown: &str) -> String {
+    #[derive(Default)]
+    struct Paragraph {
+        style: Option<String>,
+        runs: String,
+    }
+
+    let mut body = String::new();
+    let mut paragraph = Paragraph::default();
+    let mut bold = 0;
+    let mut italic = 0;
+    let mut list_depth: usize = 0;
+    let mut code_block = false;
+    let mut links: Vec<String> = Vec::new();
+
+    let flush = |body: &mut String, paragraph: &mut Paragraph| {
+        if paragraph.runs.is_empty() {
+            paragraph.style = None;
+            return;
+        }
+        let properties = paragraph.style.take()
+            .map(|style| format!("<w:pPr><w:pStyle w:val=\"{}\"/></w:pPr>", style))
+            .unwrap_or_default();
+        body.push_str(&format!("<w:p>{}{}</w:p>", properties, std::mem::take(&mut paragraph.runs)));
+    };
+    let run = |text: &str, bold: bool, italic: bool, mono: bool| {
+        let mut properties = String::new();
+        if bold {
+            properties.push_str("<w:b/>");
+        }
+        if italic {
+            properties.push_str("<w:i/>");
+        }
+        if mono {
+            properties.push_str("<w:rFonts w:ascii=\"Consolas\" w:hAnsi=\"Consolas\"/>");
+        }
+        let properties = if properties.is_empty() {
+            String::new()
+        } else {
+            format!("<w:rPr>{}</w:rPr>", properties)
+        };
+        format!("<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>", properties, escape_xml(text))
+    };
+
+    for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                flush(&mut body, &mut paragraph);
+                paragraph.style = Some(match level {
+                    HeadingLevel::H1 => "Title".to_string(),
+                    HeadingLevel::H2 => "Heading1".to_string(),
+                    HeadingLevel::H3 => "Heading2".to_string(),
+                    _ => "Heading3".to_string(),
+                });
+            }
+            // The first paragraph of a list item continues the bullet
+            Event::Start(Tag::Paragraph) if list_depth == 0 || paragraph.style.is_none() => {
+                flush(&mut body, &mut paragraph);
+            }
+            Event::Start(Tag::List(_)) => {
+                flush(&mut body, &mut paragraph);
+                list_depth += 1;
+            }
+            Event::End(TagEnd::List(_)) => {
+                list_depth = list_depth.saturating_sub(1);
+            }
+            Event::Start(Tag::Item) => {
+                flush(&mut body, &mut paragraph);
+                paragraph.style = Some("ListParagraph".to_string());
+                paragraph.runs.push_str(&run(&format!("{}• ", "    ".repeat(list_depth.saturating_sub(1))), false, false, false));
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                flush(&mut body, &mut paragraph);
+                code_block = true;
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                code_block = false;
+            }
+            Event::Start(Tag::Strong) => bold += 1,
+            Event::End(TagEnd::Strong) => bold -= 1,
+            Event::Start(Tag::Emphasis) => italic += 1,
+            Event::End(TagEnd::Emphasis) => italic -= 1,
+            Event::Start(Tag::Link { dest_url, .. }) => links.push(dest_url.to_string()),
+            Event::End(TagEnd::Link) => {
+                if let Some(url) = links.pop() {
+                    paragraph.runs.push_str(&run(&format!(" ({})", url), false, false, false));
+                }
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                paragraph.runs.push_str(&run(&format!("[Image: {}]", dest_url), false, true, false));
+            }
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) if code_block => {
+                for line in text.lines() {
+                    paragraph.runs.push_str(&run(line, false, false, true));
+                    flush(&mut body, &mut paragraph);
+                }
+            }
+            Event::Text(text) | Event::Html(text) | Event::InlineHtml(text) => {
+                paragraph.runs.push_str(&run(&text, bold > 0, italic > 0, false));
+            }
+            Event::Code(code) => paragraph.runs.push_str(&run(&code, false, false, true)),
+            Event::SoftBreak => paragraph.runs.push_str(&run(" ", false, false, false)),
+            Event::HardBreak => paragraph.runs.push_str("<w:r><w:br/></w:r>"),
+            Event::End(TagEnd::Heading(_)) | Event::End(TagEnd::Paragraph) | Event::End(TagEnd::Item) | Event::Rule => {
+                flush(&mut body, &mut paragraph);
+            }
+            _ => {}
+        }
+    }
+    flush(&mut body, &mut paragraph);
+
+    body
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Configured languages are English names, documents want a language tag
+pub fn language_code(language: &str) -> String {
+    whatlang::Lang::all()
+        .iter()
+        .find(|lang| lang.eng_name().eq_ignore_ascii_case(language.trim()))
+        .map(|lang| match lang.code() {
+            // whatlang uses ISO 639-3 codes; the common languages get their two-letter tags
+            "eng" => "en".to_string(),
+            "deu" => "de".to_string(),
+            "fra" => "fr".to_string(),
+            "spa" => "es".to_string(),
+            "ita" => "it".to_string(),
+            "por" => "pt".to_string(),
+            "nld" => "nl".to_string(),
+            "rus" => "ru".to_string(),
+            "cmn" => "zh".to_string(),
+            "jpn" => "ja".to_string(),
+            "kor" => "ko".to_string(),
+            "ara" => "ar".to_string(),
+            code => code.to_string(),
+        })
+        .unwrap_or_else(|| "en".to_string())
+}
+
+// "2024-05-01T12:00:00Z" from a unix timestamp
+fn iso8601(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let time = secs % 86_400;
+
+    // Civil date from days since the epoch, after Howard Hinnant's days_from_civil inverse
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3_600,
+        (time % 3_600) / 60,
+        time % 60
+    )
+}
+
+const DOCX_CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/><Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/></Types>"#;
+
+const DOCX_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#;
+
+const DOCX_DOCUMENT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/></Relationships>"#;
+
+const DOCX_STYLES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main"><w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri"/><w:sz w:val="22"/></w:rPr></w:rPrDefault><w:pPrDefault><w:pPr><w:spacing w:after="160" w:line="276" w:lineRule="auto"/></w:pPr></w:pPrDefault></w:docDefaults><w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/></w:style><w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:rPr><w:sz w:val="48"/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:spacing w:before="360"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="32"/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:spacing w:before="240"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:sz w:val="26"/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:pPr><w:keepNext/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/></w:rPr></w:style><w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:pPr><w:spacing w:after="60"/><w:ind w:left="360"/></w:pPr></w:style></w:styles>"#;
+
+const EPUB_CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container"><rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
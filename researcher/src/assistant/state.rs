@@ -1,73 +1,126 @@
 use serde::{Deserialize, Serialize};
 use super::debate::DebatePerspectives;
+use super::error::ErrorBody;
+use super::images::ImageReference;
+use super::prompts::PromptVersion;
+use super::usage::{ServedBy, UsageReport, UsageTotals};
+
+// Tracks are named rather than numbered so status updates stay readable ("track one")
+pub const TRACK_NAMES: &[&str] = &["one", "two", "three", "four", "five"];
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ResearchTrack {
+    pub name: String,
     pub search_query: String,
     pub research_loop_count: usize,
     pub running_summary: String,
     pub sources: Vec<String>,
     pub web_research_results: Vec<String>,
+    // Why the track failed, when the run carried on without it
+    #[serde(default)]
+    pub error: Option<ErrorBody>,
 }
 
 impl ResearchTrack {
     pub fn new() -> Self {
+        Self::named(TRACK_NAMES[0])
+    }
+
+    pub fn named(name: &str) -> Self {
         Self {
+            name: name.to_string(),
             search_query: String::new(),
             research_loop_count: 0,
             running_summary: String::new(),
             sources: Vec::new(),
             web_research_results: Vec::new(),
+            error: None,
         }
     }
 
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+
     pub fn should_continue_research(&self) -> bool {
         // Continue if either:
         // 1. We haven't started yet (no summary or results)
         // 2. We have meaningful content and haven't reached diminishing returns
         (self.running_summary.is_empty() && self.web_research_results.is_empty()) ||
-        (!self.running_summary.is_empty() && self.web_research_results.len() > 0 && self.research_loop_count < 3)
+        (!self.running_summary.is_empty() && !self.web_research_results.is_empty() && self.research_loop_count < 3)
+    }
+}
+
+impl Default for ResearchTrack {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SummaryState {
     pub research_topic: String,
-    pub track_one: ResearchTrack,
-    pub track_two: ResearchTrack,
+    pub language: String,
+    pub tracks: Vec<ResearchTrack>,
     pub final_summary: Option<String>,
     pub debate_perspectives: Option<DebatePerspectives>,
+    pub images: Vec<ImageReference>,
+    #[serde(default)]
+    pub usage: Option<UsageReport>,
+    // Which LLM provider answered each node, including fallbacks
+    #[serde(default)]
+    pub served_by: Vec<ServedBy>,
+    // The prompt templates the run was given
+    #[serde(default)]
+    pub prompts: Option<PromptVersion>,
+    // Why research ended before every loop ran, such as the budget running out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stopped_early: Option<String>,
 }
 
 impl SummaryState {
     pub fn with_research_topic(topic: String) -> Self {
         Self {
             research_topic: topic,
-            track_one: ResearchTrack::new(),
-            track_two: ResearchTrack::new(),
+            language: "English".to_string(),
+            tracks: TRACK_NAMES[..2].iter().map(|name| ResearchTrack::named(name)).collect(),
             final_summary: None,
             debate_perspectives: None,
+            images: Vec::new(),
+            usage: None,
+            served_by: Vec::new(),
+            prompts: None,
+            stopped_early: None,
         }
     }
 
+    pub fn set_language(&mut self, language: String) {
+        self.language = language;
+    }
+
     pub fn set_debate_perspectives(&mut self, perspectives: DebatePerspectives) {
         self.debate_perspectives = Some(perspectives);
     }
 
+    // One track per debate perspective, capped at the number of track names
+    pub fn set_track_count(&mut self, count: usize) {
+        let count = count.clamp(1, TRACK_NAMES.len());
+        self.tracks = TRACK_NAMES[..count].iter().map(|name| ResearchTrack::named(name)).collect();
+    }
+
+    pub fn track_names(&self) -> Vec<String> {
+        self.tracks.iter().map(|track| track.name.clone()).collect()
+    }
+
     pub fn get_track(&self, track: &str) -> &ResearchTrack {
-        match track {
-            "one" => &self.track_one,
-            "two" => &self.track_two,
-            _ => &self.track_one, // Default to track one
-        }
+        self.tracks.iter()
+            .find(|t| t.name == track)
+            .unwrap_or(&self.tracks[0]) // Default to track one
     }
 
     pub fn get_track_mut(&mut self, track: &str) -> &mut ResearchTrack {
-        match track {
-            "one" => &mut self.track_one,
-            "two" => &mut self.track_two,
-            _ => &mut self.track_one, // Default to track one
-        }
+        let index = self.tracks.iter().position(|t| t.name == track).unwrap_or(0);
+        &mut self.tracks[index]
     }
 
     pub fn set_search_query(&mut self, track: &str, query: String) {
@@ -87,6 +140,14 @@ impl SummaryState {
         self.get_track_mut(track).web_research_results.push(result);
     }
 
+    pub fn set_track_error(&mut self, track: &str, error: ErrorBody) {
+        self.get_track_mut(track).error = Some(error);
+    }
+
+    pub fn failed_tracks(&self) -> Vec<&ResearchTrack> {
+        self.tracks.iter().filter(|track| track.failed()).collect()
+    }
+
     pub fn set_running_summary(&mut self, track: &str, summary: String) {
         self.get_track_mut(track).running_summary = summary;
     }
@@ -94,6 +155,26 @@ impl SummaryState {
     pub fn set_final_summary(&mut self, summary: String) {
         self.final_summary = Some(summary);
     }
+
+    pub fn set_images(&mut self, images: Vec<ImageReference>) {
+        self.images = images;
+    }
+
+    pub fn set_usage(&mut self, usage: UsageReport) {
+        self.usage = Some(usage);
+    }
+
+    pub fn set_served_by(&mut self, served_by: Vec<ServedBy>) {
+        self.served_by = served_by;
+    }
+
+    pub fn set_prompts(&mut self, prompts: PromptVersion) {
+        self.prompts = Some(prompts);
+    }
+
+    pub fn set_stopped_early(&mut self, reason: String) {
+        self.stopped_early = Some(reason);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -104,6 +185,8 @@ pub struct SummaryStateInput {
 #[derive(Debug, Clone)]
 pub struct SummaryStateOutput {
     pub running_summary: String,
+    pub images: Vec<ImageReference>,
+    pub state: SummaryState,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -115,6 +198,11 @@ pub struct StatusUpdate {
     pub chain_of_thought: Option<String>,
     pub track: Option<String>,
     pub perspectives: Option<DebatePerspectives>,
+    // Running totals for the research run the update belongs to
+    pub usage: Option<UsageTotals>,
+    // Set on the update that reports a failed run; /status sends these as `error` events
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorBody>,
 }
 
 impl Default for StatusUpdate {
@@ -127,6 +215,8 @@ impl Default for StatusUpdate {
             chain_of_thought: None,
             track: None,
             perspectives: None,
+            usage: None,
+            error: None,
         }
     }
 } 
\ No newline at end of file
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use super::configuration::Configuration;
+use super::error::ResearchError;
+use super::llm::TokenUsage;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+}
+
+impl UsageTotals {
+    pub fn total_tokens(&self) -> u64 {
+        self.prompt_tokens + self.completion_tokens
+    }
+
+    fn add(&mut self, usage: TokenUsage, cost_usd: f64) {
+        self.calls += 1;
+        self.prompt_tokens += usage.prompt_tokens;
+        self.completion_tokens += usage.completion_tokens;
+        self.cost_usd += cost_usd;
+    }
+}
+
+// Token usage and cost of one research run, broken down by track, node and model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub total: UsageTotals,
+    pub max_cost_per_run: Option<f64>,
+    pub max_tokens_per_run: Option<u64>,
+    #[serde(default)]
+    pub by_track: BTreeMap<String, UsageTotals>,
+    #[serde(default)]
+    pub by_node: BTreeMap<String, UsageTotals>,
+    // Keyed "provider/model"
+    #[serde(default)]
+    pub by_model: BTreeMap<String, UsageTotals>,
+    // Remote models with no entry in the price table, counted at zero cost
+    #[serde(default)]
+    pub unpriced_models: BTreeSet<String>,
+}
+
+// The provider that answered a node's LLM calls; `fallback` when it wasn't the research mode's own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServedBy {
+    pub track: Option<String>,
+    pub node: String,
+    pub provider: String,
+    pub model: String,
+    pub calls: u64,
+    pub fallback: bool,
+}
+
+pub struct UsageLedger {
+    config: Configuration,
+    report: Mutex<UsageReport>,
+    served_by: Mutex<Vec<ServedBy>>,
+}
+
+impl UsageLedger {
+    pub fn new(config: &Configuration) -> Self {
+        Self {
+            config: config.clone(),
+            report: Mutex::new(UsageReport {
+                max_cost_per_run: config.max_cost_per_run,
+                max_tokens_per_run: config.max_tokens_per_run,
+                ..Default::default()
+            }),
+            served_by: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, track: Option<&str>, node: &str, provider: &str, model: &str, usage: TokenUsage) {
+        // Models served by Ollama run locally and cost nothing
+        let price = match provider {
+            "ollama" => Some(Default::default()),
+            _ => self.config.price_for(model),
+        };
+        let cost = price
+            .map(|price| {
+                price.per_request
+                    + usage.prompt_tokens as f64 * price.input_per_million / 1_000_000.0
+                    + usage.completion_tokens as f64 * price.output_per_million / 1_000_000.0
+            })
+            .unwrap_or(0.0);
+
+        let mut report = self.report.lock().unwrap();
+        report.total.add(usage, cost);
+        if let Some(track) = track {
+            report.by_track.entry(track.to_string()).or_default().add(usage, cost);
+        }
+        report.by_node.entry(node.to_string()).or_default().add(usage, cost);
+        report.by_model.entry(format!("{}/{}", provider, model)).or_default().add(usage, cost);
+        if price.is_none() {
+            report.unpriced_models.insert(model.to_string());
+        }
+    }
+
+    pub fn serve(&self, track: Option<&str>, node: &str, provider: &str, model: &str, fallback: bool) {
+        let mut served_by = self.served_by.lock().unwrap();
+        let existing = served_by.iter_mut().find(|served| {
+            served.track.as_deref() == track && served.node == node && served.provider == provider && served.model == model
+        });
+        match existing {
+            Some(served) => served.calls += 1,
+            None => served_by.push(ServedBy {
+                track: track.map(|track| track.to_string()),
+                node: node.to_string(),
+                provider: provider.to_string(),
+                model: model.to_string(),
+                calls: 1,
+                fallback,
+            }),
+        }
+    }
+
+    // In the order nodes first called each provider
+    pub fn served_by(&self) -> Vec<ServedBy> {
+        self.served_by.lock().unwrap().clone()
+    }
+
+    pub fn totals(&self) -> UsageTotals {
+        self.report.lock().unwrap().total
+    }
+
+    pub fn report(&self) -> UsageReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub fn check_budget(&self) -> Result<()> {
+        let report = self.report.lock().unwrap();
+        if let Some(max_cost) = report.max_cost_per_run {
+            if report.total.cost_usd >= max_cost {
+                return Err(ResearchError::BudgetExceeded(format!(
+                    "Research budget exceeded: spent ${:.4} of ${:.4}",
+                    report.total.cost_usd,
+                    max_cost
+                )).into());
+            }
+        }
+        if let Some(max_tokens) = report.max_tokens_per_run {
+            if report.total.total_tokens() >= max_tokens {
+                return Err(ResearchError::BudgetExceeded(format!(
+                    "Research token budget exceeded: used {} of {} tokens",
+                    report.total.total_tokens(),
+                    max_tokens
+                )).into());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct UsageScope {
+    ledger: Arc<UsageLedger>,
+    track: Option<String>,
+    node: &'static str,
+}
+
+tokio::task_local! {
+    static USAGE_SCOPE: UsageScope;
+}
+
+// Usage recorded while `future` runs is charged to this ledger, track and node. Task locals are
+// per future, so tracks running side by side under join_all are still told apart.
+pub async fn with_usage_scope<F: Future>(
+    ledger: Arc<UsageLedger>,
+    track: Option<&str>,
+    node: &'static str,
+    future: F,
+) -> F::Output {
+    let scope = UsageScope {
+        ledger,
+        track: track.map(|track| track.to_string()),
+        node,
+    };
+    USAGE_SCOPE.scope(scope, future).await
+}
+
+// Called by the LLM and search clients; calls made outside a research run aren't charged anywhere
+pub fn record_usage(provider: &str, model: &str, usage: TokenUsage) {
+    let _ = USAGE_SCOPE.try_with(|scope| {
+        scope.ledger.record(scope.track.as_deref(), scope.node, provider, model, usage)
+    });
+}
+
+// Called once an LLM call succeeds, with whichever provider in the fallback chain answered it
+pub fn record_served_by(provider: &str, model: &str, fallback: bool) {
+    let _ = USAGE_SCOPE.try_with(|scope| {
+        scope.ledger.serve(scope.track.as_deref(), scope.node, provider, model, fallback)
+    });
+}
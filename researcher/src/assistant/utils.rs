@@ -1,10 +1,22 @@
 use anyhow::Result;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::env;
-use dotenv::dotenv;
+use std::time::Instant;
+
+use super::cache::cached;
+use super::configuration::Configuration;
+use super::error::ResearchError;
+use super::http::HttpClient;
+use super::llm::TokenUsage;
+use super::metrics::metrics;
+use super::ratelimit::estimate_tokens;
+use super::usage::record_usage;
+
+const PERPLEXITY_MODEL: &str = "sonar-pro";
+// Typical answer length, used for rate limiting until the real usage comes back
+const SEARCH_ANSWER_TOKENS: u64 = 1000;
+const SEARCH_SYSTEM_PROMPT: &str = "Search the web and provide factual information with sources.";
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SearchResult {
@@ -13,6 +25,8 @@ pub struct SearchResult {
     pub content: String,
     #[serde(default)]
     pub raw_content: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -67,51 +81,86 @@ pub fn format_sources(search_results: &SearchResponse) -> String {
         .join("\n")
 }
 
+pub fn detect_language(text: &str) -> Option<String> {
+    whatlang::detect(text)
+        .filter(|info| info.is_reliable())
+        .map(|info| info.lang().eng_name().to_string())
+}
+
 pub async fn perplexity_search(
     query: &str,
     perplexity_search_loop_count: i32,
+    config: &Configuration,
 ) -> Result<SearchResponse> {
-    dotenv().ok();
-    let api_key = env::var("PERPLEXITY_API_KEY")?;
-    let client = Client::new();
-    
-    let response = client
-        .post("https://api.perplexity.ai/chat/completions")
-        .header("Authorization", format!("Bearer {}", api_key))
-        .json(&serde_json::json!({
-            "model": "sonar-pro",
+    if config.perplexity_api_key.is_empty() {
+        return Err(ResearchError::Misconfigured("PERPLEXITY_API_KEY is not set".to_string()).into());
+    }
+    let params = serde_json::json!({ "system": SEARCH_SYSTEM_PROMPT });
+    let (data, cached) = cached(config, "perplexity", PERPLEXITY_MODEL, query, params, || async {
+        let http = HttpClient::new("perplexity", &config.http);
+        let body = serde_json::json!({
+            "model": PERPLEXITY_MODEL,
             "messages": [
                 {
                     "role": "system",
-                    "content": "Search the web and provide factual information with sources."
+                    "content": SEARCH_SYSTEM_PROMPT
                 },
                 {
                     "role": "user",
                     "content": query
                 }
             ]
-        }))
-        .send()
-        .await?;
-        
-    let data: Value = response.json().await?;
-    let content = data["choices"][0]["message"]["content"].as_str()
-        .ok_or_else(|| anyhow::anyhow!("Failed to get content from response"))?;
+        });
+
+        let started = Instant::now();
+        let estimated_tokens = estimate_tokens(query) + SEARCH_ANSWER_TOKENS;
+        let response = http
+            .send_metered(estimated_tokens, |client| client
+                .post(format!("{}/chat/completions", config.perplexity_base_url.trim_end_matches('/')))
+                .header("Authorization", format!("Bearer {}", config.perplexity_api_key))
+                .json(&body))
+            .await;
+        let status = match &response {
+            Ok(response) => response.status().as_u16().to_string(),
+            Err(e) => e.status_label(),
+        };
+        metrics().search_requests.with_label_values(&["perplexity", &status]).inc();
+        metrics().search_request_duration
+            .with_label_values(&["perplexity"])
+            .observe(started.elapsed().as_secs_f64());
+
+        let data: Value = response?.json().await
+            .map_err(|e| ResearchError::Parse { what: "the Perplexity response".to_string(), message: e.to_string() })?;
+        http.reconcile_tokens(estimated_tokens, search_usage(&data).total());
+        // Checked before caching, so an empty answer is asked for again next time
+        search_answer(&data, query)?;
+        Ok(data)
+    }).await?;
+
+    if cached {
+        metrics().search_requests.with_label_values(&["perplexity", "cached"]).inc();
+    } else {
+        record_usage("perplexity", PERPLEXITY_MODEL, search_usage(&data));
+    }
+    let content = search_answer(&data, query)?;
         
     // Create a Vec that will live for the entire function
     let citations_vec = data.get("citations")
         .and_then(|c| c.as_array())
         .map(|arr| arr.to_vec())
         .unwrap_or_default();
-        
+
+    // The first result carries the answer itself, which only takes the answer's language without a citation
+    let first_url = citations_vec.first().and_then(|c| c.as_str());
     let mut results = vec![SearchResult {
         title: format!("Perplexity Search {}, Source 1", perplexity_search_loop_count + 1),
-        url: citations_vec.first()
-            .and_then(|c| c.as_str())
-            .unwrap_or("https://perplexity.ai")
-            .to_string(),
+        url: first_url.unwrap_or("https://perplexity.ai").to_string(),
         content: content.to_string(),
         raw_content: Some(content.to_string()),
+        language: match first_url {
+            Some(url) => source_language(&data, url),
+            None => detect_language(content),
+        },
     }];
     
     // Add additional citations
@@ -122,9 +171,39 @@ pub async fn perplexity_search(
                 url: url.to_string(),
                 content: "See above for full content".to_string(),
                 raw_content: None,
+                language: source_language(&data, url),
             });
         }
     }
     
     Ok(SearchResponse { results })
+}
+
+// Detected from the snippet or title Perplexity lists for the source in `search_results`; bare URLs have none
+fn source_language(data: &Value, url: &str) -> Option<String> {
+    let source = data["search_results"].as_array()?
+        .iter()
+        .find(|source| source["url"].as_str() == Some(url))?;
+    source["snippet"].as_str()
+        .and_then(detect_language)
+        .or_else(|| source["title"].as_str().and_then(detect_language))
+}
+
+fn search_usage(data: &Value) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: data["usage"]["prompt_tokens"].as_u64().unwrap_or(0),
+        completion_tokens: data["usage"]["completion_tokens"].as_u64().unwrap_or(0),
+    }
+}
+
+fn search_answer<'a>(data: &'a Value, query: &str) -> Result<&'a str> {
+    let content = data["choices"][0]["message"]["content"].as_str()
+        .ok_or_else(|| ResearchError::Parse {
+            what: "the Perplexity response".to_string(),
+            message: "no answer content".to_string(),
+        })?;
+    if content.trim().is_empty() {
+        return Err(ResearchError::SearchEmpty { query: query.to_string() }.into());
+    }
+    Ok(content)
 } 
\ No newline at end of file
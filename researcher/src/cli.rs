@@ -0,0 +1,593 @@
+use anyhow::Result;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use serde_json::{Map, Value};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tokio::sync::broadcast;
+
+use crate::assistant::{
+    batch::{parse_batch, run_batch, BatchFormat, BatchOptions, BatchStore},
+    cache,
+    configuration::{mask_secrets, Configuration},
+    diagnostics::run_checks,
+    eval::{parse_eval_suite, run_eval},
+    error::ResearchError,
+    graph::ResearchGraph,
+    history::{now, ResearchRun, RunHistory},
+    report::{Report, ReportFormat},
+    state::{StatusUpdate, SummaryStateInput},
+};
+use crate::podcast::{generate_script, render_transcript, EpisodeLibrary, TranscriptFormat};
+use crate::podcast::library::NewEpisode;
+use crate::server::run_server;
+use crate::logging::LogFormat;
+
+// Matches the server's status channel
+const PROGRESS_CHANNEL_CAPACITY: usize = 100;
+
+#[derive(Parser)]
+#[command(name = "researcher", version, about = "Multi-perspective web research assistant")]
+pub struct Cli {
+    /// How results are printed
+    #[arg(long, value_enum, global = true, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Log filter, e.g. "debug" or "researcher=debug,tower_http=info" (overrides RESEARCHER_LOG)
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// Log output format (overrides RESEARCHER_LOG_FORMAT)
+    #[arg(long, value_enum, global = true)]
+    pub log_format: Option<LogFormat>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Reports as markdown, everything else as plain text
+    Text,
+    Json,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Start the HTTP server (the default)
+    Serve,
+    /// Check that every configured provider is reachable
+    Check {
+        /// Also send a request to providers that bill every call, such as Perplexity
+        #[arg(long)]
+        live: bool,
+    },
+    /// Research a topic, streaming progress to stderr and the report to stdout
+    Research(ResearchArgs),
+    /// Research every topic in a JSONL or CSV file, writing a report per topic and an index
+    Batch(BatchArgs),
+    /// Score research reports for a set of topics against reference facts, across configurations
+    Eval(EvalArgs),
+    /// Write a podcast script for a report and add it to the episode library
+    Podcast(PodcastArgs),
+    /// Browse past research runs
+    History {
+        #[command(subcommand)]
+        command: HistoryCommand,
+    },
+    /// Manage the response cache
+    Cache {
+        #[command(subcommand)]
+        command: CacheCommand,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Args)]
+pub struct ResearchArgs {
+    pub topic: String,
+    /// Start from a named configuration profile
+    #[arg(long)]
+    pub profile: Option<String>,
+    #[arg(long)]
+    pub max_loops: Option<i32>,
+    /// local or remote
+    #[arg(long)]
+    pub mode: Option<String>,
+    #[arg(long)]
+    pub local_llm: Option<String>,
+    #[arg(long)]
+    pub groq_model: Option<String>,
+    #[arg(long)]
+    pub language: Option<String>,
+    /// Number of perspectives, one research track each
+    #[arg(long)]
+    pub perspectives: Option<usize>,
+    /// Ask the providers again instead of reusing cached responses
+    #[arg(long)]
+    pub no_cache: bool,
+    /// Write the report to this file instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Don't print progress updates
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+impl ResearchArgs {
+    fn overrides(&self) -> Map<String, Value> {
+        let mut overrides = Map::new();
+        let mut set = |key: &str, value: Option<Value>| {
+            if let Some(value) = value {
+                overrides.insert(key.to_string(), value);
+            }
+        };
+
+        set("max_web_research_loops", self.max_loops.map(Value::from));
+        set("research_mode", self.mode.clone().map(Value::from));
+        set("local_llm", self.local_llm.clone().map(Value::from));
+        set("groq_model", self.groq_model.clone().map(Value::from));
+        set("language", self.language.clone().map(Value::from));
+        set("num_perspectives", self.perspectives.map(Value::from));
+        set("no_cache", self.no_cache.then_some(Value::Bool(true)));
+        overrides
+    }
+}
+
+#[derive(Args)]
+pub struct BatchArgs {
+    /// One topic per line as JSONL, or a CSV file with a `topic` column; other fields are overrides
+    pub file: PathBuf,
+    /// Research runs in flight at once
+    #[arg(long, default_value_t = 2)]
+    pub concurrency: usize,
+    /// Start at most this many runs per minute across the batch
+    #[arg(long)]
+    pub runs_per_minute: Option<u32>,
+    /// Profile for topics that don't name their own
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Don't print progress updates
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Args)]
+pub struct EvalArgs {
+    /// JSONL with a `topic` and the `facts` a good report mentions on each line
+    pub file: PathBuf,
+    /// Profile to compare, repeat for several; defaults to the current configuration
+    #[arg(long = "profile")]
+    pub profiles: Vec<String>,
+    /// Also have an LLM grade each report from 0 to 10
+    #[arg(long)]
+    pub judge: bool,
+    /// Profile whose LLM does the grading, defaults to the current configuration
+    #[arg(long, requires = "judge")]
+    pub judge_profile: Option<String>,
+    /// Write the comparison here instead of stdout
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Don't print progress updates
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+
+#[derive(Args)]
+pub struct PodcastArgs {
+    /// A markdown report, or a research run saved in the history
+    pub report_file: PathBuf,
+    /// Episode title, defaults to the research topic
+    #[arg(long)]
+    pub title: Option<String>,
+    #[arg(long, default_value_t = 5)]
+    pub minutes: u32,
+    #[arg(long)]
+    pub language: Option<String>,
+    #[arg(long)]
+    pub profile: Option<String>,
+    /// Transcript format printed in text mode: vtt, srt, txt or md
+    #[arg(long, default_value = "md")]
+    pub transcript: String,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommand {
+    /// List past research runs, newest first
+    List,
+    /// Print the report of a past research run
+    Show { id: String },
+    /// Export a past research run as md, html, json, docx or epub
+    Export {
+        id: String,
+        #[arg(long, default_value = "html")]
+        to: String,
+        /// Defaults to `<id>.<extension>` in the current directory
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheCommand {
+    /// Delete expired and unreadable responses from the cache directory
+    Prune,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommand {
+    /// Print the effective configuration and where each value came from
+    Show,
+}
+
+pub async fn run(cli: Cli) -> Result<()> {
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve().await,
+        Command::Check { live } => check(live, cli.format).await,
+        Command::Research(args) => research(args, cli.format).await,
+        Command::Batch(args) => batch(args, cli.format).await,
+        Command::Eval(args) => eval(args, cli.format).await,
+        Command::Podcast(args) => podcast(args, cli.format).await,
+        Command::History { command } => history(command, cli.format).await,
+        Command::Cache { command: CacheCommand::Prune } => prune_cache(cli.format).await,
+        Command::Config { command: ConfigCommand::Show } => show_config(cli.format),
+    }
+}
+
+async fn serve() -> Result<()> {
+    let config = Configuration::from_runnable_config(None)?;
+    tracing::info!("Successfully loaded configuration");
+
+    let diagnostics = run_checks(&config, false).await;
+    diagnostics.print();
+    if !diagnostics.ok {
+        tracing::warn!("Some providers failed their checks, research runs may fail - see GET /config/validate");
+    }
+
+    run_server(config).await;
+    Ok(())
+}
+
+async fn check(live: bool, format: Format) -> Result<()> {
+    let config = Configuration::from_runnable_config(None)?;
+    let diagnostics = run_checks(&config, live).await;
+
+    match format {
+        Format::Text => diagnostics.print(),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&diagnostics)?),
+    }
+
+    if !diagnostics.ok {
+        return Err(anyhow::anyhow!("Some providers failed their checks"));
+    }
+    Ok(())
+}
+
+async fn research(args: ResearchArgs, format: Format) -> Result<()> {
+    let mut config = Configuration::from_runnable_config(None)?;
+    if let Some(profile) = &args.profile {
+        config = config.with_profile(profile)?;
+    }
+    let overrides = args.overrides();
+    if !overrides.is_empty() {
+        let overridden = config.with_overrides(&Value::Object(overrides))?;
+        overridden.check_request_limits(&config)?;
+        config = overridden;
+    }
+    let history = RunHistory::open(&config.research_history_dir).await?;
+
+    let (status_tx, status_rx) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+    let progress = tokio::spawn(print_progress(status_rx, args.quiet));
+
+    let started_at = now();
+    let output = {
+        let mut graph = ResearchGraph::new(config.clone());
+        graph.set_status_sender(status_tx);
+        tokio::select! {
+            output = graph.process_research(SummaryStateInput {
+                research_topic: args.topic,
+            }) => output,
+            _ = tokio::signal::ctrl_c() => Err(ResearchError::Cancelled.into()),
+        }
+        // Dropping the graph closes the channel, which ends the progress task
+    };
+    let _ = progress.await;
+
+    let run = ResearchRun::new(started_at, &config, output?);
+    history.save(&run).await?;
+
+    let rendered = match format {
+        Format::Text => run.summary.clone(),
+        Format::Json => serde_json::to_string_pretty(&run)?,
+    };
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, rendered).await?;
+            eprintln!("Wrote report to {}", path.display());
+        }
+        None => println!("{}", rendered),
+    }
+    for track in run.state.failed_tracks() {
+        if let Some(error) = &track.error {
+            eprintln!("Track {} failed and was left out: {}", track.name, error.message);
+        }
+    }
+    if let Some(reason) = &run.state.stopped_early {
+        eprintln!("Research stopped early: {}", reason);
+    }
+    if let Some(usage) = &run.state.usage {
+        eprintln!(
+            "Used {} tokens in {} calls, ${:.4}",
+            usage.total.total_tokens(),
+            usage.total.calls,
+            usage.total.cost_usd
+        );
+    }
+    eprintln!("Saved research run {} ({}s)", run.id, run.duration_secs());
+
+    Ok(())
+}
+
+async fn batch(args: BatchArgs, format: Format) -> Result<()> {
+    let batch_format = BatchFormat::from_path(&args.file)
+        .ok_or_else(|| anyhow::anyhow!("Batch files must end in .jsonl or .csv"))?;
+    let contents = tokio::fs::read_to_string(&args.file).await
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.file.display(), e))?;
+    let mut items = parse_batch(&contents, batch_format)?;
+    if let Some(profile) = &args.profile {
+        for item in items.iter_mut().filter(|item| item.profile.is_none()) {
+            item.profile = Some(profile.clone());
+        }
+    }
+
+    let config = Configuration::from_runnable_config(None)?;
+    let max_concurrency = config.limits.max_batch_concurrency;
+    if !(1..=max_concurrency).contains(&args.concurrency) {
+        return Err(anyhow::anyhow!("--concurrency must be between 1 and {}", max_concurrency));
+    }
+
+    let history = Arc::new(RunHistory::open(&config.research_history_dir).await?);
+    let store = Arc::new(BatchStore::open(&history).await?);
+    let options = BatchOptions {
+        concurrency: args.concurrency,
+        runs_per_minute: args.runs_per_minute,
+        run_slots: None,
+    };
+    let index = store.create(&items, &options).await?;
+    eprintln!("Started batch {} with {} topics", index.id, items.len());
+
+    let (status_tx, status_rx) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+    let progress = tokio::spawn(print_progress(status_rx, args.quiet));
+    let index = run_batch(index, items, config, options, store, history.clone(), Some(status_tx)).await;
+    let _ = progress.await;
+    let index = index?;
+
+    match format {
+        Format::Text => println!("{}", index.render_markdown()),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&index)?),
+    }
+    eprintln!("Reports are in {}", history.dir().join("batches").join(&index.id).display());
+
+    if index.failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} topics failed", index.failed, index.entries.len()));
+    }
+    Ok(())
+}
+
+// Keeps receiving when quiet so the graph's status updates still have somewhere to go
+async fn print_progress(mut status_rx: broadcast::Receiver<StatusUpdate>, quiet: bool) {
+    loop {
+        match status_rx.recv().await {
+            Ok(_) if quiet => {}
+            Ok(status) => {
+                let message = status.message.lines().next().unwrap_or_default();
+                match &status.track {
+                    Some(track) => eprintln!("[{}] ({}) {}", status.phase, track, message),
+                    None => eprintln!("[{}] {}", status.phase, message),
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(_)) if quiet => {}
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                eprintln!("[progress] skipped {} updates", skipped);
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+async fn eval(args: EvalArgs, format: Format) -> Result<()> {
+    let contents = tokio::fs::read_to_string(&args.file).await
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.file.display(), e))?;
+    let cases = parse_eval_suite(&contents)?;
+
+    let base = Configuration::from_runnable_config(None)?;
+    let mut configurations = Vec::new();
+    if args.profiles.is_empty() {
+        let name = base.profile.clone().unwrap_or_else(|| "current".to_string());
+        configurations.push((name, base.clone()));
+    }
+    for profile in &args.profiles {
+        configurations.push((profile.clone(), base.with_profile(profile)?));
+    }
+    // Cached responses would make the latencies meaningless
+    for (_, config) in configurations.iter_mut() {
+        config.no_cache = true;
+    }
+    let judge = match (&args.judge, &args.judge_profile) {
+        (false, _) => None,
+        (true, Some(profile)) => Some(base.with_profile(profile)?),
+        (true, None) => Some(base.clone()),
+    };
+    eprintln!("Evaluating {} topics across {} configurations", cases.len(), configurations.len());
+
+    let (status_tx, status_rx) = broadcast::channel(PROGRESS_CHANNEL_CAPACITY);
+    let progress = tokio::spawn(print_progress(status_rx, args.quiet));
+    let report = run_eval(&cases, &configurations, judge.as_ref(), Some(status_tx)).await;
+    let _ = progress.await;
+
+    let rendered = match format {
+        Format::Text => report.render_markdown(),
+        Format::Json => serde_json::to_string_pretty(&report)?,
+    };
+    match &args.output {
+        Some(path) => {
+            tokio::fs::write(path, rendered).await?;
+            eprintln!("Wrote the comparison to {}", path.display());
+        }
+        None => println!("{}", rendered),
+    }
+
+    let failed: usize = report.summaries.iter().map(|summary| summary.failed).sum();
+    if failed > 0 {
+        return Err(anyhow::anyhow!("{} of {} runs failed", failed, report.results.len()));
+    }
+    Ok(())
+}
+
+async fn podcast(args: PodcastArgs, format: Format) -> Result<()> {
+    let transcript_format = TranscriptFormat::parse(&args.transcript)
+        .ok_or_else(|| anyhow::anyhow!("Unknown transcript format '{}' (use vtt, srt, txt or md)", args.transcript))?;
+
+    let mut config = Configuration::from_runnable_config(None)?;
+    if let Some(profile) = &args.profile {
+        config = config.with_profile(profile)?;
+    }
+    if let Some(language) = &args.language {
+        config = config.with_overrides(&serde_json::json!({ "language": language }))?;
+    }
+
+    let contents = tokio::fs::read_to_string(&args.report_file).await
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", args.report_file.display(), e))?;
+
+    // Runs saved in the history carry their topic; plain reports fall back to the file name
+    let (topic, report) = match serde_json::from_str::<ResearchRun>(&contents) {
+        Ok(run) => (run.topic, run.summary),
+        Err(_) => {
+            let stem = args.report_file.file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            (stem, contents)
+        }
+    };
+
+    eprintln!("Writing a {} minute podcast script in {}...", args.minutes, config.language);
+    let segments = generate_script(&report, args.minutes, &config).await?;
+
+    let library = EpisodeLibrary::open(&config.podcast_library_dir).await?;
+    let episode = library.create(NewEpisode {
+        title: args.title.unwrap_or_else(|| topic.clone()),
+        topic,
+        summary: report,
+        sources: Vec::new(),
+        segments,
+    }).await?;
+
+    match format {
+        Format::Text => println!("{}", render_transcript(&episode, transcript_format)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&episode)?),
+    }
+    eprintln!("Added episode {}, upload its audio with PUT /episodes/{}/audio", episode.id, episode.id);
+
+    Ok(())
+}
+
+async fn history(command: HistoryCommand, format: Format) -> Result<()> {
+    let config = Configuration::from_runnable_config(None)?;
+    let history = RunHistory::open(&config.research_history_dir).await?;
+
+    match command {
+        HistoryCommand::List => {
+            let runs = history.list().await?;
+            match format {
+                Format::Text => {
+                    for run in &runs {
+                        let finished = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(run.finished_at));
+                        println!("{}  {}  {:?}/{}  {}", run.id, finished, run.research_mode, run.model, run.topic);
+                    }
+                }
+                Format::Json => {
+                    // Listings leave out the full research state
+                    let summaries: Vec<Value> = runs.iter()
+                        .map(|run| serde_json::json!({
+                            "id": run.id,
+                            "topic": run.topic,
+                            "started_at": run.started_at,
+                            "finished_at": run.finished_at,
+                            "profile": run.profile,
+                            "research_mode": run.research_mode,
+                            "model": run.model,
+                            "language": run.language,
+                        }))
+                        .collect();
+                    println!("{}", serde_json::to_string_pretty(&summaries)?);
+                }
+            }
+        }
+        HistoryCommand::Show { id } => {
+            let run = history.get(&id).await?
+                .ok_or_else(|| anyhow::anyhow!("No research run with id {}", id))?;
+            match format {
+                Format::Text => println!("{}", run.summary),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&run)?),
+            }
+        }
+        HistoryCommand::Export { id, to, output } => {
+            let report_format = ReportFormat::parse(&to)
+                .ok_or_else(|| anyhow::anyhow!("Unknown report format '{}' (use md, html, json, docx or epub)", to))?;
+            let run = history.get(&id).await?
+                .ok_or_else(|| anyhow::anyhow!("No research run with id {}", id))?;
+
+            let report = Report::load(&run, Some(Path::new(&config.podcast_library_dir))).await;
+            let output = output.unwrap_or_else(|| PathBuf::from(format!("{}.{}", run.id, report_format.extension())));
+            tokio::fs::write(&output, report.render(report_format)?).await?;
+            eprintln!("Wrote {}", output.display());
+        }
+    }
+
+    Ok(())
+}
+
+async fn prune_cache(format: Format) -> Result<()> {
+    let config = Configuration::from_runnable_config(None)?;
+    let report = cache::prune(&config).await?;
+
+    match format {
+        Format::Text => println!(
+            "Removed {} cached responses ({} KB) from {}, kept {}",
+            report.removed,
+            report.freed_bytes.div_ceil(1024),
+            config.cache.dir,
+            report.kept
+        ),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+    }
+    Ok(())
+}
+
+fn show_config(format: Format) -> Result<()> {
+    let config = Configuration::from_runnable_config(None)?;
+
+    let Value::Object(mut values) = serde_json::to_value(&config)? else {
+        return Err(anyhow::anyhow!("Configuration did not serialize to an object"));
+    };
+    // Only say whether secrets are set, here and in the profiles
+    mask_secrets(&mut values);
+
+    match format {
+        Format::Text => {
+            for (key, value) in &values {
+                println!("{} = {} ({})", key, value, config.source_of(key));
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&serde_json::json!({
+            "config": values,
+            "provenance": config.provenance,
+        }))?),
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
 pub mod assistant;
+pub mod cli;
+pub mod logging;
+pub mod podcast;
 pub mod server;
 
 pub use assistant::configuration::Configuration;
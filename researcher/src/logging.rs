@@ -0,0 +1,60 @@
+use clap::ValueEnum;
+use std::env;
+use std::io::IsTerminal;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::EnvFilter;
+
+// Accepts anything EnvFilter does, e.g. "debug" or "researcher=debug,tower_http=info"
+pub const LOG_LEVEL_ENV: &str = "RESEARCHER_LOG";
+pub const LOG_FORMAT_ENV: &str = "RESEARCHER_LOG_FORMAT";
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line, including the fields of every enclosing span
+    Json,
+}
+
+impl LogFormat {
+    fn from_env() -> Option<Self> {
+        let value = env::var(LOG_FORMAT_ENV).ok()?;
+        match LogFormat::from_str(value.trim(), true) {
+            Ok(format) => Some(format),
+            Err(_) => {
+                eprintln!("Ignoring {}={}, expected text or json", LOG_FORMAT_ENV, value);
+                None
+            }
+        }
+    }
+}
+
+// Logs go to stderr so stdout stays clean for reports and JSON output.
+// Command line flags win over the environment, which wins over the defaults.
+pub fn init_logging(level: Option<&str>, format: Option<LogFormat>) {
+    let directives = level.map(str::to_string)
+        .or_else(|| env::var(LOG_LEVEL_ENV).ok())
+        .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string());
+    let filter = EnvFilter::try_new(&directives).unwrap_or_else(|e| {
+        eprintln!("Invalid log level {:?} ({}), falling back to {}", directives, e, DEFAULT_LOG_LEVEL);
+        EnvFilter::new(DEFAULT_LOG_LEVEL)
+    });
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        // Closing a span logs its recorded latency and token counts
+        .with_span_events(FmtSpan::CLOSE);
+
+    let result = match format.or_else(LogFormat::from_env).unwrap_or(LogFormat::Text) {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json()
+            .with_current_span(true)
+            .with_span_list(true)
+            .try_init(),
+    };
+    if let Err(e) = result {
+        eprintln!("Logging was already initialized: {}", e);
+    }
+}
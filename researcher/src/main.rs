@@ -1,27 +1,26 @@
+use clap::Parser;
 use researcher::{
     init,
-    assistant::configuration::Configuration,
-    server::run_server,
+    assistant::error::ResearchError,
+    cli::{run, Cli},
+    logging::init_logging,
 };
 
 #[tokio::main]
 async fn main() {
-    println!("Starting application...");
-    
+    let cli = Cli::parse();
+
     // Initialize environment variables from .env
     init();
-    println!("Initialized environment variables");
+    init_logging(cli.log_level.as_deref(), cli.log_format);
 
-    // Now try to create the configuration
-    match Configuration::from_runnable_config(None) {
-        Ok(config) => {
-            println!("Successfully loaded configuration");
-            
-            // Run the server
-            run_server(config).await;
-        }
-        Err(e) => {
-            eprintln!("Configuration error: {}", e);
-        }
+    if let Err(e) = run(cli).await {
+        eprintln!("Error: {}", e);
+        // Like a shell does for a command stopped with Ctrl-C
+        let code = match ResearchError::classify(&e) {
+            ResearchError::Cancelled => 130,
+            _ => 1,
+        };
+        std::process::exit(code);
     }
-} 
\ No newline at end of file
+}
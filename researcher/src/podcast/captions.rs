@@ -0,0 +1,328 @@
+use std::fmt::Write;
+
+use super::library::{Episode, EpisodeSegment};
+
+// Two caption lines of roughly 42 characters each
+const MAX_CUE_CHARS: usize = 84;
+
+// Used when the TTS step didn't report a duration for a segment
+const WORDS_PER_SECOND: f64 = 2.5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TranscriptFormat {
+    WebVtt,
+    Srt,
+    Text,
+    Markdown,
+}
+
+impl TranscriptFormat {
+    pub fn parse(format: &str) -> Option<Self> {
+        match format.to_lowercase().as_str() {
+            "vtt" | "webvtt" => Some(TranscriptFormat::WebVtt),
+            "srt" => Some(TranscriptFormat::Srt),
+            "txt" | "text" => Some(TranscriptFormat::Text),
+            "md" | "markdown" => Some(TranscriptFormat::Markdown),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            TranscriptFormat::WebVtt => "vtt",
+            TranscriptFormat::Srt => "srt",
+            TranscriptFormat::Text => "txt",
+            TranscriptFormat::Markdown => "md",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            TranscriptFormat::WebVtt => "text/vtt; charset=utf-8",
+            TranscriptFormat::Srt => "application/x-subrip; charset=utf-8",
+            TranscriptFormat::Text => "text/plain; charset=utf-8",
+            TranscriptFormat::Markdown => "text/markdown; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cue {
+    pub start: f64,
+    pub end: f64,
+    pub speaker: String,
+    pub text: String,
+}
+
+pub fn render_transcript(episode: &Episode, format: TranscriptFormat) -> String {
+    match format {
+        TranscriptFormat::WebVtt => render_webvtt(episode),
+        TranscriptFormat::Srt => render_srt(episode),
+        TranscriptFormat::Text => render_text(episode),
+        TranscriptFormat::Markdown => render_markdown(episode),
+    }
+}
+
+// Lays the segments out back to back and splits each one into caption-sized cues,
+// dividing the segment's time between its cues by text length
+pub fn build_cues(segments: &[EpisodeSegment]) -> Vec<Cue> {
+    let mut cues = Vec::new();
+    let mut offset = 0.0;
+
+    for segment in segments {
+        let duration = segment_duration(segment);
+        let chunks = split_caption_text(&segment.text);
+        let total_chars: usize = chunks.iter().map(|c| c.len()).sum::<usize>().max(1);
+
+        let mut start = offset;
+        for chunk in chunks {
+            let end = start + duration * chunk.len() as f64 / total_chars as f64;
+            cues.push(Cue {
+                start,
+                end,
+                speaker: segment.speaker.clone(),
+                text: chunk,
+            });
+            start = end;
+        }
+
+        offset += duration;
+    }
+
+    cues
+}
+
+pub fn render_webvtt(episode: &Episode) -> String {
+    let mut vtt = String::from("WEBVTT\n\n");
+
+    for (i, cue) in build_cues(&episode.segments).iter().enumerate() {
+        let _ = writeln!(vtt, "{}", i + 1);
+        let _ = writeln!(vtt, "{} --> {}", format_timestamp(cue.start, '.'), format_timestamp(cue.end, '.'));
+        let _ = writeln!(vtt, "<v {}>{}\n", escape_vtt(&single_line(&cue.speaker)), escape_vtt(&cue.text));
+    }
+
+    vtt
+}
+
+pub fn render_srt(episode: &Episode) -> String {
+    let mut srt = String::new();
+
+    for (i, cue) in build_cues(&episode.segments).iter().enumerate() {
+        let _ = writeln!(srt, "{}", i + 1);
+        let _ = writeln!(srt, "{} --> {}", format_timestamp(cue.start, ','), format_timestamp(cue.end, ','));
+        let _ = writeln!(srt, "{}: {}\n", single_line(&cue.speaker), cue.text);
+    }
+
+    srt
+}
+
+pub fn render_text(episode: &Episode) -> String {
+    let mut text = format!("{}\n\n", episode.title);
+    let mut offset = 0.0;
+
+    for segment in &episode.segments {
+        let _ = writeln!(text, "[{}] {}: {}\n", format_clock(offset), segment.speaker, segment.text.trim());
+        offset += segment_duration(segment);
+    }
+
+    if !episode.sources.is_empty() {
+        text.push_str("Sources:\n");
+        for source in &episode.sources {
+            let _ = writeln!(text, "- {} ({})", source.title, source.url);
+        }
+    }
+
+    text
+}
+
+pub fn render_markdown(episode: &Episode) -> String {
+    let mut markdown = format!("# {}\n\n", episode.title);
+    let mut offset = 0.0;
+
+    for segment in &episode.segments {
+        let _ = write!(
+            markdown,
+            "**{}** _[{}]_: {}",
+            segment.speaker,
+            format_clock(offset),
+            segment.text.trim()
+        );
+
+        // Link the sources the speaker refers to, by title or by site name
+        for (i, source) in episode.sources.iter().enumerate() {
+            if mentions_source(&segment.text, &source.title, &source.url) {
+                let _ = write!(markdown, " [[{}]]({})", i + 1, source.url);
+            }
+        }
+        markdown.push_str("\n\n");
+
+        offset += segment_duration(segment);
+    }
+
+    if !episode.sources.is_empty() {
+        markdown.push_str("## Sources\n\n");
+        for (i, source) in episode.sources.iter().enumerate() {
+            let _ = writeln!(markdown, "{}. [{}]({})", i + 1, source.title, source.url);
+        }
+    }
+
+    markdown
+}
+
+fn segment_duration(segment: &EpisodeSegment) -> f64 {
+    if segment.duration > 0.0 {
+        segment.duration
+    } else {
+        (segment.text.split_whitespace().count() as f64 / WORDS_PER_SECOND).max(1.0)
+    }
+}
+
+fn split_caption_text(text: &str) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + word.len() + 1 > MAX_CUE_CHARS {
+            chunks.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+
+        // Prefer to break cues at the end of a sentence
+        if current.len() > MAX_CUE_CHARS / 2 && word.ends_with(['.', '!', '?']) {
+            chunks.push(std::mem::take(&mut current));
+        }
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+
+    chunks
+}
+
+fn mentions_source(text: &str, title: &str, url: &str) -> bool {
+    let text = text.to_lowercase();
+
+    let host = url::Url::parse(url)
+        .ok()
+        .and_then(|u| u.host_str().map(|h| h.trim_start_matches("www.").to_lowercase()));
+    if let Some(site) = host.as_deref().and_then(|h| h.split('.').next()) {
+        if site.len() > 3 && text.contains(site) {
+            return true;
+        }
+    }
+
+    title.len() > 3 && text.contains(&title.to_lowercase())
+}
+
+// A line break in a speaker's name would end the cue early
+fn single_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn escape_vtt(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn format_timestamp(seconds: f64, separator: char) -> String {
+    let millis = (seconds * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        (millis % 3_600_000) / 60_000,
+        (millis % 60_000) / 1000,
+        separator,
+        millis % 1000
+    )
+}
+
+fn format_clock(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    format!("{:02}:{:02}", seconds / 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(speaker: &str, text: &str, duration: f64) -> EpisodeSegment {
+        EpisodeSegment { speaker: speaker.to_string(), text: text.to_string(), duration, image: None }
+    }
+
+    fn episode(segments: Vec<EpisodeSegment>) -> Episode {
+        Episode {
+            id: "1700000000-heat-pumps".to_string(),
+            title: "Heat pumps".to_string(),
+            topic: "Heat pumps".to_string(),
+            summary: String::new(),
+            sources: Vec::new(),
+            segments,
+            published_at: 1_700_000_000,
+            audio_file: None,
+            audio_length: 0,
+        }
+    }
+
+    #[test]
+    fn timestamps_carry_hours_and_milliseconds() {
+        assert_eq!(format_timestamp(0.0, '.'), "00:00:00.000");
+        assert_eq!(format_timestamp(61.2345, '.'), "00:01:01.235");
+        assert_eq!(format_timestamp(3_723.5, ','), "01:02:03,500");
+    }
+
+    #[test]
+    fn long_text_is_split_into_caption_sized_chunks() {
+        let text = "word ".repeat(60);
+        let chunks = split_caption_text(&text);
+
+        assert!(chunks.len() > 1);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= MAX_CUE_CHARS), "{:?}", chunks);
+        assert_eq!(chunks.join(" "), text.trim());
+        assert!(split_caption_text("  ").is_empty());
+    }
+
+    #[test]
+    fn chunks_break_at_the_end_of_a_sentence_once_long_enough() {
+        let chunks = split_caption_text("Short one. Heat pumps keep heating far below freezing these days. And they save money.");
+
+        assert_eq!(chunks, vec![
+            "Short one. Heat pumps keep heating far below freezing these days.".to_string(),
+            "And they save money.".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn cues_share_their_segment_time_by_length_and_follow_on() {
+        let long = "Heat pumps keep heating far below freezing these days. ".repeat(2);
+        let cues = build_cues(&[segment("Host", &long, 10.0), segment("Guest", "Indeed.", 0.0)]);
+
+        assert_eq!(cues.len(), 3);
+        assert_eq!(cues[0].start, 0.0);
+        assert!((cues[0].end - 5.0).abs() < 0.01, "{}", cues[0].end);
+        assert!((cues[1].end - 10.0).abs() < 1e-9);
+        // Without a duration, the segment gets one from its word count, and at least a second
+        assert_eq!((cues[2].start, cues[2].end), (10.0, 11.0));
+        assert_eq!(cues[2].speaker, "Guest");
+    }
+
+    #[test]
+    fn webvtt_escapes_speakers_and_text() {
+        let vtt = render_webvtt(&episode(vec![segment("Dr. <Smith> & co\n\nx", "COP < 3 & falling", 2.0)]));
+
+        assert!(vtt.starts_with("WEBVTT\n\n1\n00:00:00.000 --> 00:00:02.000\n"), "{}", vtt);
+        assert!(vtt.contains("<v Dr. &lt;Smith&gt; &amp; co x>COP &lt; 3 &amp; falling\n\n"), "{}", vtt);
+    }
+
+    #[test]
+    fn srt_numbers_cues_with_comma_timestamps() {
+        let srt = render_srt(&episode(vec![segment("Host", "Welcome.", 1.5), segment("Guest\nTwo", "Thanks.", 1.0)]));
+
+        assert_eq!(
+            srt,
+            "1\n00:00:00,000 --> 00:00:01,500\nHost: Welcome.\n\n2\n00:00:01,500 --> 00:00:02,500\nGuest Two: Thanks.\n\n"
+        );
+    }
+}
@@ -0,0 +1,132 @@
+use std::fmt::Write;
+use std::time::{Duration, UNIX_EPOCH};
+
+use super::library::Episode;
+
+#[derive(Debug, Clone)]
+pub struct FeedChannel {
+    pub title: String,
+    pub description: String,
+    pub author: String,
+    pub language: String,
+    // Public URL the server is reachable at, used for the channel link and enclosures
+    pub base_url: String,
+}
+
+pub fn render_feed(channel: &FeedChannel, episodes: &[Episode]) -> String {
+    let base_url = channel.base_url.trim_end_matches('/');
+    let mut xml = String::new();
+
+    xml.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:itunes=\"http://www.itunes.com/dtds/podcast-1.0.dtd\" xmlns:content=\"http://purl.org/rss/1.0/modules/content/\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("<channel>\n");
+    let _ = writeln!(xml, "<title>{}</title>", escape_xml(&channel.title));
+    let _ = writeln!(xml, "<link>{}</link>", escape_xml(base_url));
+    let _ = writeln!(xml, "<atom:link href=\"{}/feed.xml\" rel=\"self\" type=\"application/rss+xml\"/>", escape_xml(base_url));
+    let _ = writeln!(xml, "<description>{}</description>", escape_xml(&channel.description));
+    let _ = writeln!(xml, "<language>{}</language>", escape_xml(&channel.language));
+    let _ = writeln!(xml, "<itunes:author>{}</itunes:author>", escape_xml(&channel.author));
+    let _ = writeln!(xml, "<itunes:summary>{}</itunes:summary>", escape_xml(&channel.description));
+    xml.push_str("<itunes:explicit>false</itunes:explicit>\n");
+    xml.push_str("<itunes:category text=\"Education\"/>\n");
+
+    if let Some(latest) = episodes.iter().map(|e| e.published_at).max() {
+        let _ = writeln!(xml, "<lastBuildDate>{}</lastBuildDate>", format_rfc2822(latest));
+    }
+
+    // Episodes without uploaded audio can't be played, so they stay out of the feed
+    for episode in episodes.iter().filter(|e| e.audio_file.is_some()) {
+        render_item(&mut xml, base_url, episode);
+    }
+
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+fn render_item(xml: &mut String, base_url: &str, episode: &Episode) {
+    let audio_url = format!(
+        "{}/media/{}",
+        base_url,
+        episode.audio_file.as_deref().unwrap_or_default()
+    );
+    let summary = plain_summary(&episode.summary);
+
+    xml.push_str("<item>\n");
+    let _ = writeln!(xml, "<title>{}</title>", escape_xml(&episode.title));
+    let _ = writeln!(xml, "<guid isPermaLink=\"false\">{}</guid>", escape_xml(&episode.id));
+    let _ = writeln!(xml, "<pubDate>{}</pubDate>", format_rfc2822(episode.published_at));
+    let _ = writeln!(
+        xml,
+        "<enclosure url=\"{}\" length=\"{}\" type=\"audio/mpeg\"/>",
+        escape_xml(&audio_url),
+        episode.audio_length
+    );
+    let _ = writeln!(xml, "<description>{}</description>", escape_xml(&summary));
+    let _ = writeln!(xml, "<itunes:summary>{}</itunes:summary>", escape_xml(&summary));
+    let _ = writeln!(xml, "<itunes:duration>{}</itunes:duration>", format_duration(episode.duration_secs()));
+    xml.push_str("<itunes:episodeType>full</itunes:episodeType>\n");
+    let _ = writeln!(xml, "<content:encoded><![CDATA[{}]]></content:encoded>", show_notes(episode).replace("]]>", "]]]]><![CDATA[>"));
+    xml.push_str("</item>\n");
+}
+
+// Show notes: the summary as paragraphs followed by the research sources
+fn show_notes(episode: &Episode) -> String {
+    let mut html = String::new();
+
+    for paragraph in plain_summary(&episode.summary).split("\n\n") {
+        let _ = write!(html, "<p>{}</p>", escape_xml(paragraph.trim()));
+    }
+
+    if !episode.sources.is_empty() {
+        html.push_str("<h3>Sources</h3><ul>");
+        for source in &episode.sources {
+            let _ = write!(
+                html,
+                "<li><a href=\"{}\">{}</a></li>",
+                escape_xml(&source.url),
+                escape_xml(&source.title)
+            );
+        }
+        html.push_str("</ul>");
+    }
+
+    html
+}
+
+// Drops the markdown headings and source listings the finalizer adds around each track summary
+fn plain_summary(summary: &str) -> String {
+    let mut text = String::new();
+    let mut in_sources = false;
+
+    for line in summary.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('#') {
+            in_sources = trimmed.to_lowercase().contains("sources");
+            continue;
+        }
+        if in_sources {
+            continue;
+        }
+        text.push_str(trimmed);
+        text.push('\n');
+    }
+
+    text.trim().to_string()
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn format_rfc2822(timestamp: u64) -> String {
+    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(timestamp))
+}
+
+fn format_duration(seconds: u64) -> String {
+    format!("{:02}:{:02}:{:02}", seconds / 3600, (seconds % 3600) / 60, seconds % 60)
+}
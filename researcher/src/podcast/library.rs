@@ -0,0 +1,230 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeSegment {
+    pub speaker: String,
+    pub text: String,
+    // Length of the synthesized audio for this segment, in seconds
+    #[serde(default)]
+    pub duration: f64,
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EpisodeSource {
+    pub title: String,
+    pub url: String,
+}
+
+impl EpisodeSource {
+    // Parses the "- title (url) [language]" lines the research graph stores for each track
+    pub fn from_source_line(line: &str) -> Option<Self> {
+        let line = line.trim().strip_prefix("- ")?;
+        let line = match line.rfind(" [") {
+            Some(tag) if line.ends_with(']') => &line[..tag],
+            _ => line,
+        };
+        let open = line.rfind(" (")?;
+        let url = line[open + 2..].strip_suffix(')')?;
+        if !url.starts_with("http") {
+            return None;
+        }
+
+        Some(Self {
+            title: line[..open].trim().to_string(),
+            url: url.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Episode {
+    pub id: String,
+    pub title: String,
+    pub topic: String,
+    pub summary: String,
+    pub sources: Vec<EpisodeSource>,
+    pub segments: Vec<EpisodeSegment>,
+    pub published_at: u64,
+    pub audio_file: Option<String>,
+    #[serde(default)]
+    pub audio_length: u64,
+}
+
+impl Episode {
+    pub fn duration_secs(&self) -> u64 {
+        self.segments.iter().map(|s| s.duration).sum::<f64>().round() as u64
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewEpisode {
+    pub title: String,
+    #[serde(default)]
+    pub topic: String,
+    pub summary: String,
+    #[serde(default)]
+    pub sources: Vec<EpisodeSource>,
+    #[serde(default)]
+    pub segments: Vec<EpisodeSegment>,
+}
+
+// Episodes are stored as `<id>.json` metadata files next to their `<id>.mp3` audio
+pub struct EpisodeLibrary {
+    dir: PathBuf,
+}
+
+impl EpisodeLibrary {
+    pub async fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir).await
+            .map_err(|e| anyhow::anyhow!("Failed to create episode library at {:?}: {}", dir, e))?;
+        Ok(Self { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub async fn list(&self) -> Result<Vec<Episode>> {
+        let mut episodes = Vec::new();
+        let mut entries = fs::read_dir(&self.dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+
+            let data = fs::read(&path).await?;
+            match serde_json::from_slice::<Episode>(&data) {
+                Ok(episode) => episodes.push(episode),
+                Err(e) => tracing::warn!(path = ?path, error = %e, "Skipping unreadable episode file"),
+            }
+        }
+
+        // Newest episodes first, as podcast apps expect
+        episodes.sort_by_key(|e| std::cmp::Reverse(e.published_at));
+        Ok(episodes)
+    }
+
+    pub async fn get(&self, id: &str) -> Result<Option<Episode>> {
+        if !is_valid_id(id) {
+            return Ok(None);
+        }
+
+        match fs::read(self.metadata_path(id)).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn save(&self, episode: &Episode) -> Result<()> {
+        let data = serde_json::to_vec_pretty(episode)?;
+        fs::write(self.metadata_path(&episode.id), data).await?;
+        Ok(())
+    }
+
+    pub async fn create(&self, new_episode: NewEpisode) -> Result<Episode> {
+        let published_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        // Fall back to the sources listed in the final summary when none are given explicitly
+        let sources = if new_episode.sources.is_empty() {
+            new_episode.summary
+                .lines()
+                .filter_map(EpisodeSource::from_source_line)
+                .collect()
+        } else {
+            new_episode.sources
+        };
+
+        let episode = Episode {
+            id: new_id(published_at, &new_episode.title),
+            title: new_episode.title,
+            topic: new_episode.topic,
+            summary: new_episode.summary,
+            sources,
+            segments: new_episode.segments,
+            published_at,
+            audio_file: None,
+            audio_length: 0,
+        };
+
+        self.save(&episode).await?;
+        Ok(episode)
+    }
+
+    pub async fn set_segment_images(&self, id: &str, images: Vec<Option<String>>) -> Result<Option<Episode>> {
+        let Some(mut episode) = self.get(id).await? else {
+            return Ok(None);
+        };
+
+        for (segment, image) in episode.segments.iter_mut().zip(images) {
+            if image.is_some() {
+                segment.image = image;
+            }
+        }
+        self.save(&episode).await?;
+
+        Ok(Some(episode))
+    }
+
+    pub async fn store_audio(&self, id: &str, audio: &[u8]) -> Result<Option<Episode>> {
+        let Some(mut episode) = self.get(id).await? else {
+            return Ok(None);
+        };
+
+        let file_name = format!("{}.mp3", episode.id);
+        fs::write(self.dir.join(&file_name), audio).await?;
+
+        episode.audio_file = Some(file_name);
+        episode.audio_length = audio.len() as u64;
+        self.save(&episode).await?;
+
+        Ok(Some(episode))
+    }
+
+    fn metadata_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+}
+
+// Ids end up in file names, so only accept the characters `slugify` produces
+pub(crate) fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+// The random suffix keeps ids apart when the same title is saved twice in one second
+pub(crate) fn new_id(timestamp: u64, title: &str) -> String {
+    format!("{}-{}-{:08x}", timestamp, slugify(title), fastrand::u32(..))
+}
+
+pub(crate) fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.chars() {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c.to_ascii_lowercase());
+        } else if !slug.ends_with('-') && !slug.is_empty() {
+            slug.push('-');
+        }
+        if slug.len() >= 48 {
+            break;
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "episode".to_string()
+    } else {
+        slug.to_string()
+    }
+}
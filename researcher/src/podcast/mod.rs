@@ -0,0 +1,9 @@
+pub mod captions;
+pub mod feed;
+pub mod library;
+pub mod script;
+
+pub use captions::{render_transcript, TranscriptFormat};
+pub use feed::{render_feed, FeedChannel};
+pub use library::{Episode, EpisodeLibrary, EpisodeSegment, EpisodeSource};
+pub use script::generate_script;
@@ -0,0 +1,69 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::library::EpisodeSegment;
+use crate::assistant::configuration::Configuration;
+use crate::assistant::llm;
+use crate::assistant::prompts;
+
+#[derive(Debug, Deserialize)]
+struct ScriptLine {
+    speaker: String,
+    text: String,
+}
+
+// Turns a research report into a two-speaker script; durations are filled in once audio exists
+pub async fn generate_script(report: &str, minutes: u32, config: &Configuration) -> Result<Vec<EpisodeSegment>> {
+    let prompt = prompts::render(config, "podcast_script.jinja", json!({
+        "report": report,
+        "language": config.language,
+        "minutes": minutes,
+    }))?;
+
+    let response = llm::generate(&prompt, config).await?;
+    parse_script(&response)
+}
+
+fn parse_script(response: &str) -> Result<Vec<EpisodeSegment>> {
+    let lines: Vec<ScriptLine> = llm::json_array(response)
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse the podcast script: no JSON array of lines in the reply"))?;
+
+    let segments: Vec<EpisodeSegment> = lines.into_iter()
+        .filter(|line| !line.text.trim().is_empty())
+        .map(|line| EpisodeSegment {
+            speaker: line.speaker,
+            text: line.text.trim().to_string(),
+            duration: 0.0,
+            image: None,
+        })
+        .collect();
+
+    if segments.is_empty() {
+        return Err(anyhow::anyhow!("The podcast script came back empty"));
+    }
+    Ok(segments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_script_is_read_from_text_around_it() {
+        let response = "<think>Two voices [host, guest]</think>Here you go] as asked:\n\
+            [{\"speaker\": \"Host\", \"text\": \" Welcome. \"}, {\"speaker\": \"Guest\", \"text\": \"\"},\
+            {\"speaker\": \"Guest\", \"text\": \"Thanks.\"}]\nHope that helps [1].";
+
+        let segments = parse_script(response).unwrap();
+
+        let lines: Vec<(&str, &str)> = segments.iter().map(|s| (s.speaker.as_str(), s.text.as_str())).collect();
+        assert_eq!(lines, vec![("Host", "Welcome."), ("Guest", "Thanks.")]);
+    }
+
+    #[test]
+    fn a_reply_without_a_script_is_an_error() {
+        assert!(parse_script("Sorry] I can't write that [yet").is_err());
+        assert!(parse_script("[]").is_err());
+    }
+}
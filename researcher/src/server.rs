@@ -2,7 +2,9 @@ use axum::{
     routing::{post, get, put},
     Router,
     Json,
-    extract::State,
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    middleware::{self, Next},
+    body::Bytes,
     http::StatusCode,
     response::{IntoResponse, Response, sse::{Event, Sse}},
 };
@@ -12,77 +14,228 @@ use std::{
     convert::Infallible,
     time::{Duration, SystemTime, UNIX_EPOCH},
     env,
-    sync::OnceLock,
 };
 use crate::assistant::{
-    configuration::Configuration,
-    state::{SummaryStateInput, StatusUpdate},
+    batch::{batch_from_values, parse_batch, run_batch, BatchFormat, BatchOptions, BatchStore},
+    configuration::{mask_secrets, ConfigSource, Configuration, OutputFormat, PartialResults, Profile, SearchAPI},
+    state::{SummaryState, SummaryStateInput, StatusUpdate},
     graph::ResearchGraph,
-    configuration::ResearchMode,
+    metrics::{metrics, SubscriberGuard, CONTENT_TYPE as METRICS_CONTENT_TYPE},
+    configuration::{LlmProvider, ResearchMode},
+    diagnostics::run_checks,
+    enrich::EnrichmentCache,
+    history::{now, ResearchRun, RunHistory},
+    usage::UsageReport,
+    error::{ErrorBody, ResearchError},
+    report::{language_code, Report, ReportFormat},
+    images::{render_visuals, visual_prompts_for_segments, ImageProvider, ImageReference},
 };
+use crate::podcast::{render_feed, render_transcript, Episode, EpisodeLibrary, FeedChannel, TranscriptFormat};
+use crate::podcast::library::NewEpisode;
 use tower_http::cors::CorsLayer;
+use tower_http::services::ServeDir;
 use futures::stream::Stream;
 use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
 use serde_json::json;
-use serde::Deserialize;
-use tokio::sync::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tokio::sync::{Mutex, RwLock, Semaphore};
 use http::{Method, header};
 use http::header::HeaderValue;
 
 // Increase channel capacity
 const CHANNEL_CAPACITY: usize = 100;
 
-static STATUS_CHANNEL: OnceLock<broadcast::Sender<StatusUpdate>> = OnceLock::new();
+// What `/media` serves from the library directory; episode metadata stays behind the API
+const MEDIA_EXTENSIONS: &[&str] = &["mp3", "svg", "png", "jpg", "jpeg", "webp"];
 
-fn get_status_channel() -> broadcast::Sender<StatusUpdate> {
-    STATUS_CHANNEL.get_or_init(|| {
-        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
-        tx
-    }).clone()
-}
+// Generated episodes are a few minutes of 128kbps mp3, well under this
+const MAX_AUDIO_UPLOAD_BYTES: usize = 200 * 1024 * 1024;
 
 #[derive(Clone)]
 pub struct AppState {
-    graph: Arc<Mutex<ResearchGraph>>,
+    config: Arc<RwLock<Configuration>>,
+    // Research runs share the status stream, so they run one at a time
+    research_lock: Arc<Mutex<()>>,
+    enrichments: Arc<EnrichmentCache>,
     status_tx: broadcast::Sender<StatusUpdate>,
+    library: Arc<EpisodeLibrary>,
+    history: Arc<RunHistory>,
+    batches: Arc<BatchStore>,
+    // Research runs in flight across all batches, capped at `[limits] max_batch_concurrency`
+    batch_slots: Arc<Semaphore>,
+    feed_channel: FeedChannel,
+    image_provider: Option<Arc<dyn ImageProvider>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 struct ConfigUpdate {
+    // Switches to a named profile before the other fields are applied
+    #[serde(default, skip_serializing)]
+    profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     local_llm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     max_web_research_loops: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     research_mode: Option<ResearchMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
 pub struct ResearchRequest {
     topic: String,
+    // A named profile this run starts from instead of the server configuration
+    profile: Option<String>,
+    #[serde(flatten)]
+    options: ResearchOptions,
+    // Any other configuration overrides that only apply to this run
+    config: Option<serde_json::Value>,
+}
+
+// Per-run options, validated against the server's limits before the run starts
+#[derive(Default, Deserialize, Serialize)]
+struct ResearchOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_web_research_loops: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    research_mode: Option<ResearchMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    local_llm: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    groq_model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    llm_fallback: Option<Vec<LlmProvider>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    search_api: Option<SearchAPI>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    language: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    num_perspectives: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    partial_results: Option<PartialResults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_format: Option<OutputFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    no_cache: Option<bool>,
+}
+
+impl ResearchRequest {
+    fn run_config(&self, base: &Configuration) -> anyhow::Result<Configuration> {
+        let profiled;
+        let base = match &self.profile {
+            Some(name) => {
+                profiled = base.with_profile(name)?;
+                &profiled
+            }
+            None => base,
+        };
+
+        let mut overrides = match &self.config {
+            Some(serde_json::Value::Object(overrides)) => overrides.clone(),
+            Some(_) => return Err(anyhow::anyhow!("config must be an object")),
+            None => serde_json::Map::new(),
+        };
+        // Explicit options win over the generic overrides
+        if let serde_json::Value::Object(options) = json!(self.options) {
+            overrides.extend(options);
+        }
+
+        if overrides.is_empty() {
+            return Ok(base.clone());
+        }
+
+        let config = base.with_overrides(&serde_json::Value::Object(overrides))?;
+        config.check_request_limits(base)?;
+        Ok(config)
+    }
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    format: Option<String>,
+}
+
+// Batch settings, given in the query string when the body is a CSV or JSONL file
+#[derive(Deserialize)]
+struct BatchQuery {
+    concurrency: Option<usize>,
+    runs_per_minute: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    // Topic strings or objects with per-topic overrides, as in a JSONL batch file
+    topics: Vec<serde_json::Value>,
+    concurrency: Option<usize>,
+    runs_per_minute: Option<u32>,
+}
+
+const DEFAULT_BATCH_CONCURRENCY: usize = 2;
+
+#[derive(Deserialize)]
+struct EnrichRequest {
+    text: String,
+    #[serde(default)]
+    ground: bool,
 }
 
 #[derive(serde::Serialize)]
 struct ResearchResponse {
+    // Where the run was saved in the research history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
     summary: String,
     status: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    images: Vec<ImageReference>,
+    // The full research state, when the JSON output format was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<SummaryState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    usage: Option<UsageReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<ErrorBody>,
 }
 
-// Custom error type for our API
-struct ApiError(anyhow::Error);
+// Failures are answered with the status code of their ResearchError and a machine-readable `error`
+struct ApiError(ResearchError);
 
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
+        let status = StatusCode::from_u16(self.0.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let mut response = (
+            status,
             Json(ResearchResponse {
+                id: None,
                 summary: format!("Error: {}", self.0),
                 status: "Error occurred".to_string(),
+                images: Vec::new(),
+                report: None,
+                usage: None,
+                error: Some(self.0.body()),
             })
-        ).into_response()
+        ).into_response();
+
+        if let Some(retry_after) = self.0.retry_after() {
+            if let Ok(value) = header::HeaderValue::from_str(&retry_after.as_secs().max(1).to_string()) {
+                response.headers_mut().insert(header::RETRY_AFTER, value);
+            }
+        }
+        response
     }
 }
 
-// Convert anyhow::Error into our ApiError
 impl From<anyhow::Error> for ApiError {
     fn from(err: anyhow::Error) -> Self {
+        ApiError(ResearchError::classify(&err))
+    }
+}
+
+impl From<ResearchError> for ApiError {
+    fn from(err: ResearchError) -> Self {
         ApiError(err)
     }
 }
@@ -94,21 +247,88 @@ struct ConfigResponse {
     max_web_research_loops: i32,
     research_mode: ResearchMode,
     groq_model: String,
+    llm_fallback: Vec<LlmProvider>,
+    language: String,
+    profile: Option<String>,
+    provenance: BTreeMap<String, ConfigSource>,
+}
+
+#[derive(serde::Serialize)]
+struct ProfilesResponse {
+    active: Option<String>,
+    profiles: BTreeMap<String, Profile>,
 }
 
 pub async fn run_server(config: Configuration) {
-    let status_tx = get_status_channel();
-    
-    let mut graph = ResearchGraph::new(config);
-    graph.set_status_sender(status_tx.clone());
+    let port = env::var("PORT").unwrap_or_else(|_| "4000".to_string()).parse::<u16>().unwrap_or(4000);
+    let app = match app(config, port).await {
+        Ok(app) => app,
+        Err(e) => {
+            tracing::error!(error = %e, "Server setup failed");
+            return;
+        }
+    };
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::info!(port, "Starting server on http://localhost:{}", port);
 
+    axum::serve(
+        tokio::net::TcpListener::bind(&addr).await.unwrap(),
+        app.into_make_service(),
+    )
+    .await
+    .unwrap();
+}
+
+// The API with its own status stream, episode library and history; `port` is only used for links
+// in the feed when `public_base_url` isn't set
+pub async fn app(config: Configuration, port: u16) -> anyhow::Result<Router> {
+    let (status_tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+    let library = EpisodeLibrary::open(&config.podcast_library_dir).await
+        .map_err(|e| anyhow::anyhow!("Episode library error: {}", e))?;
+    let media_dir = ServeDir::new(library.dir());
+
+    let history = RunHistory::open(&config.research_history_dir).await
+        .map_err(|e| anyhow::anyhow!("Research history error: {}", e))?;
+    let batches = BatchStore::open(&history).await
+        .map_err(|e| anyhow::anyhow!("Research history error: {}", e))?;
+
+    let image_provider = match crate::assistant::images::provider_from_config(&config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            tracing::warn!(error = %e, "Image provider unavailable, episode images are disabled");
+            None
+        }
+    };
+
+    let batch_slots = Arc::new(Semaphore::new(config.limits.max_batch_concurrency.max(1)));
+
+    let feed_channel = FeedChannel {
+        title: config.podcast_title.clone(),
+        description: "Research podcasts generated from ZU-LM research runs".to_string(),
+        author: "ZU-LM".to_string(),
+        language: language_code(&config.language),
+        base_url: config.public_base_url.clone()
+            .unwrap_or_else(|| format!("http://localhost:{}", port)),
+    };
+    
     let state = Arc::new(AppState {
-        graph: Arc::new(Mutex::new(graph)),
+        config: Arc::new(RwLock::new(config)),
+        research_lock: Arc::new(Mutex::new(())),
+        enrichments: Arc::new(EnrichmentCache::default()),
         status_tx,
+        library: Arc::new(library),
+        history: Arc::new(history),
+        batches: Arc::new(batches),
+        batch_slots,
+        feed_channel,
+        image_provider,
     });
 
     let frontend_origin = env::var("FRONTEND_URL")
         .unwrap_or_else(|_| "http://localhost:3000".to_string());
+    tracing::info!(origin = %frontend_origin, "Allowing CORS for origin");
     
     let cors = CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::PUT])
@@ -119,88 +339,290 @@ pub async fn run_server(config: Configuration) {
             header::CONNECTION,
         ])
         .allow_credentials(true)
-        .allow_origin(frontend_origin.parse::<HeaderValue>().unwrap());
+        .allow_origin(frontend_origin.parse::<HeaderValue>()?);
 
-    let app = Router::new()
+    Ok(Router::new()
         .route("/research", post(handle_research))
+        .route("/research/batch", post(start_batch))
+        .route("/research/batch/:id", get(get_batch))
+        .route("/research/:id/report", get(get_report))
         .route("/config", put(update_config))
         .route("/config", get(get_config))
+        .route("/config/validate", get(validate_config))
+        .route("/profiles", get(get_profiles))
         .route("/status", get(status_stream))
+        .route("/metrics", get(get_metrics))
+        .route("/enrich", post(handle_enrich))
+        .route("/feed.xml", get(get_feed))
+        .route("/episodes", get(list_episodes).post(create_episode))
+        .route(
+            "/episodes/:id/audio",
+            put(upload_episode_audio).layer(DefaultBodyLimit::max(MAX_AUDIO_UPLOAD_BYTES)),
+        )
+        .route("/episodes/:id/transcript/:format", get(get_episode_transcript))
+        .route("/episodes/:id/images", post(generate_episode_images))
+        .nest_service("/media", Router::new()
+            .fallback_service(media_dir)
+            .layer(middleware::from_fn(only_media_files)))
         .layer(cors)
-        .with_state(state);
-
-    let port = env::var("PORT").unwrap_or_else(|_| "4000".to_string()).parse::<u16>().unwrap_or(4000);
-    let addr = SocketAddr::from(([0, 0, 0, 0], port));
-    println!("Starting server on http://localhost:{}", port);
-    println!("Allowing CORS for origin: {}", frontend_origin);
-    
-    axum::serve(
-        tokio::net::TcpListener::bind(&addr).await.unwrap(),
-        app.into_make_service(),
-    )
-    .await
-    .unwrap();
+        .with_state(state))
 }
 
 async fn handle_research(
     State(state): State<Arc<AppState>>,
     Json(request): Json<ResearchRequest>,
 ) -> Response {
+    let run_config = match request.run_config(&*state.config.read().await) {
+        Ok(config) => config,
+        Err(e) => return ApiError(ResearchError::Config(e.to_string())).into_response(),
+    };
+    let output_format = run_config.output_format.clone();
+
     let input = SummaryStateInput {
         research_topic: request.topic,
     };
 
     // Send initial status update
-    let mut status = StatusUpdate::default();
-    status.phase = "init".to_string();
-    status.message = format!("Starting research on topic: {}", input.research_topic);
-    status.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let status = StatusUpdate {
+        phase: "init".to_string(),
+        message: format!("Starting research on topic: {}", input.research_topic),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        ..Default::default()
+    };
 
     if let Err(e) = state.status_tx.send(status) {
-        eprintln!("Failed to send initial status update: {}", e);
+        tracing::debug!(error = %e, "No subscribers for the initial status update");
     }
 
-    let mut graph = state.graph.lock().await;
-    // Update the graph's status sender
+    let _research_guard = state.research_lock.lock().await;
+
+    // Each run gets its own graph so per-request options don't leak into later requests
+    let started_at = now();
+    let mut graph = ResearchGraph::new(run_config.clone());
     graph.set_status_sender(state.status_tx.clone());
 
-    match graph.process_research(input).await {
+    let mut cancelled = CancelledRun(Some(state.status_tx.clone()));
+    let result = graph.process_research(input).await;
+    cancelled.0 = None;
+
+    match result {
         Ok(output) => {
+            let run = ResearchRun::new(started_at, &run_config, output);
+            let id = match state.history.save(&run).await {
+                Ok(()) => Some(run.id.clone()),
+                Err(e) => {
+                    tracing::error!(run = %run.id, error = %e, "Failed to save research run");
+                    None
+                }
+            };
+
             // Send final status update
-            let mut status = StatusUpdate::default();
-            status.phase = "complete".to_string();
-            status.message = output.running_summary.clone();
-            status.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            let status = StatusUpdate {
+                phase: "complete".to_string(),
+                message: run.summary.clone(),
+                timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+                ..Default::default()
+            };
 
             let _ = state.status_tx.send(status);
             
             (
                 StatusCode::OK,
                 Json(ResearchResponse { 
-                    summary: output.running_summary,
-                    status: "Research completed".to_string(),
+                    id,
+                    summary: run.summary,
+                    status: match (run.state.failed_tracks().len(), &run.state.stopped_early) {
+                        (0, None) => "Research completed".to_string(),
+                        (0, Some(reason)) => format!("Research stopped early: {}", reason),
+                        (failed, _) => format!("Research completed, {} of {} tracks failed", failed, run.state.tracks.len()),
+                    },
+                    images: run.images,
+                    usage: run.state.usage.clone(),
+                    report: (output_format == OutputFormat::Json).then_some(run.state),
+                    error: None,
                 })
             ).into_response()
         },
+        // The graph has already sent the typed error event to /status
         Err(e) => {
-            eprintln!("Research error: {:?}", e);
-            let error_message = e.to_string();
+            tracing::error!(error = ?e, "Research error");
+            ApiError::from(e).into_response()
+        },
+    }
+}
+
+// axum drops the handler when the client disconnects, which stops the run mid-flight; tell
+// /status subscribers it was cancelled instead of leaving them waiting for a result
+struct CancelledRun(Option<broadcast::Sender<StatusUpdate>>);
+
+impl Drop for CancelledRun {
+    fn drop(&mut self) {
+        let Some(status_tx) = self.0.take() else { return };
+        let error = ResearchError::Cancelled;
+        tracing::info!("Research run cancelled, the client disconnected");
+        metrics().research_runs.with_label_values(&["cancelled"]).inc();
+        let _ = status_tx.send(StatusUpdate {
+            phase: "error".to_string(),
+            message: format!("Error: {}", error),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+            error: Some(error.body()),
+            ..Default::default()
+        });
+    }
+}
 
-            let mut status = StatusUpdate::default();
-            status.phase = "error".to_string();
-            status.message = format!("Error: {}", error_message);
-            status.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+// Accepts a JSON body, or a CSV (text/csv) or JSONL (application/x-ndjson) file of topics,
+// and runs the batch in the background; poll GET /research/batch/{id} for progress
+async fn start_batch(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<BatchQuery>,
+    headers: header::HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let bad_request = |message: String| (
+        StatusCode::BAD_REQUEST,
+        Json(json!({ "error": message })),
+    ).into_response();
+
+    let content_type = headers.get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/json");
+    let body_text = String::from_utf8_lossy(&body);
+
+    let parsed = if content_type.starts_with("text/csv") {
+        parse_batch(&body_text, BatchFormat::Csv).map(|items| (items, query.concurrency, query.runs_per_minute))
+    } else if content_type.starts_with("application/x-ndjson") || content_type.starts_with("application/jsonl") {
+        parse_batch(&body_text, BatchFormat::Jsonl).map(|items| (items, query.concurrency, query.runs_per_minute))
+    } else {
+        serde_json::from_slice::<BatchRequest>(&body)
+            .map_err(anyhow::Error::from)
+            .and_then(|request| {
+                let items = batch_from_values(request.topics)?;
+                Ok((items, request.concurrency, request.runs_per_minute))
+            })
+    };
+    let (items, concurrency, runs_per_minute) = match parsed {
+        Ok(parsed) => parsed,
+        Err(e) => return Ok(bad_request(e.to_string())),
+    };
 
-            let _ = state.status_tx.send(status);
-            
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ResearchResponse { 
-                    summary: format!("Error: {}", error_message),
-                    status: "Error occurred".to_string(),
-                })
-            ).into_response()
+    let config = state.config.read().await.clone();
+    let max_concurrency = config.limits.max_batch_concurrency;
+    let concurrency = concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY.min(max_concurrency));
+    if !(1..=max_concurrency).contains(&concurrency) {
+        return Ok(bad_request(format!("concurrency must be between 1 and {}", max_concurrency)));
+    }
+
+    let options = BatchOptions {
+        concurrency,
+        runs_per_minute,
+        run_slots: Some(state.batch_slots.clone()),
+    };
+    let index = state.batches.create(&items, &options).await?;
+    let id = index.id.clone();
+
+    let batches = state.batches.clone();
+    let history = state.history.clone();
+    let status_tx = state.status_tx.clone();
+    tokio::spawn(async move {
+        if let Err(e) = run_batch(index, items, config, options, batches, history, Some(status_tx)).await {
+            tracing::error!(error = %e, "Batch error");
+        }
+    });
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({
+            "id": id,
+            "status": "Batch started",
+            "index": format!("/research/batch/{}", id),
+        })),
+    ).into_response())
+}
+
+async fn get_batch(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    match state.batches.get(&id).await? {
+        Some(index) => Ok((StatusCode::OK, Json(index)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Batch {} not found", id) })),
+        ).into_response()),
+    }
+}
+
+// The format comes from `?format=` or else the Accept header, defaulting to markdown
+async fn get_report(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ReportQuery>,
+    headers: header::HeaderMap,
+) -> Result<Response, ApiError> {
+    let unsupported = |status| (
+        status,
+        Json(json!({ "error": "Supported report formats are md, html, json, docx and epub" })),
+    ).into_response();
+    let format = match &query.format {
+        Some(format) => match ReportFormat::parse(format) {
+            Some(format) => format,
+            None => return Ok(unsupported(StatusCode::BAD_REQUEST)),
         },
+        None => match headers.get(header::ACCEPT).and_then(|value| value.to_str().ok()) {
+            Some(accept) => match ReportFormat::from_accept(accept) {
+                Some(format) => format,
+                None => return Ok(unsupported(StatusCode::NOT_ACCEPTABLE)),
+            },
+            None => ReportFormat::Markdown,
+        },
+    };
+
+    let Some(run) = state.history.get(&id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Research run {} not found", id) })),
+        ).into_response());
+    };
+
+    let report = Report::load(&run, Some(state.library.dir())).await;
+    let body = report.render(format)?;
+    let disposition = if format.is_binary() { "attachment" } else { "inline" };
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, format!("{}; filename=\"{}.{}\"", disposition, run.id, format.extension())),
+        ],
+        body,
+    ).into_response())
+}
+
+async fn handle_enrich(
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<EnrichRequest>,
+) -> Result<Response, ApiError> {
+    if request.text.trim().is_empty() {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": "Text is required" })),
+        ).into_response());
+    }
+
+    let config = state.config.read().await.clone();
+    let enrichment = state.enrichments.enrich(&request.text, request.ground, &config).await?;
+
+    Ok((StatusCode::OK, Json(enrichment)).into_response())
+}
+
+async fn get_metrics() -> Response {
+    match metrics().render() {
+        Ok(body) => ([(header::CONTENT_TYPE, METRICS_CONTENT_TYPE)], body).into_response(),
+        Err(e) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": e.to_string() })),
+        ).into_response(),
     }
 }
 
@@ -208,9 +630,11 @@ async fn status_stream(
     State(state): State<Arc<AppState>>,
 ) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
     let mut rx = state.status_tx.subscribe();
-    println!("New SSE connection established");
+    let subscriber = SubscriberGuard::new();
+    tracing::info!("New SSE connection established");
     
     let stream = async_stream::stream! {
+        let _subscriber = subscriber;
         let mut retry_count = 0;
         const MAX_RETRIES: u32 = 3;
         const RETRY_DELAY: Duration = Duration::from_secs(1);
@@ -219,24 +643,35 @@ async fn status_stream(
             match rx.recv().await {
                 Ok(status) => {
                     let json = serde_json::to_string(&status).unwrap();
-                    println!("Sending status update: {}", json);
+                    tracing::trace!(status = %json, "Sending status update");
                     retry_count = 0; // Reset retry count on successful message
-                    yield Ok(Event::default()
+                    let event = match status.error {
+                        Some(_) => Event::default().event("error"),
+                        None => Event::default(),
+                    };
+                    yield Ok(event
                         .data(json)
                         .id(status.timestamp.to_string()) // Add message ID for retry
                         .retry(Duration::from_millis(RETRY_DELAY.as_millis() as u64))); // Set retry interval using Duration
                 }
                 Err(e) => {
-                    eprintln!("Error receiving status update: {}", e);
+                    tracing::warn!(error = %e, "Error receiving status update");
+                    match e {
+                        RecvError::Lagged(skipped) => {
+                            metrics().sse_lagged_updates.inc_by(skipped);
+                            metrics().sse_receive_errors.with_label_values(&["lagged"]).inc();
+                        }
+                        RecvError::Closed => metrics().sse_receive_errors.with_label_values(&["closed"]).inc(),
+                    }
                     if retry_count < MAX_RETRIES {
                         retry_count += 1;
-                        eprintln!("Retrying connection ({}/{})", retry_count, MAX_RETRIES);
+                        tracing::warn!(retry_count, max_retries = MAX_RETRIES, "Retrying connection");
                         // Get a fresh receiver and continue
                         rx = state.status_tx.subscribe();
                         tokio::time::sleep(RETRY_DELAY * retry_count).await;
                         continue;
                     } else {
-                        eprintln!("Max retries reached, closing connection");
+                        tracing::warn!("Max retries reached, closing connection");
                         break;
                     }
                 }
@@ -255,30 +690,47 @@ async fn status_stream(
 async fn update_config(
     State(state): State<Arc<AppState>>,
     Json(update): Json<ConfigUpdate>,
-) -> impl IntoResponse {
-    let mut graph = state.graph.lock().await;
+) -> Response {
+    let mut config = state.config.write().await;
     
     // Send status updates
-    let mut status = StatusUpdate::default();
-    status.phase = "config".to_string();
-    status.message = "Updating configuration...".to_string();
-    status.timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    let status = StatusUpdate {
+        phase: "config".to_string(),
+        message: "Updating configuration...".to_string(),
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs(),
+        ..Default::default()
+    };
 
     let _ = state.status_tx.send(status);
 
-    if let Some(llm) = update.local_llm {
-        println!("Updating LLM to: {}", llm);
-        graph.update_llm(llm);
-    }
-    
-    if let Some(loops) = update.max_web_research_loops {
-        println!("Updating max loops to: {}", loops);
-        graph.update_max_loops(loops);
-    }
+    let base = match &update.profile {
+        Some(name) => match config.with_profile(name) {
+            Ok(profiled) => {
+                tracing::info!(profile = %name, "Switching to profile");
+                profiled
+            }
+            Err(e) => {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(json!({ "error": e.to_string() })),
+                ).into_response();
+            }
+        },
+        None => config.clone(),
+    };
 
-    if let Some(mode) = update.research_mode {
-        println!("Updating research mode to: {:?}", mode);
-        graph.update_research_mode(mode);
+    let layer = json!(update);
+    match base.with_layer(&layer, ConfigSource::Runtime) {
+        Ok(updated) => {
+            tracing::info!(update = %layer, "Updating configuration");
+            *config = updated;
+        }
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(json!({ "error": e.to_string() })),
+            ).into_response();
+        }
     }
     
     (
@@ -287,22 +739,181 @@ async fn update_config(
             "status": "Configuration updated",
             "message": "Changes will take effect on next research request"
         }))
-    )
+    ).into_response()
 }
 
-// Add this new handler function
 async fn get_config(
     State(state): State<Arc<AppState>>,
 ) -> impl IntoResponse {
-    let graph = state.graph.lock().await;
+    let config = state.config.read().await;
     
     (
         StatusCode::OK,
         Json(ConfigResponse {
-            local_llm: graph.get_llm_model().to_string(),
-            max_web_research_loops: graph.get_max_loops(),
-            research_mode: graph.get_research_mode(),
-            groq_model: graph.get_groq_model().to_string(),
+            local_llm: config.local_llm.clone(),
+            max_web_research_loops: config.max_web_research_loops,
+            research_mode: config.research_mode.clone(),
+            groq_model: config.groq_model.clone(),
+            llm_fallback: config.llm_fallback.clone(),
+            language: config.language.clone(),
+            profile: config.profile.clone(),
+            provenance: config.provenance.clone(),
         })
     )
-} 
\ No newline at end of file
+}
+
+// Checks every configured provider without billed requests; 503 when any of them would fail a research run
+async fn validate_config(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let config = state.config.read().await.clone();
+    let diagnostics = run_checks(&config, false).await;
+
+    let status = if diagnostics.ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(diagnostics))
+}
+
+async fn get_profiles(
+    State(state): State<Arc<AppState>>,
+) -> impl IntoResponse {
+    let config = state.config.read().await;
+
+    // Profiles from the config file may carry their own keys
+    let mut profiles = config.available_profiles();
+    for profile in profiles.values_mut() {
+        mask_secrets(&mut profile.settings);
+    }
+
+    (
+        StatusCode::OK,
+        Json(ProfilesResponse {
+            active: config.profile.clone(),
+            profiles,
+        })
+    )
+}
+
+async fn get_feed(
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, ApiError> {
+    let episodes = state.library.list().await?;
+    // Follows language changes made through PUT /config
+    let channel = FeedChannel {
+        language: language_code(&state.config.read().await.language),
+        ..state.feed_channel.clone()
+    };
+    let feed = render_feed(&channel, &episodes);
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/rss+xml; charset=utf-8")],
+        feed,
+    ).into_response())
+}
+
+async fn only_media_files(request: Request, next: Next) -> Response {
+    let is_media = std::path::Path::new(request.uri().path())
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| MEDIA_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()));
+    if !is_media {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    next.run(request).await
+}
+
+async fn list_episodes(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<Episode>>, ApiError> {
+    Ok(Json(state.library.list().await?))
+}
+
+async fn create_episode(
+    State(state): State<Arc<AppState>>,
+    Json(new_episode): Json<NewEpisode>,
+) -> Result<(StatusCode, Json<Episode>), ApiError> {
+    let episode = state.library.create(new_episode).await?;
+    tracing::info!(episode = %episode.id, "Added episode to the library");
+    Ok((StatusCode::CREATED, Json(episode)))
+}
+
+async fn upload_episode_audio(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    audio: Bytes,
+) -> Result<Response, ApiError> {
+    match state.library.store_audio(&id, &audio).await? {
+        Some(episode) => Ok((StatusCode::OK, Json(episode)).into_response()),
+        None => Ok((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Episode {} not found", id) })),
+        ).into_response()),
+    }
+}
+
+async fn get_episode_transcript(
+    State(state): State<Arc<AppState>>,
+    Path((id, format)): Path<(String, String)>,
+) -> Result<Response, ApiError> {
+    let Some(format) = TranscriptFormat::parse(&format) else {
+        return Ok((
+            StatusCode::BAD_REQUEST,
+            Json(json!({ "error": format!("Unsupported transcript format: {}", format) })),
+        ).into_response());
+    };
+
+    let Some(episode) = state.library.get(&id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Episode {} not found", id) })),
+        ).into_response());
+    };
+
+    let disposition = format!("attachment; filename=\"{}.{}\"", episode.id, format.extension());
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        render_transcript(&episode, format),
+    ).into_response())
+}
+
+async fn generate_episode_images(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Response, ApiError> {
+    let Some(provider) = &state.image_provider else {
+        return Ok((
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({ "error": "No image provider is configured" })),
+        ).into_response());
+    };
+
+    let Some(episode) = state.library.get(&id).await? else {
+        return Ok((
+            StatusCode::NOT_FOUND,
+            Json(json!({ "error": format!("Episode {} not found", id) })),
+        ).into_response());
+    };
+
+    let prompts = visual_prompts_for_segments(episode.segments.iter().map(|s| s.text.as_str()));
+    let rendered = render_visuals(&prompts, provider.as_ref()).await;
+
+    // Failed generations leave their segment without an image
+    let images = prompts.iter()
+        .map(|prompt| rendered.iter()
+            .find(|image| image.section == prompt.section)
+            .map(|image| image.url.clone()))
+        .collect();
+
+    match state.library.set_segment_images(&id, images).await? {
+        Some(episode) => Ok((StatusCode::OK, Json(episode)).into_response()),
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
@@ -0,0 +1,162 @@
+// The on-disk response cache, in front of scripted providers
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{mock_config, research_mocks, scratch_dir, TOPIC};
+use researcher::assistant::cache::prune;
+use researcher::assistant::llm;
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply, MockSearch};
+use researcher::{Configuration, ResearchGraph, SummaryStateInput};
+use serde_json::Value;
+use tokio::sync::broadcast;
+
+const PROMPT: &str = "Name one cold-climate heat pump.";
+
+fn cached_config(mocks: &std::sync::Arc<MockProviders>) -> Configuration {
+    let mut config = mock_config(mocks);
+    config.cache.enabled = true;
+    config.cache.dir = scratch_dir().join("cache").display().to_string();
+    config
+}
+
+fn llm_mocks() -> std::sync::Arc<MockProviders> {
+    MockProviders::new(MockLlm::new().when("heat pump", MockReply::text("The Mitsubishi Hyper-Heat.")), MockSearch::new())
+}
+
+// Every cached entry, wherever its key put it
+fn entries(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(listing) = std::fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in listing {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                found.push(path);
+            }
+        }
+    }
+    found
+}
+
+// Moves an entry's timestamp back, as if it had been stored `secs` ago
+fn age_entry(path: &Path, secs: u64) {
+    let mut entry: Value = serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    entry["stored_at"] = Value::from(now - secs);
+    std::fs::write(path, serde_json::to_vec(&entry).unwrap()).unwrap();
+}
+
+#[tokio::test]
+async fn a_repeated_prompt_is_answered_from_the_cache() {
+    let mocks = llm_mocks();
+    let config = cached_config(&mocks);
+
+    let first = llm::complete(PROMPT, &config).await.unwrap();
+    let second = llm::complete(PROMPT, &config).await.unwrap();
+
+    assert!(!first.cached);
+    assert!(second.cached);
+    assert_eq!(second.text, "The Mitsubishi Hyper-Heat.");
+    assert_eq!(mocks.llm.prompts().len(), 1);
+    assert_eq!(entries(Path::new(&config.cache.dir)).len(), 1);
+}
+
+#[tokio::test]
+async fn a_different_prompt_misses() {
+    let mocks = llm_mocks();
+    let config = cached_config(&mocks);
+
+    llm::complete(PROMPT, &config).await.unwrap();
+    let other = llm::complete("Name one heat pump for mild climates.", &config).await.unwrap();
+
+    assert!(!other.cached);
+    assert_eq!(mocks.llm.prompts().len(), 2);
+}
+
+#[tokio::test]
+async fn no_cache_asks_the_provider_and_stores_nothing() {
+    let mocks = llm_mocks();
+    let mut config = cached_config(&mocks);
+    config.no_cache = true;
+
+    llm::complete(PROMPT, &config).await.unwrap();
+    let second = llm::complete(PROMPT, &config).await.unwrap();
+
+    assert!(!second.cached);
+    assert_eq!(mocks.llm.prompts().len(), 2);
+    assert!(entries(Path::new(&config.cache.dir)).is_empty());
+}
+
+#[tokio::test]
+async fn expired_entries_are_asked_for_again_and_replaced() {
+    let mocks = llm_mocks();
+    let mut config = cached_config(&mocks);
+    config.cache.ttls.insert("ollama".to_string(), 60);
+
+    llm::complete(PROMPT, &config).await.unwrap();
+    let stored = entries(Path::new(&config.cache.dir));
+    age_entry(&stored[0], 120);
+
+    let second = llm::complete(PROMPT, &config).await.unwrap();
+
+    assert!(!second.cached);
+    assert_eq!(mocks.llm.prompts().len(), 2);
+    // The expired entry was removed on lookup and the new answer stored in its place
+    assert!(llm::complete(PROMPT, &config).await.unwrap().cached);
+    assert_eq!(entries(Path::new(&config.cache.dir)).len(), 1);
+}
+
+#[tokio::test]
+async fn pruning_removes_expired_entries_only() {
+    let mocks = llm_mocks();
+    let mut config = cached_config(&mocks);
+    config.cache.ttls.insert("ollama".to_string(), 60);
+
+    llm::complete(PROMPT, &config).await.unwrap();
+    llm::complete("Name one heat pump for mild climates.", &config).await.unwrap();
+    let dir = PathBuf::from(&config.cache.dir);
+    let stored = entries(&dir);
+    age_entry(&stored[0], 120);
+    std::fs::write(stored[1].with_file_name("garbage.json"), "not json").unwrap();
+
+    let report = prune(&config).await.unwrap();
+
+    assert_eq!((report.removed, report.kept), (2, 1));
+    assert_eq!(entries(&dir), vec![stored[1].clone()]);
+    // Nothing to prune without a cache directory
+    config.cache.dir = scratch_dir().join("missing").display().to_string();
+    assert_eq!(prune(&config).await.unwrap().removed, 0);
+}
+
+#[tokio::test]
+async fn cache_hits_show_up_on_the_status_stream() {
+    let mocks = research_mocks();
+    let config = cached_config(&mocks);
+    let input = || SummaryStateInput { research_topic: TOPIC.to_string() };
+    ResearchGraph::new(config.clone()).process_research(input()).await.unwrap();
+    let calls = mocks.llm.prompts().len();
+
+    let (tx, mut rx) = broadcast::channel(100);
+    let mut graph = ResearchGraph::new(config);
+    graph.set_status_sender(tx);
+    let output = graph.process_research(input()).await.unwrap();
+
+    let mut hits = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        if update.phase == "cache" {
+            hits.push(update.message);
+        }
+    }
+    assert!(!hits.is_empty());
+    assert!(hits.iter().all(|hit| hit.starts_with("Using a cached")), "{:?}", hits);
+    // The second run never reached the providers and cost nothing
+    assert_eq!(mocks.llm.prompts().len(), calls);
+    assert_eq!(output.state.usage.unwrap().total.calls, 0);
+}
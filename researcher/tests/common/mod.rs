@@ -0,0 +1,110 @@
+// Shared by the integration tests: configurations backed by mock providers, and the API server
+// running on an ephemeral port
+#![allow(dead_code)]
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use researcher::assistant::configuration::{HttpPolicy, ResearchMode};
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply, MockSearch};
+use researcher::Configuration;
+use serde_json::json;
+use tokio::task::JoinHandle;
+
+pub const TOPIC: &str = "Do heat pumps work in cold climates?";
+pub const PERSPECTIVE_ONE: &str = "Heat pumps now work well far below freezing";
+pub const PERSPECTIVE_TWO: &str = "Cold snaps still need backup heating";
+pub const SUMMARY: &str = "Cold-climate heat pumps keep heating down to about -25C.";
+
+static NEXT_DIR: AtomicU64 = AtomicU64::new(1);
+
+// A fresh directory per test for the history and episode library
+pub fn scratch_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "researcher-test-{}-{}",
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// One research loop in local mode, without retries so scripted errors surface right away
+pub fn mock_config(mocks: &Arc<MockProviders>) -> Configuration {
+    let dir = scratch_dir();
+    let mut config = Configuration {
+        research_mode: ResearchMode::Local,
+        max_web_research_loops: 1,
+        perplexity_api_key: "test".to_string(),
+        groq_api_key: Some("test".to_string()),
+        podcast_library_dir: dir.join("episodes").display().to_string(),
+        research_history_dir: dir.join("history").display().to_string(),
+        http: HttpPolicy {
+            max_retries: 0,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 10,
+            ..HttpPolicy::default()
+        },
+        ..Configuration::default()
+    };
+    mocks.install(&mut config);
+    config
+}
+
+// Answers for every prompt a research run sends, matched on the instructions each node uses
+pub fn research_llm() -> MockLlm {
+    with_research_replies(MockLlm::new())
+}
+
+// Rules are tried in the order they were added, so scripted failures go into `llm` first
+pub fn with_research_replies(llm: MockLlm) -> MockLlm {
+    llm
+        .when("debate coach", MockReply::json(json!({
+            "topic": TOPIC,
+            "perspective_one": PERSPECTIVE_ONE,
+            "perspective_two": PERSPECTIVE_TWO,
+        })))
+        .when("generating effective search queries", MockReply::json(json!({ "query": "cold climate heat pump COP" })))
+        .when("identifying knowledge gaps", MockReply::json(json!({ "follow_up_query": "heat pump running costs" })))
+        .when("summarizing information", MockReply::thinking("Compare the sources.", SUMMARY))
+}
+
+pub fn research_search() -> MockSearch {
+    MockSearch::new().otherwise(MockReply::search(
+        "Field studies report a seasonal COP of 2.5 to 3 down to -25C.",
+        &["https://www.energy.gov/heat-pumps", "https://www.nrel.gov/cold-climate-heat-pumps"],
+    ))
+}
+
+pub fn research_mocks() -> Arc<MockProviders> {
+    MockProviders::new(research_llm(), research_search())
+}
+
+// The axum app from server.rs, stopped when dropped
+pub struct TestServer {
+    pub base_url: String,
+    task: JoinHandle<()>,
+}
+
+impl TestServer {
+    pub async fn start(config: Configuration) -> Self {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let app = researcher::server::app(config, port).await.expect("the app starts");
+        let task = tokio::spawn(async move {
+            axum::serve(listener, app.into_make_service()).await.unwrap();
+        });
+        Self { base_url: format!("http://127.0.0.1:{}", port), task }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,64 @@
+// Enrichment of podcast text and its in-memory cache
+mod common;
+
+use common::{mock_config, research_search};
+use researcher::assistant::enrich::EnrichmentCache;
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply};
+
+fn enrichment_mocks() -> std::sync::Arc<MockProviders> {
+    MockProviders::new(
+        MockLlm::new().when("key facts", MockReply::text("* Heat pumps move heat instead of making it")),
+        research_search(),
+    )
+}
+
+#[tokio::test]
+async fn repeated_text_is_served_from_the_cache() {
+    let mocks = enrichment_mocks();
+    let config = mock_config(&mocks);
+    let cache = EnrichmentCache::default();
+
+    let first = cache.enrich("Heat pumps work in the cold.", false, &config).await.unwrap();
+    let second = cache.enrich("  Heat pumps work in the cold. ", false, &config).await.unwrap();
+
+    assert!(!first.cached);
+    assert!(second.cached);
+    assert_eq!(first.text_hash, second.text_hash);
+    assert_eq!(second.facts[0].fact, "Heat pumps move heat instead of making it");
+    assert_eq!(mocks.llm.prompts().len(), 1);
+}
+
+#[tokio::test]
+async fn settings_that_change_the_facts_are_part_of_the_key() {
+    let mocks = enrichment_mocks();
+    let config = mock_config(&mocks);
+    let cache = EnrichmentCache::default();
+    let text = "Heat pumps work in the cold.";
+
+    cache.enrich(text, false, &config).await.unwrap();
+    let mut german = config.clone();
+    german.language = "German".to_string();
+    let mut other_model = config.clone();
+    other_model.local_llm = "llama3.2".to_string();
+
+    assert!(!cache.enrich(text, false, &german).await.unwrap().cached);
+    assert!(!cache.enrich(text, false, &other_model).await.unwrap().cached);
+    assert_eq!(mocks.llm.prompts().len(), 3);
+}
+
+#[tokio::test]
+async fn the_least_recently_used_enrichment_makes_room() {
+    let mocks = enrichment_mocks();
+    let config = mock_config(&mocks);
+    let cache = EnrichmentCache::with_capacity(2);
+
+    cache.enrich("first", false, &config).await.unwrap();
+    cache.enrich("second", false, &config).await.unwrap();
+    // Using the first again leaves the second as the oldest
+    assert!(cache.enrich("first", false, &config).await.unwrap().cached);
+    cache.enrich("third", false, &config).await.unwrap();
+
+    assert!(cache.enrich("first", false, &config).await.unwrap().cached);
+    assert!(cache.enrich("third", false, &config).await.unwrap().cached);
+    assert!(!cache.enrich("second", false, &config).await.unwrap().cached);
+}
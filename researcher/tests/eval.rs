@@ -0,0 +1,154 @@
+// Scoring research runs against reference facts, with scripted providers behind them
+mod common;
+
+use common::{mock_config, research_llm, research_mocks, research_search, SUMMARY, TOPIC};
+use researcher::assistant::eval::{parse_eval_suite, run_eval, score_report, EvalCase};
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply};
+use researcher::assistant::prompts::BUILTIN_PROMPTS_VERSION;
+use researcher::SummaryState;
+use serde_json::json;
+
+fn case() -> EvalCase {
+    EvalCase {
+        topic: TOPIC.to_string(),
+        facts: vec!["Heat pumps keep heating down to -25C".to_string(), "A seasonal COP of 2.5".to_string()],
+    }
+}
+
+#[test]
+fn suites_skip_blank_lines_and_point_at_bad_ones() {
+    let cases = parse_eval_suite("{\"topic\": \"a\", \"facts\": [\"x\"]}\n\n{\"topic\": \"b\"}\n").unwrap();
+    assert_eq!(cases.len(), 2);
+    assert!(cases[1].facts.is_empty());
+
+    let error = parse_eval_suite("{\"topic\": \"a\"}\n{\"facts\": []}").unwrap_err();
+    assert!(error.to_string().starts_with("Line 2:"), "{}", error);
+    assert!(parse_eval_suite("{\"topic\": \" \"}").is_err());
+    assert!(parse_eval_suite("\n").is_err());
+}
+
+#[test]
+fn citations_count_per_paragraph_and_source_lists_are_left_out() {
+    let report = "### Track One\nHeat pumps work [1].\n\nThey need backup heat.\n\n\
+        See [the study](https://example.org/study).\n\n### Track One Sources:\n* https://example.org/study";
+
+    let scores = score_report(report, &SummaryState::with_research_topic(TOPIC.to_string()), &[]);
+
+    assert!((scores.citation_coverage - 2.0 / 3.0).abs() < 1e-9, "{}", scores.citation_coverage);
+    assert_eq!(scores.factual_recall, 0.0);
+    assert_eq!(scores.sources, 0);
+}
+
+#[tokio::test]
+async fn runs_are_scored_per_configuration() {
+    let mocks = research_mocks();
+    let config = mock_config(&mocks);
+
+    let report = run_eval(&[case()], &[("local".to_string(), config)], None, None).await;
+
+    assert_eq!(report.results.len(), 1);
+    let scores = report.results[0].scores.as_ref().unwrap();
+    // The summary mentions the temperature but not the COP
+    assert_eq!(scores.facts_found, 1);
+    assert_eq!(scores.facts_missed, vec!["A seasonal COP of 2.5".to_string()]);
+    assert_eq!(scores.factual_recall, 0.5);
+    assert_eq!(scores.domains, 2);
+    assert_eq!(scores.source_diversity, 1.0);
+    assert_eq!(scores.words, SUMMARY.split_whitespace().count());
+    assert!(scores.judge.is_none());
+
+    let summary = &report.summaries[0];
+    assert_eq!((summary.runs, summary.failed), (1, 0));
+    let table = report.render_markdown();
+    assert!(table.contains(&format!("| local | {} (", BUILTIN_PROMPTS_VERSION)), "{}", table);
+    assert!(table.contains(") | 1 | 0 | 50% |"), "{}", table);
+    assert!(table.contains("Missed: A seasonal COP of 2.5"), "{}", table);
+}
+
+#[tokio::test]
+async fn failed_runs_are_kept_in_the_comparison() {
+    let good = mock_config(&research_mocks());
+    let broken = MockProviders::new(MockLlm::new().otherwise(MockReply::error(401, "invalid api key")), research_search());
+    let bad = mock_config(&broken);
+
+    let report = run_eval(&[case()], &[("good".to_string(), good), ("bad".to_string(), bad)], None, None).await;
+
+    assert_eq!(report.summaries.len(), 2);
+    assert_eq!(report.summaries[0].failed, 0);
+    assert_eq!(report.summaries[1].failed, 1);
+    assert!(report.results[1].error.is_some());
+    assert!(report.render_markdown().contains("| bad | Do heat pumps work in cold climates? | | | | | | Failed:"));
+}
+
+#[tokio::test]
+async fn a_judge_grades_each_report() {
+    let mocks = research_mocks();
+    let config = mock_config(&mocks);
+    let judge = MockProviders::new(
+        MockLlm::new().when("grading a research report", MockReply::thinking(
+            "It covers the temperature.",
+            &json!({ "score": 14, "reason": "Accurate but misses the COP." }).to_string(),
+        )),
+        research_search(),
+    );
+    let judge_config = mock_config(&judge);
+
+    let report = run_eval(&[case()], &[("local".to_string(), config)], Some(&judge_config), None).await;
+
+    let verdict = report.results[0].scores.as_ref().unwrap().judge.clone().unwrap();
+    // Scores are held to the 0 to 10 scale
+    assert_eq!(verdict.score, 10.0);
+    assert_eq!(verdict.reason, "Accurate but misses the COP.");
+    assert!(judge.llm.prompts()[0].contains("A seasonal COP of 2.5"));
+    assert_eq!(report.summaries[0].judge_score, Some(10.0));
+    assert!(report.render_markdown().contains("| Judge |"));
+    // The research run itself never saw the judge's prompt
+    assert!(mocks.llm.prompts().iter().all(|prompt| !prompt.contains("grading a research report")));
+}
+
+#[tokio::test]
+async fn a_failed_judge_leaves_the_score_out() {
+    // The research script has no rule for the judge's prompt, so grading fails but the run still counts
+    let mocks = MockProviders::new(research_llm(), research_search());
+    let config = mock_config(&mocks);
+
+    let report = run_eval(&[case()], &[("local".to_string(), config.clone())], Some(&config), None).await;
+
+    assert!(report.results[0].error.is_none());
+    assert!(report.results[0].scores.as_ref().unwrap().judge.is_none());
+}
+
+#[tokio::test]
+async fn a_malformed_verdict_leaves_the_score_out() {
+    let mocks = research_mocks();
+    let config = mock_config(&mocks);
+    // A closing brace before the only opening one
+    let judge = MockProviders::new(
+        MockLlm::new().when("grading a research report", MockReply::text("} Score: {7")),
+        research_search(),
+    );
+
+    let report = run_eval(&[case()], &[("local".to_string(), config)], Some(&mock_config(&judge)), None).await;
+
+    assert!(report.results[0].error.is_none());
+    assert!(report.results[0].scores.as_ref().unwrap().judge.is_none());
+}
+
+#[tokio::test]
+async fn the_verdict_is_read_from_the_first_object_after_thinking() {
+    let mocks = research_mocks();
+    let config = mock_config(&mocks);
+    let judge = MockProviders::new(
+        MockLlm::new().when("grading a research report", MockReply::thinking(
+            "{\"score\": 1, \"reason\": \"draft\"}",
+            "Verdict: {\"score\": 6, \"reason\": \"Covers {most} of it.\"} Hope that helps}",
+        )),
+        research_search(),
+    );
+
+    let report = run_eval(&[case()], &[("local".to_string(), config)], Some(&mock_config(&judge)), None).await;
+
+    let verdict = report.results[0].scores.as_ref().unwrap().judge.clone().unwrap();
+    assert_eq!(verdict.score, 6.0);
+    assert_eq!(verdict.reason, "Covers {most} of it.");
+}
@@ -0,0 +1,227 @@
+// The research graph's control flow, against scripted providers
+mod common;
+
+use std::time::Duration;
+
+use common::{
+    mock_config, research_llm, research_mocks, research_search, with_research_replies, PERSPECTIVE_ONE, PERSPECTIVE_TWO,
+    SUMMARY, TOPIC,
+};
+use researcher::assistant::configuration::{LlmProvider, PartialResults, ResearchMode};
+use researcher::assistant::error::ResearchError;
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply, MockSearch};
+use researcher::{ResearchGraph, SummaryStateInput};
+use serde_json::json;
+use tokio::sync::broadcast;
+
+fn input() -> SummaryStateInput {
+    SummaryStateInput { research_topic: TOPIC.to_string() }
+}
+
+#[tokio::test]
+async fn local_run_loops_until_the_limit() {
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    config.max_web_research_loops = 2;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    assert_eq!(output.running_summary, SUMMARY);
+    // Every loop searches for the track's perspective
+    assert_eq!(mocks.search.queries(), vec![PERSPECTIVE_ONE.to_string(), PERSPECTIVE_ONE.to_string()]);
+    // The debate, then query, summary and reflection per loop
+    assert_eq!(mocks.llm.prompts().len(), 7);
+    let usage = output.state.usage.unwrap();
+    assert_eq!(usage.total.calls, 9);
+    assert_eq!(usage.by_node["web_research"].calls, 2);
+}
+
+#[tokio::test]
+async fn a_spent_budget_stops_research_but_keeps_the_report() {
+    // One unbudgeted loop to learn what the debate, search and summary cost
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    config.max_web_research_loops = 1;
+    let usage = ResearchGraph::new(config).process_research(input()).await.unwrap().state.usage.unwrap();
+    let budget: u64 = ["debate", "web_research", "summarizer"].iter()
+        .map(|node| usage.by_node[*node].total_tokens())
+        .sum();
+    assert!(budget < usage.total.total_tokens());
+
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    config.max_web_research_loops = 3;
+    config.max_tokens_per_run = Some(budget);
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    // The budget runs out before the first reflection, and the summary so far is still finalized
+    assert_eq!(output.running_summary, SUMMARY);
+    assert_eq!(mocks.search.queries(), vec![PERSPECTIVE_ONE.to_string()]);
+    assert!(output.state.stopped_early.unwrap().contains("token budget"));
+    assert!(!output.state.usage.unwrap().by_node.contains_key("reflection"));
+}
+
+#[tokio::test]
+async fn status_updates_follow_the_pipeline() {
+    let mocks = research_mocks();
+    let config = mock_config(&mocks);
+    let (tx, mut rx) = broadcast::channel(100);
+
+    let mut graph = ResearchGraph::new(config);
+    graph.set_status_sender(tx);
+    graph.process_research(input()).await.unwrap();
+
+    let mut phases = Vec::new();
+    while let Ok(update) = rx.try_recv() {
+        if phases.last() != Some(&update.phase) {
+            phases.push(update.phase);
+        }
+    }
+    assert_eq!(phases.first().map(String::as_str), Some("init"));
+    assert_eq!(phases.last().map(String::as_str), Some("complete"));
+    for phase in ["perspectives", "loop", "research", "summary", "reflection", "query"] {
+        assert!(phases.iter().any(|seen| seen == phase), "no {} update in {:?}", phase, phases);
+    }
+}
+
+#[tokio::test]
+async fn remote_run_researches_each_perspective() {
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    config.research_mode = ResearchMode::Remote;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    let mut queries = mocks.search.queries();
+    queries.sort();
+    assert_eq!(queries, vec![PERSPECTIVE_TWO.to_string(), PERSPECTIVE_ONE.to_string()]);
+    assert!(output.running_summary.contains("### Track One"));
+    assert!(output.running_summary.contains("### Track Two"));
+    assert!(output.state.served_by.iter().all(|served| served.provider == "groq" && !served.fallback));
+}
+
+#[tokio::test]
+async fn a_blank_perspective_keeps_the_others_on_their_tracks() {
+    let llm = MockLlm::new().when("debate coach", MockReply::json(json!({
+        "topic": TOPIC,
+        "perspectives": [PERSPECTIVE_ONE, "", PERSPECTIVE_TWO],
+    })));
+    let search = MockSearch::new()
+        .when(PERSPECTIVE_TWO, MockReply::search("Backup heat runs a few days a year.", &["https://example.org/backup"]))
+        .otherwise(MockReply::search("Heat pumps hold a COP of 2 at -15C.", &["https://example.org/cop"]));
+    let mocks = MockProviders::new(with_research_replies(llm), search);
+    let mut config = mock_config(&mocks);
+    config.research_mode = ResearchMode::Remote;
+    config.num_perspectives = 3;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    // Track two falls back to the first perspective and track three keeps the third
+    let sources = |track: &str| output.state.tracks.iter().find(|t| t.name == track).unwrap().sources.join(" ");
+    assert!(sources("two").contains("example.org/cop"), "{}", sources("two"));
+    assert!(sources("three").contains("example.org/backup"), "{}", sources("three"));
+}
+
+#[tokio::test]
+async fn local_runs_research_a_single_track() {
+    let llm = MockLlm::new().when("debate coach", MockReply::json(json!({
+        "topic": TOPIC,
+        "perspectives": [PERSPECTIVE_ONE, PERSPECTIVE_TWO, "Running costs decide it"],
+    })));
+    let mocks = MockProviders::new(with_research_replies(llm), research_search());
+    let mut config = mock_config(&mocks);
+    config.num_perspectives = 3;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    assert_eq!(output.state.tracks.len(), 1);
+    assert_eq!(mocks.search.queries(), vec![PERSPECTIVE_ONE.to_string()]);
+    assert!(output.state.failed_tracks().is_empty());
+}
+
+#[tokio::test]
+async fn a_failed_track_leaves_a_partial_result() {
+    let search = MockSearch::new().when(PERSPECTIVE_TWO, MockReply::error(500, "search backend down"));
+    let search = search.otherwise(MockReply::search("Heat pumps hold a COP of 2 at -15C.", &["https://example.org/cop"]));
+    let mocks = MockProviders::new(research_llm(), search);
+    let mut config = mock_config(&mocks);
+    config.research_mode = ResearchMode::Remote;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    let failed = output.state.failed_tracks();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, "two");
+    assert_eq!(failed[0].error.as_ref().unwrap().code, "provider");
+    assert!(output.running_summary.contains("This track failed"));
+}
+
+#[tokio::test]
+async fn partial_results_all_fails_the_run() {
+    let search = MockSearch::new()
+        .when(PERSPECTIVE_TWO, MockReply::error(500, "search backend down"))
+        .otherwise(MockReply::search("Heat pumps hold a COP of 2 at -15C.", &["https://example.org/cop"]));
+    let mocks = MockProviders::new(research_llm(), search);
+    let mut config = mock_config(&mocks);
+    config.research_mode = ResearchMode::Remote;
+    config.partial_results = PartialResults::All;
+
+    let error = ResearchGraph::new(config).process_research(input()).await.unwrap_err();
+
+    assert!(error.to_string().contains("1 of 2 research tracks failed (two)"), "{}", error);
+}
+
+#[tokio::test]
+async fn retries_recover_from_a_transient_error() {
+    let llm = with_research_replies(MockLlm::new().times("debate coach", 1, MockReply::error(503, "overloaded")));
+    let mocks = MockProviders::new(llm, research_search());
+    let mut config = mock_config(&mocks);
+    config.http.max_retries = 1;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    assert_eq!(output.running_summary, SUMMARY);
+    assert_eq!(mocks.llm.prompts().iter().filter(|prompt| prompt.contains("debate coach")).count(), 2);
+}
+
+#[tokio::test]
+async fn failed_calls_fall_back_to_the_next_provider() {
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    config.research_mode = ResearchMode::Remote;
+    config.llm_fallback = vec![LlmProvider::Ollama];
+    // The same mock answers both providers, so fail Groq by leaving it without a key
+    config.groq_api_key = None;
+
+    let output = ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    assert!(!output.state.served_by.is_empty());
+    assert!(output.state.served_by.iter().all(|served| served.provider == "ollama" && served.fallback));
+}
+
+#[tokio::test]
+async fn slow_providers_are_waited_for() {
+    let mocks = MockProviders::new(research_llm().with_latency(Duration::from_millis(20)), research_search());
+    let config = mock_config(&mocks);
+
+    let started = std::time::Instant::now();
+    ResearchGraph::new(config).process_research(input()).await.unwrap();
+
+    // The debate, query, summary and reflection calls each wait
+    assert!(started.elapsed() >= Duration::from_millis(80));
+}
+
+#[tokio::test]
+async fn malformed_perspectives_fail_the_run() {
+    let llm = MockLlm::new().otherwise(MockReply::text("I can't frame a debate on that."));
+    let mocks = MockProviders::new(llm, research_search());
+    let config = mock_config(&mocks);
+
+    let error = ResearchGraph::new(config).process_research(input()).await.unwrap_err();
+
+    match ResearchError::classify(&error) {
+        ResearchError::Parse { what, .. } => assert_eq!(what, "debate perspectives"),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
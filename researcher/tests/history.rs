@@ -0,0 +1,86 @@
+// Saved research runs and batches
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{mock_config, research_mocks, scratch_dir, SUMMARY, TOPIC};
+use researcher::assistant::batch::{batch_from_values, parse_batch, run_batch, BatchFormat, BatchOptions, BatchStatus, BatchStore};
+use researcher::assistant::history::{ResearchRun, RunHistory};
+use researcher::{Configuration, SummaryState, SummaryStateOutput};
+use serde_json::json;
+use tokio::sync::Semaphore;
+
+fn output() -> SummaryStateOutput {
+    SummaryStateOutput {
+        running_summary: SUMMARY.to_string(),
+        images: Vec::new(),
+        state: SummaryState::with_research_topic(TOPIC.to_string()),
+    }
+}
+
+#[tokio::test]
+async fn runs_of_the_same_topic_are_kept_apart() {
+    let history = RunHistory::open(scratch_dir()).await.unwrap();
+    let config = Configuration::default();
+
+    let first = ResearchRun::new(0, &config, output());
+    let second = ResearchRun::new(0, &config, output());
+    history.save(&first).await.unwrap();
+    history.save(&second).await.unwrap();
+
+    assert_ne!(first.id, second.id);
+    assert_eq!(history.list().await.unwrap().len(), 2);
+    assert_eq!(history.get(&second.id).await.unwrap().unwrap().summary, SUMMARY);
+}
+
+#[tokio::test]
+async fn batches_of_the_same_topics_are_kept_apart() {
+    let history = RunHistory::open(scratch_dir()).await.unwrap();
+    let store = BatchStore::open(&history).await.unwrap();
+    let items = parse_batch(&format!("\"{}\"", TOPIC), BatchFormat::Jsonl).unwrap();
+    let options = BatchOptions { concurrency: 1, runs_per_minute: None, run_slots: None };
+
+    let first = store.create(&items, &options).await.unwrap();
+    let second = store.create(&items, &options).await.unwrap();
+
+    assert_ne!(first.id, second.id);
+    assert!(store.get(&first.id).await.unwrap().is_some());
+}
+
+#[test]
+fn request_topics_are_strings_or_objects() {
+    let items = batch_from_values(vec![json!(TOPIC), json!({ "topic": "Solar", "profile": "deep", "max_web_research_loops": 2 })]).unwrap();
+
+    assert_eq!(items[0].topic, TOPIC);
+    assert_eq!(items[1].profile.as_deref(), Some("deep"));
+    assert_eq!(items[1].overrides["max_web_research_loops"], 2);
+
+    let error = batch_from_values(vec![json!(TOPIC), json!(7)]).unwrap_err();
+    assert!(error.to_string().starts_with("Topic 2:"), "{}", error);
+    assert!(batch_from_values(vec![json!(" ")]).is_err());
+    assert!(batch_from_values(Vec::new()).is_err());
+}
+
+#[tokio::test]
+async fn batches_wait_for_a_shared_run_slot() {
+    let config = mock_config(&research_mocks());
+    let history = Arc::new(RunHistory::open(&config.research_history_dir).await.unwrap());
+    let store = Arc::new(BatchStore::open(&history).await.unwrap());
+    let items = parse_batch(&format!("\"{}\"", TOPIC), BatchFormat::Jsonl).unwrap();
+    let slots = Arc::new(Semaphore::new(0));
+    let options = BatchOptions { concurrency: 2, runs_per_minute: None, run_slots: Some(slots.clone()) };
+    let index = store.create(&items, &options).await.unwrap();
+    let id = index.id.clone();
+
+    let batch = tokio::spawn(run_batch(index, items, config, options, store.clone(), history, None));
+
+    // Another batch holds every slot, so this one can't start its topic
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert_eq!(store.get(&id).await.unwrap().unwrap().entries[0].status, BatchStatus::Pending);
+
+    slots.add_permits(1);
+    let index = batch.await.unwrap().unwrap();
+    assert_eq!(index.entries[0].status, BatchStatus::Completed);
+    assert_eq!(slots.available_permits(), 1);
+}
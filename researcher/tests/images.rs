@@ -0,0 +1,34 @@
+// Image prompts for report sections, against a scripted model
+mod common;
+
+use common::mock_config;
+use researcher::assistant::images::generate_visual_prompts;
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply, MockSearch};
+
+const REPORT: &str = "### Track One\nHeat pumps keep heating at -25C.\n\n### Track Two\nBackup heat runs a few days a year.\n";
+
+#[tokio::test]
+async fn prompts_are_read_past_stray_brackets() {
+    let reply = "Sections per note] 1 and 2 [see below]:\n\
+        [{\"section\": \"Track One\", \"prompt\": \"A heat pump in deep snow\"}] and that's it]";
+    let mocks = MockProviders::new(MockLlm::new().when("art director", MockReply::text(reply)), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let prompts = generate_visual_prompts(REPORT, &config).await.unwrap();
+
+    assert_eq!(prompts.len(), 1);
+    assert_eq!(prompts[0].section, "Track One");
+    assert_eq!(prompts[0].prompt, "A heat pump in deep snow");
+}
+
+#[tokio::test]
+async fn replies_without_an_array_fall_back_to_the_sections() {
+    let reply = "<think>Nothing fits.</think> I would rather not] draw anything [sorry";
+    let mocks = MockProviders::new(MockLlm::new().when("art director", MockReply::text(reply)), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let prompts = generate_visual_prompts(REPORT, &config).await.unwrap();
+
+    let sections: Vec<&str> = prompts.iter().map(|prompt| prompt.section.as_str()).collect();
+    assert_eq!(sections, vec!["Track One", "Track Two"]);
+}
@@ -0,0 +1,159 @@
+// Each graph node on its own, against scripted providers
+mod common;
+
+use std::sync::Arc;
+
+use common::{mock_config, research_llm, research_search, SUMMARY, TOPIC};
+use researcher::assistant::error::ResearchError;
+use researcher::assistant::graph::{Node, QueryGeneratorNode, ReflectionNode, SummarizerNode, WebResearchNode};
+use researcher::assistant::mock::{MockLlm, MockProviders, MockReply, MockSearch};
+use researcher::SummaryState;
+use tokio::sync::Mutex;
+
+fn state() -> Arc<Mutex<SummaryState>> {
+    let mut state = SummaryState::with_research_topic(TOPIC.to_string());
+    state.set_search_query("one", "heat pumps below freezing".to_string());
+    Arc::new(Mutex::new(state))
+}
+
+#[tokio::test]
+async fn query_generator_uses_the_query_from_the_model() {
+    let mocks = MockProviders::new(research_llm(), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let query = QueryGeneratorNode.process(state(), &config, "one").await.unwrap();
+
+    assert_eq!(query, "cold climate heat pump COP");
+    let prompts = mocks.llm.prompts();
+    assert_eq!(prompts.len(), 1);
+    assert!(prompts[0].contains(TOPIC));
+}
+
+#[tokio::test]
+async fn query_generator_falls_back_to_the_topic_on_prose() {
+    let mocks = MockProviders::new(
+        MockLlm::new().otherwise(MockReply::text("Sure! Here is a better query: heat pumps")),
+        MockSearch::new(),
+    );
+    let config = mock_config(&mocks);
+
+    let query = QueryGeneratorNode.process(state(), &config, "one").await.unwrap();
+
+    assert_eq!(query, TOPIC);
+}
+
+#[tokio::test]
+async fn web_research_stores_the_answer_and_citations() {
+    let mocks = MockProviders::new(MockLlm::new(), research_search());
+    let config = mock_config(&mocks);
+    let state = state();
+
+    WebResearchNode.process(state.clone(), &config, "one").await.unwrap();
+
+    assert_eq!(mocks.search.queries(), vec!["heat pumps below freezing".to_string()]);
+    let state = state.lock().await;
+    let track = state.get_track("one");
+    assert_eq!(track.web_research_results.len(), 1);
+    assert!(track.web_research_results[0].contains("seasonal COP"));
+    assert_eq!(track.sources.len(), 2);
+    assert!(track.sources[1].contains("https://www.nrel.gov/cold-climate-heat-pumps"));
+}
+
+#[tokio::test]
+async fn citations_are_not_tagged_with_the_answers_language() {
+    let answer = "Wärmepumpen heizen auch bei minus zwanzig Grad zuverlässig, wenn sie richtig ausgelegt sind.";
+    let search = MockSearch::new().otherwise(MockReply::search(answer, &["https://example.org/a", "https://example.org/b"]));
+    let mocks = MockProviders::new(MockLlm::new(), search);
+    let config = mock_config(&mocks);
+    let state = state();
+
+    WebResearchNode.process(state.clone(), &config, "one").await.unwrap();
+
+    // Perplexity only returns URLs here, so there's nothing to tell the sources' languages from
+    let state = state.lock().await;
+    let sources = &state.get_track("one").sources;
+    assert_eq!(sources.len(), 2);
+    assert!(sources.iter().all(|source| !source.contains("German")), "{:?}", sources);
+}
+
+#[tokio::test]
+async fn web_research_reports_an_empty_answer() {
+    let mocks = MockProviders::new(MockLlm::new(), MockSearch::new().otherwise(MockReply::search("  ", &[])));
+    let config = mock_config(&mocks);
+
+    let error = WebResearchNode.process(state(), &config, "one").await.unwrap_err();
+
+    assert_eq!(
+        ResearchError::classify(&error),
+        ResearchError::SearchEmpty { query: "heat pumps below freezing".to_string() }
+    );
+}
+
+#[tokio::test]
+async fn web_research_reports_malformed_json() {
+    let mocks = MockProviders::new(MockLlm::new(), MockSearch::new().otherwise(MockReply::Malformed));
+    let config = mock_config(&mocks);
+
+    let error = WebResearchNode.process(state(), &config, "one").await.unwrap_err();
+
+    assert!(matches!(ResearchError::classify(&error), ResearchError::Parse { .. }), "{:#}", error);
+}
+
+#[tokio::test]
+async fn summarizer_strips_think_blocks() {
+    let mocks = MockProviders::new(research_llm(), MockSearch::new());
+    let config = mock_config(&mocks);
+    let state = state();
+    state.lock().await.add_web_research_result("one", "Field studies report a COP of 2.5.".to_string());
+
+    SummarizerNode.process(state.clone(), &config, "one").await.unwrap();
+
+    assert_eq!(state.lock().await.get_track("one").running_summary, SUMMARY);
+    assert!(mocks.llm.prompts()[0].contains("Field studies report a COP of 2.5."));
+}
+
+#[tokio::test]
+async fn reflection_falls_back_when_the_model_ignores_the_format() {
+    let mocks = MockProviders::new(MockLlm::new().otherwise(MockReply::text("More research is needed.")), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let query = ReflectionNode.process(state(), &config, "one").await.unwrap();
+
+    assert_eq!(query, format!("Tell me more about {}", TOPIC));
+}
+
+#[tokio::test]
+async fn provider_errors_are_classified() {
+    let mocks = MockProviders::new(MockLlm::new().otherwise(MockReply::error(401, "invalid api key")), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let error = ReflectionNode.process(state(), &config, "one").await.unwrap_err();
+
+    match ResearchError::classify(&error) {
+        ResearchError::ProviderAuth { provider, .. } => assert_eq!(provider, "ollama"),
+        other => panic!("expected an auth error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn a_missing_key_is_a_server_problem() {
+    let mocks = MockProviders::new(research_llm(), research_search());
+    let mut config = mock_config(&mocks);
+    config.perplexity_api_key.clear();
+
+    let error = WebResearchNode.process(state(), &config, "one").await.unwrap_err();
+
+    let error = ResearchError::classify(&error);
+    assert!(matches!(error, ResearchError::Misconfigured(_)), "{:?}", error);
+    assert_eq!(error.http_status(), 503);
+}
+
+#[tokio::test]
+async fn timeouts_are_classified() {
+    let mocks = MockProviders::new(MockLlm::new().otherwise(MockReply::Timeout), MockSearch::new());
+    let config = mock_config(&mocks);
+
+    let error = ReflectionNode.process(state(), &config, "one").await.unwrap_err();
+
+    assert!(matches!(ResearchError::classify(&error), ResearchError::Timeout { .. }), "{:#}", error);
+}
@@ -0,0 +1,87 @@
+// The episode library and the feed built from it
+mod common;
+
+use common::scratch_dir;
+use researcher::podcast::library::{EpisodeLibrary, NewEpisode};
+use researcher::podcast::{render_feed, EpisodeSegment, EpisodeSource, FeedChannel};
+
+fn channel() -> FeedChannel {
+    FeedChannel {
+        title: "Research & Co".to_string(),
+        description: "Research podcasts".to_string(),
+        author: "ZU-LM".to_string(),
+        language: "de".to_string(),
+        base_url: "https://pods.example.org/".to_string(),
+    }
+}
+
+fn new_episode(title: &str) -> NewEpisode {
+    NewEpisode {
+        title: title.to_string(),
+        topic: "Heat pumps".to_string(),
+        summary: "Cold-climate heat pumps keep heating down to about -25C.".to_string(),
+        sources: Vec::new(),
+        segments: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn episodes_with_the_same_title_get_their_own_ids() {
+    let library = EpisodeLibrary::open(scratch_dir()).await.unwrap();
+
+    let first = library.create(new_episode("Heat pumps in winter")).await.unwrap();
+    let second = library.create(new_episode("Heat pumps in winter")).await.unwrap();
+
+    assert_ne!(first.id, second.id);
+    assert!(first.id.contains("-heat-pumps-in-winter-"), "{}", first.id);
+    assert_eq!(library.list().await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn audio_and_images_are_stored_with_the_episode() {
+    let library = EpisodeLibrary::open(scratch_dir()).await.unwrap();
+    let mut episode = new_episode("Heat pumps in winter");
+    episode.segments = vec![
+        EpisodeSegment { speaker: "Host".to_string(), text: "Welcome.".to_string(), duration: 1.5, image: None },
+        EpisodeSegment { speaker: "Guest".to_string(), text: "Thanks.".to_string(), duration: 2.0, image: None },
+    ];
+    let episode = library.create(episode).await.unwrap();
+
+    library.store_audio(&episode.id, b"ID3 audio").await.unwrap().unwrap();
+    library.set_segment_images(&episode.id, vec![None, Some("/media/images/a.svg".to_string())]).await.unwrap();
+
+    let stored = library.get(&episode.id).await.unwrap().unwrap();
+    assert_eq!(stored.audio_file, Some(format!("{}.mp3", episode.id)));
+    assert_eq!(stored.audio_length, 9);
+    assert!(library.dir().join(format!("{}.mp3", episode.id)).exists());
+    assert_eq!(stored.segments[0].image, None);
+    assert_eq!(stored.segments[1].image.as_deref(), Some("/media/images/a.svg"));
+
+    assert!(library.store_audio("1700000000-missing", b"ID3").await.unwrap().is_none());
+    assert!(library.get("../outside").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn the_feed_lists_episodes_with_audio() {
+    let library = EpisodeLibrary::open(scratch_dir()).await.unwrap();
+    let mut with_audio = new_episode("Heat pumps <in> winter");
+    with_audio.summary = "## Track One\nThey keep heating.\n\n### Sources\n- Energy (https://www.energy.gov)".to_string();
+    with_audio.sources = vec![EpisodeSource { title: "Energy".to_string(), url: "https://www.energy.gov".to_string() }];
+    with_audio.segments = vec![EpisodeSegment { speaker: "Host".to_string(), text: "Hi.".to_string(), duration: 65.0, image: None }];
+    let with_audio = library.create(with_audio).await.unwrap();
+    library.store_audio(&with_audio.id, b"ID3 audio").await.unwrap();
+    library.create(new_episode("Not recorded yet")).await.unwrap();
+
+    let feed = render_feed(&channel(), &library.list().await.unwrap());
+
+    assert!(feed.contains("<title>Research &amp; Co</title>"), "{}", feed);
+    assert!(feed.contains("<language>de</language>"));
+    assert_eq!(feed.matches("<item>").count(), 1);
+    assert!(feed.contains("<title>Heat pumps &lt;in&gt; winter</title>"));
+    let enclosure = format!("<enclosure url=\"https://pods.example.org/media/{}.mp3\" length=\"9\" type=\"audio/mpeg\"/>", with_audio.id);
+    assert!(feed.contains(&enclosure), "{}", feed);
+    assert!(feed.contains("<itunes:duration>00:01:05</itunes:duration>"));
+    // The description drops the headings and source listing; the show notes link the sources
+    assert!(feed.contains("<description>They keep heating.</description>"), "{}", feed);
+    assert!(feed.contains("<li><a href=\"https://www.energy.gov\">Energy</a></li>"));
+}
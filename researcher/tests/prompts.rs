@@ -0,0 +1,104 @@
+// Prompt templates: the built-in ones, a prompts directory replacing them, and what a run records
+mod common;
+
+use common::{mock_config, research_mocks, scratch_dir, SUMMARY, TOPIC};
+use researcher::assistant::error::ResearchError;
+use researcher::assistant::prompts::{check_templates, prompt_version, render, BUILTIN_PROMPTS_VERSION};
+use researcher::{Configuration, ResearchGraph, SummaryStateInput};
+use serde_json::json;
+
+fn with_prompts_dir(config: &mut Configuration, templates: &[(&str, &str)]) {
+    let dir = scratch_dir().join("prompts");
+    for (name, source) in templates {
+        let path = dir.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, source).unwrap();
+    }
+    config.prompts.dir = Some(dir.display().to_string());
+}
+
+#[test]
+fn query_writer_is_given_the_topic() {
+    let prompt = render(&Configuration::default(), "query_writer.jinja", json!({ "research_topic": TOPIC })).unwrap();
+
+    assert!(prompt.contains(&format!("about the research topic: {}", TOPIC)), "{}", prompt);
+}
+
+#[test]
+fn summaries_follow_the_requested_language() {
+    let context = |language: &str| json!({
+        "research_topic": TOPIC,
+        "language": language,
+        "existing_summary": "",
+        "search_results": "Field studies report a COP of 2.5.",
+    });
+    let config = Configuration::default();
+
+    let english = render(&config, "summarizer.jinja", context("en")).unwrap();
+    let german = render(&config, "summarizer.jinja", context("German")).unwrap();
+
+    assert!(!english.contains("Write your entire response in"));
+    assert!(german.contains("Write your entire response in German"));
+    assert!(!german.contains("<Existing Summary>"));
+}
+
+#[test]
+fn a_prompts_directory_replaces_templates_and_adds_partials() {
+    let mut config = Configuration::default();
+    let builtin = prompt_version(&config).unwrap();
+    with_prompts_dir(&mut config, &[
+        ("reflection.jinja", "{% include \"partials/house_style.jinja\" %} What is missing about {{ research_topic }}?"),
+        ("partials/house_style.jinja", "Answer like a skeptical engineer."),
+    ]);
+
+    let prompt = render(&config, "reflection.jinja", json!({ "research_topic": TOPIC, "running_summary": "" })).unwrap();
+    assert_eq!(prompt, format!("Answer like a skeptical engineer. What is missing about {}?", TOPIC));
+    // Templates the directory doesn't have stay built in
+    assert!(render(&config, "enrichment.jinja", json!({ "text": "heat pumps" })).unwrap().contains("key facts"));
+
+    let version = prompt_version(&config).unwrap();
+    assert_eq!(builtin.version, BUILTIN_PROMPTS_VERSION);
+    assert_eq!(version.version, "custom");
+    assert_eq!(version.custom, vec!["partials/house_style.jinja".to_string(), "reflection.jinja".to_string()]);
+    assert_ne!(version.fingerprint, builtin.fingerprint);
+}
+
+#[test]
+fn broken_templates_are_configuration_errors() {
+    let mut config = Configuration::default();
+    with_prompts_dir(&mut config, &[
+        ("debate.jinja", "Frame a debate on {{ topic }"),
+        ("judge.jinja", "Grade the report on {{ research_topik }}."),
+    ]);
+
+    let error = check_templates(&config).unwrap_err();
+    assert!(matches!(ResearchError::classify(&error), ResearchError::Misconfigured(_)), "{:#}", error);
+    assert!(error.to_string().contains("debate.jinja"), "{}", error);
+
+    // Misspelled variables fail instead of leaving a blank in the prompt
+    let error = render(&config, "judge.jinja", json!({ "topic": TOPIC, "facts": [], "report": "" })).unwrap_err();
+    assert!(error.to_string().contains("judge.jinja"), "{}", error);
+}
+
+#[tokio::test]
+async fn runs_use_and_record_their_prompts() {
+    let mocks = research_mocks();
+    let mut config = mock_config(&mocks);
+    with_prompts_dir(&mut config, &[(
+        "query_writer.jinja",
+        "You are generating effective search queries for engineers. Topic: {{ research_topic }}",
+    )]);
+    config.prompts.version = Some("engineers-v1".to_string());
+
+    let output = ResearchGraph::new(config)
+        .process_research(SummaryStateInput { research_topic: TOPIC.to_string() })
+        .await
+        .unwrap();
+
+    assert_eq!(output.running_summary, SUMMARY);
+    let prompts = mocks.llm.prompts();
+    assert!(prompts.contains(&format!("You are generating effective search queries for engineers. Topic: {}", TOPIC)));
+    let version = output.state.prompts.unwrap();
+    assert_eq!(version.version, "engineers-v1");
+    assert_eq!(version.custom, vec!["query_writer.jinja".to_string()]);
+}
@@ -0,0 +1,97 @@
+// Runs the research pipeline against recorded provider traffic, so no network is needed.
+// Fixtures are recorded with `[http.fixtures] mode = "record"`; scripts/record-fixture.sh re-records
+// research_local.json, see the README.
+use researcher::assistant::configuration::{FixtureConfig, FixtureMode, HttpPolicy, ResearchMode};
+use researcher::assistant::error::ResearchError;
+use researcher::{Configuration, ResearchGraph, SummaryStateInput};
+
+const TOPIC: &str = "Do heat pumps work in cold climates?";
+
+fn replay_config(fixture: &str) -> Configuration {
+    Configuration {
+        research_mode: ResearchMode::Local,
+        max_web_research_loops: 1,
+        perplexity_api_key: "test".to_string(),
+        http: HttpPolicy {
+            max_retries: 0,
+            fixtures: Some(FixtureConfig {
+                mode: FixtureMode::Replay,
+                path: format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture),
+            }),
+            ..HttpPolicy::default()
+        },
+        ..Configuration::default()
+    }
+}
+
+#[tokio::test]
+async fn local_research_replays_recorded_run() {
+    let mut graph = ResearchGraph::new(replay_config("research_local.json"));
+    let output = graph
+        .process_research(SummaryStateInput { research_topic: TOPIC.to_string() })
+        .await
+        .expect("the recorded run replays");
+
+    // The <think> block in the recorded summary is stripped
+    assert!(output.running_summary.starts_with("Modern cold-climate heat pumps"));
+    assert!(!output.running_summary.contains("<think>"));
+
+    let state = &output.state;
+    let perspectives = state.debate_perspectives.as_ref().expect("perspectives are recorded");
+    assert_eq!(perspectives.perspective_one, "Heat pumps now work well far below freezing");
+
+    let track = state.get_track("one");
+    assert_eq!(track.web_research_results.len(), 1);
+    assert!(track.web_research_results[0].contains("seasonal COP of 2.5 to 3"));
+    assert_eq!(track.sources.len(), 2);
+    assert!(track.sources[0].contains("https://www.energy.gov/heat-pumps"));
+    assert!(state.failed_tracks().is_empty());
+
+    // Debate, query, search, summary and reflection
+    let usage = state.usage.as_ref().expect("usage is reported");
+    assert_eq!(usage.total.calls, 5);
+    assert_eq!(usage.by_model["perplexity/sonar-pro"].calls, 1);
+}
+
+#[tokio::test]
+async fn replay_is_deterministic() {
+    let config = replay_config("research_local.json");
+    let mut summaries = Vec::new();
+    for _ in 0..2 {
+        let mut graph = ResearchGraph::new(config.clone());
+        let output = graph
+            .process_research(SummaryStateInput { research_topic: TOPIC.to_string() })
+            .await
+            .expect("the recorded run replays");
+        summaries.push(output.running_summary);
+    }
+    assert_eq!(summaries[0], summaries[1]);
+}
+
+#[tokio::test]
+async fn unrecorded_request_fails_without_network() {
+    let mut graph = ResearchGraph::new(replay_config("research_local.json"));
+    let error = graph
+        .process_research(SummaryStateInput { research_topic: "A topic nobody recorded".to_string() })
+        .await
+        .expect_err("nothing was recorded for this topic");
+
+    match ResearchError::classify(&error) {
+        ResearchError::Provider { provider, message } => {
+            assert_eq!(provider, "ollama");
+            assert!(message.contains("No recorded response"), "{}", message);
+        }
+        other => panic!("expected a provider error, got {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn missing_fixture_file_is_reported() {
+    let mut graph = ResearchGraph::new(replay_config("does_not_exist.json"));
+    let error = graph
+        .process_research(SummaryStateInput { research_topic: TOPIC.to_string() })
+        .await
+        .expect_err("there is nothing to replay");
+
+    assert!(format!("{:#}", error).contains("Couldn't load fixtures"), "{:#}", error);
+}
@@ -0,0 +1,135 @@
+// Report exports: format negotiation and the rendered documents
+mod common;
+
+use std::io::{Cursor, Read};
+
+use common::{mock_config, research_mocks, scratch_dir, TestServer, SUMMARY, TOPIC};
+use researcher::assistant::history::{ResearchRun, RunHistory};
+use researcher::assistant::images::ImageReference;
+use researcher::assistant::report::{Report, ReportFormat};
+use researcher::{Configuration, SummaryState, SummaryStateOutput};
+use zip::{CompressionMethod, ZipArchive};
+
+const SVG: &str = "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"4\" height=\"4\"/>";
+
+fn run() -> ResearchRun {
+    let config = Configuration { language: "German".to_string(), ..Configuration::default() };
+    let output = SummaryStateOutput {
+        running_summary: format!("### Track One\n{} <script>alert(1)</script>", SUMMARY),
+        images: vec![ImageReference {
+            section: "Track One".to_string(),
+            prompt: "A heat pump in the snow".to_string(),
+            url: "/media/images/a.svg".to_string(),
+            provider: "placeholder".to_string(),
+        }],
+        state: SummaryState::with_research_topic(TOPIC.to_string()),
+    };
+    ResearchRun::new(0, &config, output)
+}
+
+fn media_dir() -> std::path::PathBuf {
+    let dir = scratch_dir();
+    std::fs::create_dir_all(dir.join("images")).unwrap();
+    std::fs::write(dir.join("images/a.svg"), SVG).unwrap();
+    dir
+}
+
+fn unzip(bytes: Vec<u8>) -> ZipArchive<Cursor<Vec<u8>>> {
+    ZipArchive::new(Cursor::new(bytes)).expect("a zip archive")
+}
+
+fn entry(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+    let mut contents = String::new();
+    archive.by_name(name).unwrap_or_else(|_| panic!("no {}", name)).read_to_string(&mut contents).unwrap();
+    contents
+}
+
+#[test]
+fn accept_headers_honour_quality_and_specificity() {
+    let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+
+    assert_eq!(ReportFormat::from_accept("text/plain, text/html"), Some(ReportFormat::Html));
+    assert_eq!(ReportFormat::from_accept("*/*, application/json"), Some(ReportFormat::Json));
+    assert_eq!(ReportFormat::from_accept("text/html;q=0.5, application/epub+zip"), Some(ReportFormat::Epub));
+    assert_eq!(ReportFormat::from_accept(&format!("text/markdown;q=0.2, {};q=0.9", docx)), Some(ReportFormat::Docx));
+    assert_eq!(
+        ReportFormat::from_accept("text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"),
+        Some(ReportFormat::Html)
+    );
+    assert_eq!(ReportFormat::from_accept("text/markdown;q=0, */*"), Some(ReportFormat::Html));
+    assert_eq!(ReportFormat::from_accept("*/*"), Some(ReportFormat::Markdown));
+    assert_eq!(ReportFormat::from_accept("image/png, text/markdown;q=0"), None);
+}
+
+#[tokio::test]
+async fn html_is_self_contained_and_escapes_raw_html() {
+    let run = run();
+    let dir = media_dir();
+    let html = String::from_utf8(Report::load(&run, Some(&dir)).await.render(ReportFormat::Html).unwrap()).unwrap();
+
+    assert!(html.starts_with("<!DOCTYPE html>\n<html lang=\"de\">"), "{}", html);
+    assert!(html.contains(SUMMARY));
+    assert!(html.contains("&lt;script&gt;"), "{}", html);
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("src=\"data:image/svg+xml;base64,"), "{}", html);
+}
+
+#[tokio::test]
+async fn docx_unzips_into_a_word_document() {
+    let run = run();
+    let bytes = Report::load(&run, None).await.render(ReportFormat::Docx).unwrap();
+    let mut archive = unzip(bytes);
+
+    assert!(entry(&mut archive, "[Content_Types].xml").contains("wordprocessingml.document.main+xml"));
+    let document = entry(&mut archive, "word/document.xml");
+    assert!(document.contains(SUMMARY), "{}", document);
+    assert!(document.contains("&lt;script&gt;"));
+    assert!(entry(&mut archive, "docProps/core.xml").contains("<dc:language>de</dc:language>"));
+    for name in ["_rels/.rels", "word/_rels/document.xml.rels", "word/styles.xml"] {
+        assert!(archive.by_name(name).is_ok(), "no {}", name);
+    }
+}
+
+#[tokio::test]
+async fn epub_starts_with_a_stored_mimetype_and_bundles_images() {
+    let run = run();
+    let dir = media_dir();
+    let bytes = Report::load(&run, Some(&dir)).await.render(ReportFormat::Epub).unwrap();
+    let mut archive = unzip(bytes);
+
+    {
+        let mimetype = archive.by_index(0).unwrap();
+        assert_eq!(mimetype.name(), "mimetype");
+        assert_eq!(mimetype.compression(), CompressionMethod::Stored);
+    }
+    assert_eq!(entry(&mut archive, "mimetype"), "application/epub+zip");
+    assert!(entry(&mut archive, "META-INF/container.xml").contains("OEBPS/content.opf"));
+    let package = entry(&mut archive, "OEBPS/content.opf");
+    assert!(package.contains("<dc:language>de</dc:language>"), "{}", package);
+    assert!(package.contains("href=\"images/a.svg\" media-type=\"image/svg+xml\""), "{}", package);
+    assert_eq!(entry(&mut archive, "OEBPS/images/a.svg"), SVG);
+    let chapter = entry(&mut archive, "OEBPS/report.xhtml");
+    assert!(chapter.contains(SUMMARY));
+    assert!(chapter.contains("src=\"images/a.svg\""), "{}", chapter);
+}
+
+#[tokio::test]
+async fn unknown_formats_are_bad_requests() {
+    let config = mock_config(&research_mocks());
+    let history = RunHistory::open(&config.research_history_dir).await.unwrap();
+    let run = run();
+    history.save(&run).await.unwrap();
+    let server = TestServer::start(config).await;
+    let client = reqwest::Client::new();
+    let url = server.url(&format!("/research/{}/report", run.id));
+
+    let response = client.get(format!("{}?format=pdf", url)).send().await.unwrap();
+    assert_eq!(response.status(), 400);
+
+    let response = client.get(&url).header("accept", "application/pdf").send().await.unwrap();
+    assert_eq!(response.status(), 406);
+
+    let response = client.get(&url).header("accept", "text/plain, text/html").send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert!(response.headers()["content-type"].to_str().unwrap().starts_with("text/html"));
+}
@@ -0,0 +1,220 @@
+// The HTTP and SSE API, served on an ephemeral port with scripted providers behind it
+mod common;
+
+use std::time::Duration;
+
+use common::{mock_config, research_llm, research_mocks, research_search, TestServer, SUMMARY, TOPIC};
+use researcher::assistant::mock::{MockProviders, MockReply, MockSearch};
+use serde_json::{json, Value};
+
+#[tokio::test]
+async fn research_returns_the_summary() {
+    let server = TestServer::start(mock_config(&research_mocks())).await;
+
+    let response = reqwest::Client::new()
+        .post(server.url("/research"))
+        .json(&json!({ "topic": TOPIC }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["summary"], SUMMARY);
+    assert_eq!(body["status"], "Research completed");
+    assert!(body["id"].is_string());
+
+    // The run was saved and can be exported
+    let report = reqwest::get(server.url(&format!("/research/{}/report?format=md", body["id"].as_str().unwrap())))
+        .await
+        .unwrap();
+    assert_eq!(report.status(), 200);
+    assert!(report.text().await.unwrap().contains(SUMMARY));
+}
+
+#[tokio::test]
+async fn rate_limits_are_passed_on() {
+    let mocks = MockProviders::new(research_llm(), MockSearch::new().otherwise(MockReply::rate_limited(7)));
+    let server = TestServer::start(mock_config(&mocks)).await;
+
+    let response = reqwest::Client::new()
+        .post(server.url("/research"))
+        .json(&json!({ "topic": TOPIC }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 429);
+    assert_eq!(response.headers()["retry-after"], "7");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "rate_limited");
+    assert_eq!(body["error"]["provider"], "perplexity");
+}
+
+#[tokio::test]
+async fn invalid_options_are_rejected() {
+    let server = TestServer::start(mock_config(&research_mocks())).await;
+
+    let response = reqwest::Client::new()
+        .post(server.url("/research"))
+        .json(&json!({ "topic": TOPIC, "max_web_research_loops": 1000 }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 400);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "config");
+}
+
+#[tokio::test]
+async fn status_stream_reports_progress() {
+    let server = TestServer::start(mock_config(&research_mocks())).await;
+    let client = reqwest::Client::new();
+
+    let mut stream = client.get(server.url("/status")).send().await.unwrap();
+    assert_eq!(stream.status(), 200);
+    assert!(stream.headers()["content-type"].to_str().unwrap().starts_with("text/event-stream"));
+
+    let research = tokio::spawn(client.post(server.url("/research")).json(&json!({ "topic": TOPIC })).send());
+
+    let mut events = String::new();
+    let read = tokio::time::timeout(Duration::from_secs(10), async {
+        while let Some(chunk) = stream.chunk().await.unwrap() {
+            events.push_str(&String::from_utf8_lossy(&chunk));
+            if events.contains("\"phase\":\"complete\"") && events.contains(SUMMARY) {
+                break;
+            }
+        }
+    });
+    read.await.expect("the run completes on the status stream");
+    assert!(events.contains("\"phase\":\"perspectives\""));
+    assert_eq!(research.await.unwrap().unwrap().status(), 200);
+}
+
+#[tokio::test]
+async fn disconnecting_cancels_the_run() {
+    let mocks = MockProviders::new(research_llm().with_latency(Duration::from_secs(5)), research_search());
+    let server = TestServer::start(mock_config(&mocks)).await;
+    let client = reqwest::Client::new();
+
+    let mut stream = client.get(server.url("/status")).send().await.unwrap();
+    // The client gives up while the run waits on the model
+    let research = client
+        .post(server.url("/research"))
+        .json(&json!({ "topic": TOPIC }))
+        .timeout(Duration::from_millis(300))
+        .send()
+        .await;
+    assert!(research.unwrap_err().is_timeout());
+
+    let mut events = String::new();
+    let read = tokio::time::timeout(Duration::from_secs(3), async {
+        while let Some(chunk) = stream.chunk().await.unwrap() {
+            events.push_str(&String::from_utf8_lossy(&chunk));
+            if events.contains("\"code\":\"cancelled\"") {
+                break;
+            }
+        }
+    });
+    read.await.expect("the cancellation reaches the status stream");
+    assert!(events.contains("event: error"));
+}
+
+#[tokio::test]
+async fn config_reports_where_values_came_from() {
+    let server = TestServer::start(mock_config(&research_mocks())).await;
+
+    let body: Value = reqwest::get(server.url("/config")).await.unwrap().json().await.unwrap();
+
+    assert_eq!(body["research_mode"], "local");
+    assert!(body["provenance"].is_object());
+}
+
+#[tokio::test]
+async fn the_feed_uses_the_configured_language() {
+    let mut config = mock_config(&research_mocks());
+    config.language = "German".to_string();
+    let server = TestServer::start(config).await;
+
+    let feed = reqwest::get(server.url("/feed.xml")).await.unwrap().text().await.unwrap();
+
+    assert!(feed.contains("<language>de</language>"), "{}", feed);
+}
+
+#[tokio::test]
+async fn media_serves_audio_but_not_episode_metadata() {
+    let server = TestServer::start(mock_config(&research_mocks())).await;
+    let client = reqwest::Client::new();
+
+    let episode: Value = client.post(server.url("/episodes"))
+        .json(&json!({ "title": "Heat pumps", "summary": "They work." }))
+        .send().await.unwrap()
+        .json().await.unwrap();
+    let id = episode["id"].as_str().unwrap();
+    let upload = client.put(server.url(&format!("/episodes/{}/audio", id)))
+        .body("ID3 audio")
+        .send().await.unwrap();
+    assert_eq!(upload.status(), 200);
+
+    let audio = reqwest::get(server.url(&format!("/media/{}.mp3", id))).await.unwrap();
+    assert_eq!(audio.status(), 200);
+    assert_eq!(audio.text().await.unwrap(), "ID3 audio");
+
+    let metadata = reqwest::get(server.url(&format!("/media/{}.json", id))).await.unwrap();
+    assert_eq!(metadata.status(), 404);
+}
+
+#[tokio::test]
+async fn profiles_never_show_api_keys() {
+    let mut config = mock_config(&research_mocks());
+    let profile = serde_json::from_value(json!({
+        "description": "Groq with its own key",
+        "research_mode": "remote",
+        "groq_api_key": "gsk-secret",
+    }))
+    .unwrap();
+    config.profiles.insert("own-key".to_string(), profile);
+    let server = TestServer::start(config).await;
+
+    let response = reqwest::get(server.url("/profiles")).await.unwrap();
+    let text = response.text().await.unwrap();
+
+    assert!(!text.contains("gsk-secret"), "{}", text);
+    let body: Value = serde_json::from_str(&text).unwrap();
+    assert_eq!(body["profiles"]["own-key"]["groq_api_key"], "<set>");
+    assert_eq!(body["profiles"]["own-key"]["research_mode"], "remote");
+}
+
+#[tokio::test]
+async fn requests_can_only_lower_the_budget() {
+    let mut config = mock_config(&research_mocks());
+    config.max_cost_per_run = Some(1.0);
+    config.max_tokens_per_run = Some(100_000);
+    let server = TestServer::start(config).await;
+    let client = reqwest::Client::new();
+
+    for overrides in [
+        json!({ "max_cost_per_run": 1e9 }),
+        json!({ "max_cost_per_run": null }),
+        json!({ "max_tokens_per_run": 200_000 }),
+        json!({ "max_tokens_per_run": null }),
+    ] {
+        let response = client.post(server.url("/research"))
+            .json(&json!({ "topic": TOPIC, "config": overrides }))
+            .send()
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), 400, "{}", overrides);
+        let body: Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "config");
+    }
+
+    let response = client.post(server.url("/research"))
+        .json(&json!({ "topic": TOPIC, "config": { "max_cost_per_run": 0.5, "max_tokens_per_run": 50_000 } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+}
@@ -26,6 +26,13 @@ interface DebatePerspectives {
   topic: string;
 }
 
+interface ResearchErrorBody {
+  code: string;
+  message: string;
+  provider?: string;
+  retry_after_secs?: number;
+}
+
 interface StatusUpdate {
   phase: string;
   message: string;
@@ -35,6 +42,7 @@ interface StatusUpdate {
   chain_of_thought?: string;
   track?: string;
   perspectives?: DebatePerspectives;
+  error?: ResearchErrorBody;
 }
 
 interface StatusMessage {
@@ -319,6 +327,28 @@ export default function ResearchPage() {
     };
 
     eventSource.onerror = async (error) => {
+      // A failed run arrives as a typed `error` event with data, not as a connection error
+      if (error instanceof MessageEvent && error.data) {
+        try {
+          const data = JSON.parse(error.data) as StatusUpdate;
+          console.error("Research failed:", data.error);
+          setStatus(data.message);
+          setStatusHistory((prev) => [
+            ...prev,
+            {
+              phase: data.phase,
+              message: data.message,
+              timestamp: data.timestamp,
+              track: data.track,
+            },
+          ]);
+          setIsLoading(false);
+        } catch (parseError) {
+          console.error("Failed to parse SSE error event:", parseError, error.data);
+        }
+        return;
+      }
+
       console.error("SSE error:", error);
       eventSource.close();
       setStatusSource(null);